@@ -0,0 +1,118 @@
+/* Generated with cbindgen from src/ffi.rs; see cbindgen.toml. */
+
+#ifndef HAPHAZARD_H
+#define HAPHAZARD_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Frees a foreign object, with a context pointer for whatever state that needs.
+ */
+typedef struct AbiDeleter {
+  void *context;
+  void (*delete_object)(void *context, void *object);
+} AbiDeleter;
+
+typedef struct HazPtrDomain HazPtrDomain;
+
+typedef struct HazPtrHolder HazPtrHolder;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a new domain. It lives until passed to `haphazard_domain_free`.
+ */
+HazPtrDomain *haphazard_domain_new(void);
+
+/**
+ * The global domain, which is never freed.
+ */
+const HazPtrDomain *haphazard_domain_global(void);
+
+/**
+ * Reclaim every object retired on `domain`, and free it.
+ *
+ * # Safety
+ *
+ * `domain` must have come from `haphazard_domain_new`, and no holder for it may be left, nor
+ * any object of it be retired after this.
+ */
+void haphazard_domain_free(HazPtrDomain *domain);
+
+/**
+ * Retire `object` on `domain`, to be freed with `deleter` once no holder protects it anymore.
+ *
+ * # Safety
+ *
+ * `domain` must be valid. `object` must no longer be reachable by new readers, must not have
+ * been retired before, and must be freed by `deleter`. `deleter.context` must stay valid until
+ * the deleter has run, and `deleter` may be run on any thread.
+ */
+void haphazard_retire(const HazPtrDomain *domain, void *object, AbiDeleter deleter);
+
+/**
+ * Reclaim the objects retired on `domain` that aren't protected, and return how many there
+ * were.
+ *
+ * # Safety
+ *
+ * `domain` must be valid.
+ */
+size_t haphazard_eager_reclaim(const HazPtrDomain *domain);
+
+/**
+ * Create a holder for `domain`. It protects at most one object at a time, and must be freed
+ * with `haphazard_holder_free`.
+ *
+ * # Safety
+ *
+ * `domain` must be valid, and stay valid until the holder is freed.
+ */
+HazPtrHolder *haphazard_holder_new(const HazPtrDomain *domain);
+
+/**
+ * Release whatever `holder` protects, and free it.
+ *
+ * # Safety
+ *
+ * `holder` must have come from `haphazard_holder_new`, and not be used after this.
+ */
+void haphazard_holder_free(HazPtrHolder *holder);
+
+/**
+ * Protect the object in `src` with `holder`, in place of whatever it protected before, and
+ * return it. The object stays valid until the holder is reset, protects another object, or is
+ * freed. Returns null, with nothing protected, if `src` holds null.
+ *
+ * `src` is an atomic slot, passed as a plain pointer to it since C++ has no `_Atomic`. In C,
+ * pass an `_Atomic(void *)` as `(void *const *)&slot`; in C++, pass a `std::atomic<void *>`
+ * as `reinterpret_cast<void *const *>(&slot)`.
+ *
+ * # Safety
+ *
+ * `holder` must be valid and not in use by another thread. `src` must be valid, and only ever
+ * be accessed atomically. The objects in it must only be freed by retiring them on the
+ * holder's domain.
+ */
+void *haphazard_holder_protect(HazPtrHolder *holder, void *const *src);
+
+/**
+ * Stop protecting whatever `holder` protects.
+ *
+ * # Safety
+ *
+ * `holder` must be valid and not in use by another thread.
+ */
+void haphazard_holder_reset(HazPtrHolder *holder);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HAPHAZARD_H */
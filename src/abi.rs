@@ -0,0 +1,139 @@
+//! A C-compatible retire interface, for code on the other side of a dynamic-library boundary.
+//!
+//! Trait objects such as `&dyn Deleter` and `*mut dyn Drop` have no stable layout, so a plugin
+//! built separately from its host (possibly against a different version of this crate, or by a
+//! different compiler) can't safely pass them to the host's domain. Instead, the host hands out
+//! an [`AbiDomain`], and plugins retire objects through it with an [`AbiDeleter`]. Both are
+//! `#[repr(C)]` and only contain raw pointers and `extern "C"` functions.
+//!
+//! Readers protect foreign objects by the same address that is passed to
+//! [`AbiDomain::retire`], exactly as for objects retired natively.
+
+use crate::{deleters, HazPtrDomain};
+use std::ffi::c_void;
+
+/// Frees a foreign object, with a context pointer for whatever state that needs (such as the
+/// allocator the object came from).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AbiDeleter {
+    /// Passed to `delete` as its first argument.
+    pub context: *mut c_void,
+    /// Frees the object passed as the second argument.
+    pub delete: unsafe extern "C" fn(context: *mut c_void, object: *mut c_void),
+}
+
+/// A handle to a [`HazPtrDomain`] that can be passed across a dynamic-library boundary.
+///
+/// Obtained with [`HazPtrDomain::abi`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AbiDomain {
+    domain: *const c_void,
+    retire: unsafe extern "C" fn(domain: *const c_void, object: *mut c_void, deleter: AbiDeleter),
+    eager_reclaim: unsafe extern "C" fn(domain: *const c_void) -> usize,
+}
+
+// Safety: the handle only refers to a 'static HazPtrDomain, which is Sync.
+unsafe impl Send for AbiDomain {}
+unsafe impl Sync for AbiDomain {}
+
+impl AbiDomain {
+    /// Retire `object`, to be freed with `deleter` once no reader protects it anymore.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`](crate::HazPtrObject::retire): `object` must no longer
+    /// be reachable by new readers, must not have been retired before, and must be freed by
+    /// `deleter`. `deleter.context` must stay valid until the deleter has run, and `deleter`
+    /// may be run on any thread.
+    pub unsafe fn retire(&self, object: *mut c_void, deleter: AbiDeleter) {
+        // Safety: by the safety contract of retire.
+        unsafe { (self.retire)(self.domain, object, deleter) }
+    }
+
+    /// See [`HazPtrDomain::eager_reclaim`].
+    pub fn eager_reclaim(&self) -> usize {
+        // Safety: domain and eager_reclaim came from HazPtrDomain::abi together.
+        unsafe { (self.eager_reclaim)(self.domain) }
+    }
+}
+
+impl HazPtrDomain {
+    /// Get a handle to this domain that plugins can retire objects through.
+    pub fn abi(&'static self) -> AbiDomain {
+        AbiDomain {
+            domain: self as *const Self as *const c_void,
+            retire: abi_retire,
+            eager_reclaim: abi_eager_reclaim,
+        }
+    }
+}
+
+// A foreign object together with the deleter that frees it.
+struct Foreign {
+    object: *mut c_void,
+    deleter: AbiDeleter,
+}
+
+impl Drop for Foreign {
+    fn drop(&mut self) {
+        // Safety: by the safety contract of AbiDomain::retire, the deleter frees the object,
+        // and may be called on any thread.
+        unsafe { (self.deleter.delete)(self.deleter.context, self.object) };
+    }
+}
+
+unsafe extern "C" fn abi_retire(domain: *const c_void, object: *mut c_void, deleter: AbiDeleter) {
+    // Safety: domain came from HazPtrDomain::abi, which takes a 'static domain.
+    let domain = unsafe { &*(domain as *const HazPtrDomain) };
+    let foreign = Box::into_raw(Box::new(Foreign { object, deleter }));
+    // Readers protect the foreign object itself, not our wrapper around it.
+    domain.retire_at(object as *mut u8, foreign, &deleters::drop_box);
+}
+
+unsafe extern "C" fn abi_eager_reclaim(domain: *const c_void) -> usize {
+    // Safety: domain came from HazPtrDomain::abi, which takes a 'static domain.
+    let domain = unsafe { &*(domain as *const HazPtrDomain) };
+    domain.eager_reclaim(false)
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::HazPtrHolder;
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn free_u64(context: *mut c_void, object: *mut c_void) {
+        assert_eq!(context.addr(), 0x1234);
+        // Safety: the test only retires Box<u64>s with this deleter.
+        drop(unsafe { Box::from_raw(object as *mut u64) });
+        FREED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn foreign_objects_honor_hazards() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let abi = DOMAIN.abi();
+
+        let x = AtomicPtr::new(Box::into_raw(Box::new(42u64)));
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x holds a valid Box, only freed by retiring it on DOMAIN.
+        assert_eq!(unsafe { h.load(&x) }, Some(&42));
+
+        let deleter = AbiDeleter {
+            context: std::ptr::without_provenance_mut(0x1234),
+            delete: free_u64,
+        };
+        // Safety: x is not used after this, and free_u64 matches how it was allocated.
+        unsafe { abi.retire(x.load(Ordering::SeqCst) as *mut c_void, deleter) };
+        assert_eq!(abi.eager_reclaim(), 0);
+        assert_eq!(FREED.load(Ordering::SeqCst), 0);
+
+        h.reset();
+        assert_eq!(abi.eager_reclaim(), 1);
+        assert_eq!(FREED.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sync::AtomicMut;
+use crate::{
+    deleters, HazPtrDomain, HazPtrHolder, ProtectSource, SafeProtectSource, SHARED_DOMAIN,
+};
+use std::sync::Arc;
+
+/// An atomically replaceable `Option<Arc<T>>`.
+///
+/// Readers can either [`peek`](AtomicArc::peek) at the current value under a hazard pointer,
+/// which costs no more than loading from an [`AtomicBox`](crate::AtomicBox) and leaves the
+/// reference count alone, or [`load_full`](AtomicArc::load_full) an `Arc` of their own to keep
+/// the value for as long as they like.
+///
+/// The box holds one strong reference to its value. When the value is replaced, that reference
+/// is retired rather than dropped, so it outlives any reader that is still peeking at it.
+///
+/// ```
+/// use haphazard::{AtomicArc, HazPtrHolder};
+/// use std::sync::Arc;
+///
+/// let config = AtomicArc::new(Some(Arc::new(String::from("v1"))));
+/// let kept = config.load_full().unwrap();
+/// config.store(Some(Arc::new(String::from("v2"))));
+/// let mut h = HazPtrHolder::default();
+/// assert_eq!(config.peek(&mut h).unwrap(), "v2");
+/// assert_eq!(*kept, "v1");
+/// ```
+pub struct AtomicArc<T: Send + Sync + 'static> {
+    ptr: AtomicPtr<T>,
+    domain: &'static HazPtrDomain,
+}
+
+// Owns the box's reference to a retired value.
+struct ArcOwner<T>(Arc<T>);
+
+impl<T> Drop for ArcOwner<T> {
+    fn drop(&mut self) {}
+}
+
+fn into_ptr<T>(value: Option<Arc<T>>) -> *mut T {
+    value.map_or(std::ptr::null_mut(), |arc| Arc::into_raw(arc) as *mut T)
+}
+
+impl<T: Send + Sync + 'static> AtomicArc<T> {
+    /// Create an `AtomicArc` whose replaced values are retired on the global domain.
+    pub fn new(value: Option<Arc<T>>) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, value)
+    }
+
+    /// Create an `AtomicArc` whose replaced values are retired on `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain, value: Option<Arc<T>>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(into_ptr(value)),
+            domain,
+        }
+    }
+
+    /// The domain replaced values are retired on.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Protect the current value with `holder`, and return a reference to it, or `None` if
+    /// there is none.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this box's domain.
+    pub fn peek<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l T> {
+        assert!(
+            std::ptr::eq(holder.domain, self.domain),
+            "holder is for a different domain"
+        );
+        // Safety: the pointer is always null or holds the box's strong reference, which is only
+        // given up by retiring it in the holder's domain.
+        unsafe { holder.load(&self.ptr) }
+    }
+
+    /// Get an `Arc` of the current value, or `None` if there is none.
+    pub fn load_full(&self) -> Option<Arc<T>> {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        let value: *const T = self.peek(&mut holder)?;
+        // Safety: value came from Arc::into_raw, and the box's strong reference can't be dropped
+        // while the holder protects value.
+        unsafe {
+            Arc::increment_strong_count(value);
+            Some(Arc::from_raw(value))
+        }
+    }
+
+    /// Put `value` in the box, and retire the reference to the value it replaced.
+    pub fn store(&self, value: Option<Arc<T>>) {
+        let old = self.ptr.swap(into_ptr(value), Ordering::SeqCst);
+        // Safety: old is no longer reachable through the box.
+        unsafe { self.retire(old) };
+    }
+
+    /// Retire the box's reference to `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null, or a value the box held that is no longer reachable through it.
+    unsafe fn retire(&self, ptr: *mut T) {
+        if ptr.is_null() {
+            return;
+        }
+        // Safety: ptr holds the strong reference the box owned.
+        let owner = ArcOwner(unsafe { Arc::from_raw(ptr) });
+        let owner = Box::into_raw(Box::new(owner));
+        // Readers protect the value itself, not the owner that wraps the reference to it.
+        self.domain
+            .retire_at(ptr as *mut u8, owner, &deleters::drop_box);
+    }
+}
+
+impl<T: Send + Sync + 'static> ProtectSource for AtomicArc<T> {
+    type Target = T;
+
+    unsafe fn load_with<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l T> {
+        self.peek(holder)
+    }
+}
+
+// Safety: AtomicArc::peek is safe, and checks the holder's domain.
+unsafe impl<T: Send + Sync + 'static> SafeProtectSource for AtomicArc<T> {}
+
+impl<T: Send + Sync + 'static> Drop for AtomicArc<T> {
+    fn drop(&mut self) {
+        let ptr = self.ptr.load_mut();
+        // Safety: we had exclusive access, and are going away, so ptr is no longer reachable.
+        unsafe { self.retire(ptr) };
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn peek_and_load_full() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let value = || Some(Arc::new(CountDrops(Arc::clone(&drops))));
+        let x = AtomicArc::with_domain(&DOMAIN, value());
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let peeked = x.peek(&mut h).unwrap() as *const CountDrops;
+        let full = x.load_full().unwrap();
+        assert!(std::ptr::eq(peeked, &*full));
+        // Peeking leaves the count alone.
+        assert_eq!(Arc::strong_count(&full), 2);
+
+        x.store(value());
+        DOMAIN.eager_reclaim(false);
+        // Both the hazard and the loaded Arc keep the old value alive.
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(Arc::strong_count(&full), 1);
+        drop(full);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        x.store(None);
+        assert!(x.peek(&mut h).is_none());
+        assert!(x.load_full().is_none());
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        drop(x);
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+    }
+}
@@ -0,0 +1,748 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sync::AtomicMut;
+use crate::tagged;
+use crate::{
+    HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, ProtectSource, Protected,
+    ProtectedRef, SafeProtectSource, SHARED_DOMAIN,
+};
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// An owned, heap-allocated object that readers can access through hazard pointers.
+///
+/// An `AtomicBox` upholds everything [`HazPtrHolder::load`] requires of its source: it is either
+/// empty or points to a valid `Box`ed object of its domain, which is only ever deallocated by
+/// retiring it. Reading through it is therefore safe.
+///
+/// Dropping an `AtomicBox` retires the object it holds, so a struct with `AtomicBox` fields
+/// needs no manual cleanup. Since the object is retired rather than freed, this is fine even if
+/// readers still have it protected. Retired objects may be dropped on any thread, hence the
+/// `T: Send` bound:
+///
+/// ```compile_fail
+/// use haphazard::{AtomicBox, HazPtrObjectWrapper};
+/// use std::rc::Rc;
+///
+/// let x = AtomicBox::new(HazPtrObjectWrapper::with_default_domain(Rc::new(1)));
+/// ```
+///
+/// While [`AtomicBox::swap_with`] exchanges the contents of two boxes, it marks each with a tag
+/// in the low bit of its pointer. Reads ignore the tag, and writes wait for it to be cleared.
+pub struct AtomicBox<T: HazPtrObject + Send> {
+    ptr: AtomicPtr<T>,
+    domain: &'static HazPtrDomain,
+}
+
+impl<T: HazPtrObject + Send> AtomicBox<T> {
+    /// Create an `AtomicBox` for an object of the global domain.
+    ///
+    /// # Panics
+    ///
+    /// If `value` does not belong to the global domain.
+    pub fn new(value: T) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, value)
+    }
+
+    /// Create an `AtomicBox` for an object of `domain`.
+    ///
+    /// # Panics
+    ///
+    /// If `value` does not belong to `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain, value: T) -> Self {
+        assert!(
+            std::ptr::eq(value.domain(), domain),
+            "object belongs to a different domain"
+        );
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            domain,
+        }
+    }
+
+    /// Create an empty `AtomicBox` for objects of the global domain.
+    pub fn empty() -> Self {
+        Self::empty_with_domain(&SHARED_DOMAIN)
+    }
+
+    /// Create an empty `AtomicBox` for objects of `domain`.
+    pub fn empty_with_domain(domain: &'static HazPtrDomain) -> Self {
+        Self {
+            ptr: AtomicPtr::new(std::ptr::null_mut()),
+            domain,
+        }
+    }
+
+    /// The domain the objects in this box belong to.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Protect the current object with `holder`, and return a reference to it, or `None` if the
+    /// box is empty.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this box's domain.
+    pub fn load<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l T> {
+        assert!(
+            std::ptr::eq(holder.domain, self.domain),
+            "holder is for a different domain"
+        );
+        // Safety: the untagged pointer is always a valid Box, only deallocated by retiring it
+        // in the holder's domain.
+        unsafe { holder.load_tagged(&self.ptr) }.0
+    }
+
+    /// Like [`AtomicBox::load`], but returns a guard that resets `holder` when dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this box's domain.
+    pub fn protect<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<ProtectedRef<'l, T>> {
+        assert!(
+            std::ptr::eq(holder.domain, self.domain),
+            "holder is for a different domain"
+        );
+        // Safety: as in load.
+        let ptr = NonNull::from(unsafe { holder.load_tagged(&self.ptr) }.0?);
+        Some(ProtectedRef { holder, ptr })
+    }
+
+    /// Protect the current object with a holder of its own, which the returned guard keeps, or
+    /// return `None` if the box is empty.
+    pub fn load_owned(&self) -> Option<Protected<T>> {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        // Safety: as in load.
+        let ptr = NonNull::from(unsafe { holder.load_tagged(&self.ptr) }.0?);
+        Some(Protected { holder, ptr })
+    }
+
+    /// Get a clone of the value the current object wraps, or `None` if the box is empty.
+    ///
+    /// The object is only protected while it is cloned, so this is the simplest way to read
+    /// from a box, at the cost of a clone.
+    ///
+    /// ```
+    /// use haphazard::{AtomicBox, HazPtrObjectWrapper};
+    ///
+    /// let x = AtomicBox::new(HazPtrObjectWrapper::with_default_domain(String::from("a")));
+    /// let a: String = x.load_cloned().unwrap();
+    /// x.store(HazPtrObjectWrapper::with_default_domain(String::from("b")));
+    /// assert_eq!(a, "a");
+    /// ```
+    pub fn load_cloned(&self) -> Option<T::Target>
+    where
+        T: Deref,
+        T::Target: Clone,
+    {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        self.load(&mut holder)
+            .map(|object| T::Target::clone(object))
+    }
+
+    /// Whether the box currently holds no object.
+    pub fn is_empty(&self) -> bool {
+        tagged::untagged(self.ptr.load(Ordering::SeqCst)).is_null()
+    }
+
+    /// Put `value` in the box, and return the object it replaced, or null if it was empty. The
+    /// old object is no longer reachable through the box but may still be protected by readers.
+    pub(crate) fn replace(&self, value: T) -> *mut T {
+        assert!(
+            std::ptr::eq(value.domain(), self.domain),
+            "object belongs to a different domain"
+        );
+        self.exchange(Box::into_raw(Box::new(value)))
+    }
+
+    /// Put `new` in the box once no swap is marking it, and return what it replaced.
+    fn exchange(&self, new: *mut T) -> *mut T {
+        let mut current = self.ptr.load(Ordering::SeqCst);
+        loop {
+            if tagged::tag(current) != 0 {
+                std::hint::spin_loop();
+                current = self.ptr.load(Ordering::SeqCst);
+                continue;
+            }
+            match self
+                .ptr
+                .compare_exchange_weak(current, new, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(old) => break old,
+                Err(now) => current = now,
+            }
+        }
+    }
+
+    /// Mark the box as being swapped, once no other swap is, and return its object.
+    fn mark(&self) -> *mut T {
+        let mut current = self.ptr.load(Ordering::SeqCst);
+        loop {
+            if tagged::tag(current) != 0 {
+                std::hint::spin_loop();
+                current = self.ptr.load(Ordering::SeqCst);
+                continue;
+            }
+            match self.ptr.compare_exchange_weak(
+                current,
+                tagged::with_tag(current, 1),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(old) => break old,
+                Err(now) => current = now,
+            }
+        }
+    }
+
+    /// Exchange the objects in this box and `other`, neither of which needs to be borrowed
+    /// exclusively.
+    ///
+    /// Each box changes from one object to the other in a single step, and readers of either box
+    /// never wait. The two boxes don't change at the same instant, though: a reader that loads
+    /// both boxes while the swap is underway may find the same object in both. Stores and takes
+    /// on either box wait for the swap to finish, and two swaps that share a box take turns, in
+    /// the order of the boxes' addresses so that they can't wait for each other.
+    ///
+    /// # Panics
+    ///
+    /// If the two boxes are for different domains, or `T`'s alignment leaves no room for a tag.
+    pub fn swap_with(&self, other: &Self) {
+        assert!(
+            std::ptr::eq(self.domain, other.domain),
+            "boxes are for different domains"
+        );
+        assert_ne!(
+            tagged::mask::<T>(),
+            0,
+            "T is not aligned enough to be tagged"
+        );
+        if std::ptr::eq(self, other) {
+            return;
+        }
+        let (first, second) = if (self as *const Self) < (other as *const Self) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let a = first.mark();
+        let b = second.mark();
+        // Storing the untagged pointers also lifts the marks.
+        first.ptr.store(b, Ordering::SeqCst);
+        second.ptr.store(a, Ordering::SeqCst);
+    }
+
+    /// Put `value` in the box, and retire the object it replaced on the box's domain.
+    ///
+    /// Readers that still have the old object protected keep it alive until they let go.
+    ///
+    /// # Panics
+    ///
+    /// If `value` does not belong to this box's domain.
+    pub fn store(&self, value: T) {
+        let old = self.replace(value);
+        if !old.is_null() {
+            // Safety:
+            //  1. old came from a Box, and has not been retired yet.
+            //  2. It is no longer reachable through the box.
+            //  3. drop_box is the right deleter for a Box.
+            unsafe { old.retire() };
+        }
+    }
+
+    /// Replace the object in the box with one built from it by `f` (read-copy-update), and retire
+    /// the object it replaced on the box's domain.
+    ///
+    /// `f` is called with the current object while it is protected, and may return `None` to
+    /// leave the box as it is. If the box changes before the new object is put in, the new object
+    /// is dropped and `f` is called again with the object that replaced it. Returns whether the
+    /// box was updated, which it isn't if `f` returned `None` or the box is empty.
+    ///
+    /// # Panics
+    ///
+    /// If an object returned by `f` does not belong to this box's domain.
+    pub fn fetch_update<F>(&self, mut f: F) -> bool
+    where
+        F: FnMut(&T) -> Option<T>,
+    {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        loop {
+            let Some(current) = self.load(&mut holder) else {
+                return false;
+            };
+            let Some(value) = f(current) else {
+                return false;
+            };
+            assert!(
+                std::ptr::eq(value.domain(), self.domain),
+                "object belongs to a different domain"
+            );
+            let current = current as *const T as *mut T;
+            let new = Box::into_raw(Box::new(value));
+            // A box that is being swapped has a tagged pointer, so it can't change here until
+            // the swap is done.
+            if self
+                .ptr
+                .compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                // Safety: as in store.
+                unsafe { current.retire() };
+                return true;
+            }
+            // Safety: new was never shared.
+            drop(unsafe { Box::from_raw(new) });
+        }
+    }
+
+    /// Empty the box, and return the object it held, or `None` if it was already empty.
+    ///
+    /// The object is no longer reachable through the box, but readers may still have it
+    /// protected, so it must be retired on [`AtomicBox::domain`] rather than freed, with
+    /// [`deleters::drop_box`] as its deleter. Otherwise, it is leaked.
+    #[must_use = "the taken object is leaked unless it is retired"]
+    pub fn take(&self) -> Option<NonNull<T>> {
+        NonNull::new(self.exchange(std::ptr::null_mut()))
+    }
+
+    /// Take this box apart into its current object pointer and its domain, without retiring the
+    /// object.
+    ///
+    /// This is meant for passing an `AtomicBox` through FFI or type-erased storage. Use
+    /// [`AtomicBox::from_raw`] to put it back together, or the object is leaked.
+    pub fn into_raw(self) -> RawAtomicBox<T> {
+        let this = ManuallyDrop::new(self);
+        RawAtomicBox {
+            ptr: this.ptr.load(Ordering::SeqCst),
+            domain: this.domain,
+        }
+    }
+
+    /// Reassemble an `AtomicBox` from the parts returned by [`AtomicBox::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been returned by `AtomicBox::<T>::into_raw`, and must be passed to
+    /// `from_raw` at most once.
+    pub unsafe fn from_raw(raw: RawAtomicBox<T>) -> Self {
+        Self {
+            ptr: AtomicPtr::new(raw.ptr),
+            // Safety: the domain of an AtomicBox is always 'static.
+            domain: unsafe { &*raw.domain },
+        }
+    }
+
+    /// Get a mutable reference to the current object, or `None` if the box is empty or a reader
+    /// still has the object protected.
+    ///
+    /// Borrowing the box exclusively keeps readers from protecting its object anew, but readers
+    /// that protected it earlier may still be using it, since protections don't borrow the box.
+    /// Checking for them takes a scan of the domain's hazards, so this is best called once
+    /// readers are known to be done, rather than in a loop.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        let ptr = self.unprotected()?;
+        // Safety: ptr came from a Box, is only reachable through the box, which we borrow
+        // exclusively, and no reader has it protected anymore.
+        Some(unsafe { &mut *ptr })
+    }
+
+    /// Take the current object out of the box without retiring it, or `None` if the box is
+    /// empty.
+    ///
+    /// Like [`AtomicBox::get_mut`], this does not wait for readers that still have the object
+    /// protected, but gives the box back as `Err` instead.
+    pub fn into_inner(self) -> Result<Option<Box<T>>, Self> {
+        let ptr = tagged::untagged(self.ptr.load(Ordering::SeqCst));
+        if !ptr.is_null() && self.domain.is_guarded(ptr.cast()) {
+            return Err(self);
+        }
+        let _ = ManuallyDrop::new(self);
+        // Safety: as in get_mut, and the box is gone without retiring ptr.
+        Ok((!ptr.is_null()).then(|| unsafe { Box::from_raw(ptr) }))
+    }
+
+    /// The current object, if there is one and no holder of the box's domain protects it.
+    fn unprotected(&mut self) -> Option<*mut T> {
+        let ptr = tagged::untagged(self.ptr.load_mut());
+        if ptr.is_null() || self.domain.is_guarded(ptr.cast()) {
+            return None;
+        }
+        Some(ptr)
+    }
+}
+
+/// The parts of an [`AtomicBox`], as returned by [`AtomicBox::into_raw`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct RawAtomicBox<T> {
+    /// The current object, allocated with `Box`, or null if the box was empty.
+    pub ptr: *mut T,
+    /// The domain the object belongs to.
+    pub domain: *const HazPtrDomain,
+}
+
+impl<T> Clone for RawAtomicBox<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RawAtomicBox<T> {}
+
+impl<T: HazPtrObject + Send> Drop for AtomicBox<T> {
+    fn drop(&mut self) {
+        let ptr = self.ptr.load_mut();
+        if ptr.is_null() {
+            return;
+        }
+        // Safety:
+        //  1. ptr came from a Box, so is valid.
+        //  2. We had exclusive access, and are going away, so ptr is no longer reachable.
+        //  3. drop_box is the right deleter for a Box.
+        unsafe { ptr.retire() };
+    }
+}
+
+// Safety: an AtomicBox hands out &T to any thread that reads it, and drops the T on whichever
+// thread reclaims it.
+impl<T: HazPtrObject + Send> ProtectSource for AtomicBox<T> {
+    type Target = T;
+
+    unsafe fn load_with<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l T> {
+        self.load(holder)
+    }
+}
+
+// Safety: AtomicBox::load is safe, and checks the holder's domain.
+unsafe impl<T: HazPtrObject + Send> SafeProtectSource for AtomicBox<T> {}
+
+unsafe impl<T: HazPtrObject + Send + Sync> Send for AtomicBox<T> {}
+unsafe impl<T: HazPtrObject + Send + Sync> Sync for AtomicBox<T> {}
+
+/// An [`AtomicBox`] for unsized values, such as trait objects.
+///
+/// A pointer to an unsized value is twice as wide as an `AtomicPtr` can hold, so the value is
+/// boxed once more, and the inner `Box` drops it through its vtable when the outer one is
+/// reclaimed.
+///
+/// ```
+/// use haphazard::{AtomicDynBox, HazPtrHolder};
+///
+/// let x: AtomicDynBox<dyn Fn() -> u32 + Send + Sync> = AtomicDynBox::new(Box::new(|| 1));
+/// let mut h = HazPtrHolder::default();
+/// assert_eq!(x.load(&mut h)(), 1);
+/// x.store(Box::new(|| 2));
+/// assert_eq!(x.load(&mut h)(), 2);
+/// ```
+pub struct AtomicDynBox<T: ?Sized + Send + 'static> {
+    inner: AtomicBox<HazPtrObjectWrapper<Box<T>>>,
+}
+
+impl<T: ?Sized + Send + 'static> AtomicDynBox<T> {
+    /// Create an `AtomicDynBox` in the global domain.
+    pub fn new(value: Box<T>) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, value)
+    }
+
+    /// Create an `AtomicDynBox` in `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain, value: Box<T>) -> Self {
+        Self {
+            inner: AtomicBox::with_domain(domain, HazPtrObjectWrapper::with_domain(domain, value)),
+        }
+    }
+
+    /// The domain the values in this box are retired on.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.inner.domain()
+    }
+
+    /// Protect the current value with `holder`, and return a reference to it.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this box's domain.
+    pub fn load<'l>(&self, holder: &'l mut HazPtrHolder) -> &'l T {
+        self.inner
+            .load(holder)
+            .expect("AtomicDynBox is never empty")
+    }
+
+    /// Put `value` in the box, and retire the value it replaced.
+    pub fn store(&self, value: Box<T>) {
+        self.inner
+            .store(HazPtrObjectWrapper::with_domain(self.domain(), value));
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::HazPtrObjectWrapper;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let raw = x.into_raw();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        let erased = raw.ptr as *mut ();
+
+        // Safety: raw came from into_raw, and is only used once.
+        let x = unsafe {
+            AtomicBox::<HazPtrObjectWrapper<CountDrops>>::from_raw(RawAtomicBox {
+                ptr: erased as *mut _,
+                domain: raw.domain,
+            })
+        };
+        assert!(std::ptr::eq(x.domain(), &DOMAIN));
+        drop(x);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn get_mut_refuses_protected_objects() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let reader = x.load_owned().unwrap();
+        assert!(x.get_mut().is_none());
+        let Err(mut x) = x.into_inner() else {
+            panic!("into_inner took a protected object");
+        };
+        drop(reader);
+
+        let fresh = Arc::new(AtomicUsize::new(0));
+        x.get_mut().unwrap().0 = Arc::clone(&fresh);
+
+        // The object is dropped right away, rather than retired.
+        drop(x.into_inner().ok().unwrap().unwrap());
+        assert_eq!(fresh.load(Ordering::SeqCst), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert!(
+            AtomicBox::<HazPtrObjectWrapper<CountDrops>>::empty_with_domain(&DOMAIN)
+                .into_inner()
+                .ok()
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn fields_are_retired_on_drop() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        struct Config {
+            name: AtomicBox<HazPtrObjectWrapper<CountDrops>>,
+            limits: AtomicBox<HazPtrObjectWrapper<CountDrops>>,
+        }
+        let drops = Arc::new(AtomicUsize::new(0));
+        let field = || {
+            AtomicBox::with_domain(
+                &DOMAIN,
+                HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+            )
+        };
+        let config = Config {
+            name: field(),
+            limits: field(),
+        };
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let _name = config.name.load(&mut h).unwrap();
+        drop(config);
+        // The protected field must survive until the holder lets go of it.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(h);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn trait_objects() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        trait Named {
+            fn name(&self) -> &str;
+        }
+        struct Named1(#[allow(dead_code)] CountDrops);
+        impl Named for Named1 {
+            fn name(&self) -> &str {
+                "one"
+            }
+        }
+        struct Named2;
+        impl Named for Named2 {
+            fn name(&self) -> &str {
+                "two"
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x: AtomicDynBox<dyn Named + Send> =
+            AtomicDynBox::with_domain(&DOMAIN, Box::new(Named1(CountDrops(Arc::clone(&drops)))));
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let one = x.load(&mut h);
+        x.store(Box::new(Named2));
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(one.name(), "one");
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        assert_eq!(x.load(&mut h).name(), "two");
+        DOMAIN.eager_reclaim(false);
+        // Dropped through its vtable.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn store_retires_old_value() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let value = || HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops)));
+        let x = AtomicBox::with_domain(&DOMAIN, value());
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let _old = x.load(&mut h).unwrap();
+        x.store(value());
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(x);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fetch_update() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let x = AtomicBox::with_domain(&DOMAIN, HazPtrObjectWrapper::with_domain(&DOMAIN, 0));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        assert!(x.fetch_update(|v| Some(HazPtrObjectWrapper::with_domain(
+                            &DOMAIN,
+                            **v + 1
+                        ))));
+                    }
+                });
+            }
+        });
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        assert_eq!(**x.load(&mut h).unwrap(), 4000);
+        assert!(!x.fetch_update(|_| None));
+        assert_eq!(**x.load(&mut h).unwrap(), 4000);
+
+        let empty = AtomicBox::<HazPtrObjectWrapper<i32>>::empty_with_domain(&DOMAIN);
+        assert!(!empty.fetch_update(|_| unreachable!()));
+    }
+
+    #[test]
+    fn empty_and_take() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let value = || HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops)));
+        let x = AtomicBox::empty_with_domain(&DOMAIN);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        assert!(x.is_empty());
+        assert!(x.load(&mut h).is_none());
+        assert!(x.load_owned().is_none());
+        assert!(x.take().is_none());
+
+        // Storing into an empty box has nothing to retire.
+        x.store(value());
+        assert!(!x.is_empty());
+        assert!(x.load(&mut h).is_some());
+        let taken = x.take().expect("not empty");
+        assert!(x.is_empty());
+        assert!(x.load(&mut h).is_none());
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // Safety: taken came from the box, is no longer reachable, and is retired only once.
+        unsafe { taken.as_ptr().retire() };
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        // Dropping an empty box retires nothing.
+        drop(x);
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+    }
+
+    #[test]
+    fn swap_shared_boxes() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let boxes: Arc<Vec<_>> = Arc::new(
+            (0..4)
+                .map(|i| {
+                    AtomicBox::with_domain(&DOMAIN, HazPtrObjectWrapper::with_domain(&DOMAIN, i))
+                })
+                .collect(),
+        );
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        boxes[0].swap_with(&boxes[1]);
+        boxes[1].swap_with(&boxes[1]);
+        assert_eq!(**boxes[0].load(&mut h).unwrap(), 1);
+        assert_eq!(**boxes[1].load(&mut h).unwrap(), 0);
+
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                let boxes = Arc::clone(&boxes);
+                std::thread::spawn(move || {
+                    let mut h = HazPtrHolder::for_domain(&DOMAIN);
+                    for i in 0..1000 {
+                        let (a, b) = ((t + i) % 4, (t + 2 * i + 1) % 4);
+                        boxes[a].swap_with(&boxes[b]);
+                        assert!(**boxes[b].load(&mut h).unwrap() < 4);
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // Every object is still in exactly one box.
+        let mut values: Vec<_> = boxes.iter().map(|b| **b.load(&mut h).unwrap()).collect();
+        values.sort_unstable();
+        assert_eq!(values, [0, 1, 2, 3]);
+        assert!(boxes.iter().all(|b| !b.is_empty()));
+    }
+
+    #[test]
+    fn owned_protection() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let load = || {
+            let x = AtomicBox::with_domain(
+                &DOMAIN,
+                HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+            );
+            // The guard outlives both the box and this closure.
+            x.load_owned().unwrap()
+        };
+        let protected = load();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        let h = protected.into_holder();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(h);
+    }
+}
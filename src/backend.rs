@@ -0,0 +1,226 @@
+//! Pluggable reclamation backends behind a common [`Holder`] front-end.
+//!
+//! A [`Backend`] decides how readers announce what they are accessing and when retired objects
+//! may be reclaimed. [`HazPtrDomain`] is the hazard-pointer backend, [`epoch::EpochDomain`] is
+//! an epoch-based one, [`eras::EraDomain`] uses hazard eras, and [`ibr::IbrDomain`] uses
+//! interval-based reclamation. Code written against [`Holder`] works with any of them, so the
+//! schemes can be compared on the same data structure by changing only the backend it is given.
+
+pub mod epoch;
+pub mod eras;
+pub mod ibr;
+
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::{Deleter, HazPtr, HazPtrDomain, HazPtrHolder};
+
+/// A memory reclamation scheme.
+pub trait Backend: Sync + 'static {
+    /// Per-reader state, such as a hazard slot or an epoch announcement.
+    type Slot;
+
+    /// Get a slot for a new reader.
+    fn acquire(&'static self) -> Self::Slot;
+
+    /// Give a slot back once its reader is done with it. The slot is reset first.
+    fn release(&'static self, slot: &Self::Slot);
+
+    /// Load the pointer in `src`, and make sure its target is not reclaimed until `slot` is
+    /// reset, released, or used to protect something else.
+    ///
+    /// # Safety
+    ///
+    /// The address in `src` must be valid as a reference, or null, and the value behind it
+    /// must only be deallocated by retiring it on this backend.
+    unsafe fn protect<T>(&self, slot: &Self::Slot, src: &AtomicPtr<T>) -> *mut T;
+
+    /// Stop protecting whatever `slot` last protected.
+    fn reset(&self, slot: &Self::Slot);
+
+    /// Retire `ptr`, to be deleted with `deleter` once no reader can access it anymore.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`](crate::HazPtrObject::retire).
+    unsafe fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter);
+
+    /// Reclaim whatever retired objects can be reclaimed, and return how many were.
+    ///
+    /// If `block` is set, keeps trying until all retired objects have been reclaimed.
+    fn eager_reclaim(&self, block: bool) -> usize;
+}
+
+/// A reader of objects managed by the backend `B`.
+pub struct Holder<B: Backend> {
+    backend: &'static B,
+    slot: Option<B::Slot>,
+}
+
+impl<B: Backend> Holder<B> {
+    /// Create a holder that reads objects managed by `backend`.
+    ///
+    /// It only gets a slot from `backend` once it first loads something.
+    pub fn new(backend: &'static B) -> Self {
+        Self {
+            backend,
+            slot: None,
+        }
+    }
+
+    /// Load the pointer in `ptr`, and keep its target alive until the holder is reset, dropped,
+    /// or used to load something else.
+    ///
+    /// # Safety
+    ///
+    /// Caller must guarantee that the address in AtomicPtr is valid as a reference, or null.
+    /// Caller must also guarantee that the value behind the AtomicPtr will only be deallocated
+    /// by retiring it on this holder's backend.
+    pub unsafe fn load<'l, T>(&'l mut self, ptr: &'_ AtomicPtr<T>) -> Option<&'l T> {
+        let backend = self.backend;
+        let slot = self.slot.get_or_insert_with(|| backend.acquire());
+        // Safety: by the safety contract of load.
+        let ptr = unsafe { backend.protect(slot, ptr) };
+        // Safety: the backend keeps ptr valid until we reset, which needs &mut self.
+        unsafe { HazPtrHolder::as_ref(ptr) }
+    }
+
+    /// Stop protecting whatever this holder last loaded.
+    pub fn reset(&mut self) {
+        if let Some(slot) = &self.slot {
+            self.backend.reset(slot);
+        }
+    }
+}
+
+impl<B: Backend> Drop for Holder<B> {
+    fn drop(&mut self) {
+        if let Some(slot) = &self.slot {
+            self.backend.release(slot);
+        }
+    }
+}
+
+impl Backend for HazPtrDomain {
+    type Slot = &'static HazPtr;
+
+    fn acquire(&'static self) -> Self::Slot {
+        HazPtrDomain::acquire(self)
+    }
+
+    fn release(&'static self, slot: &Self::Slot) {
+        slot.reset();
+        HazPtrDomain::release(self, slot);
+    }
+
+    unsafe fn protect<T>(&self, slot: &Self::Slot, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr1 = src.load(Ordering::SeqCst);
+        loop {
+            match HazPtrHolder::validate(slot, ptr1, src) {
+                Ok(ptr) => break ptr,
+                Err(ptr2) => ptr1 = ptr2,
+            }
+        }
+    }
+
+    fn reset(&self, slot: &Self::Slot) {
+        slot.reset();
+    }
+
+    unsafe fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        HazPtrDomain::retire(self, ptr, deleter);
+    }
+
+    fn eager_reclaim(&self, block: bool) -> usize {
+        HazPtrDomain::eager_reclaim(self, block)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::epoch::EpochDomain;
+    use super::eras::EraDomain;
+    use super::ibr::IbrDomain;
+    use super::*;
+    use crate::deleters;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    struct CountDrops(usize, Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // The same reader/writer interaction, against any backend.
+    fn protect_and_retire<B: Backend>(backend: &'static B) {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicPtr::new(Box::into_raw(Box::new(CountDrops(1, Arc::clone(&drops)))));
+
+        let mut h = Holder::new(backend);
+        // Safety: x always holds a valid Box, and is only ever retired on backend.
+        let my_x = unsafe { h.load(&x) }.expect("not null");
+
+        let old = x.swap(
+            Box::into_raw(Box::new(CountDrops(2, Arc::clone(&drops)))),
+            Ordering::SeqCst,
+        );
+        // Safety: old came from a Box, and is no longer reachable through x.
+        unsafe { backend.retire(old, &deleters::drop_box) };
+        backend.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(my_x.0, 1);
+
+        drop(h);
+        backend.eager_reclaim(true);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // Safety: nobody is reading x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn hazard_pointers() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        protect_and_retire(&DOMAIN);
+    }
+
+    #[test]
+    fn hazard_pointers_cache_slots() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let x = AtomicPtr::new(Box::into_raw(Box::new(0)));
+
+        let mut h = Holder::new(&DOMAIN);
+        // Safety: x always holds a valid Box, and is never retired.
+        let _ = unsafe { h.load(&x) };
+        let slot = *h.slot.as_ref().unwrap();
+        drop(h);
+        // Released to this thread's cache, like a HazPtrHolder's would be.
+        assert!(slot.active.load(Ordering::SeqCst));
+        assert!(!slot.is_held());
+        assert!(std::ptr::eq(
+            HazPtrHolder::for_domain(&DOMAIN).hazptr(),
+            slot
+        ));
+
+        // Safety: nobody is reading x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn epochs() {
+        static DOMAIN: EpochDomain = EpochDomain::new();
+        protect_and_retire(&DOMAIN);
+    }
+
+    #[test]
+    fn hazard_eras() {
+        static DOMAIN: EraDomain = EraDomain::new();
+        protect_and_retire(&DOMAIN);
+    }
+
+    #[test]
+    fn intervals() {
+        static DOMAIN: IbrDomain = IbrDomain::new();
+        protect_and_retire(&DOMAIN);
+    }
+}
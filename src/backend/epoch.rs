@@ -0,0 +1,191 @@
+//! An epoch-based reclamation backend.
+//!
+//! Readers announce the global epoch they observed when they start protecting, rather than the
+//! individual objects they access. That makes protecting cheaper (no validation loop), and a
+//! single announcement covers any number of objects, at the cost of a single stalled reader
+//! holding up reclamation of _every_ object retired after it pinned.
+
+use super::Backend;
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::{AtomicMut, Mutex};
+use crate::Deleter;
+
+/// A domain that reclaims objects once every reader has moved on from the epoch in which they
+/// were retired.
+pub struct EpochDomain {
+    epoch: AtomicUsize,
+    participants: AtomicPtr<Participant>,
+    garbage: Mutex<Vec<Garbage>>,
+}
+
+/// A reader's announcement of the epoch it is in.
+pub struct Participant {
+    // 0 if not pinned, otherwise the pinned epoch shifted left by one, with the low bit set.
+    pinned: AtomicUsize,
+    active: AtomicBool,
+    next: AtomicPtr<Participant>,
+}
+
+struct Garbage {
+    ptr: *mut dyn Drop,
+    deleter: &'static dyn Deleter,
+    epoch: usize,
+}
+
+// Safety: retired objects are no longer accessed by whoever retired them, and deleters are
+// already invoked from whichever thread happens to reclaim.
+unsafe impl Send for Garbage {}
+
+impl EpochDomain {
+    const_fn! {
+        pub const fn new() -> Self {
+            Self {
+                epoch: AtomicUsize::new(0),
+                participants: AtomicPtr::new(std::ptr::null_mut()),
+                garbage: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    fn participants(&self) -> impl Iterator<Item = &Participant> {
+        let mut node = self.participants.load(Ordering::SeqCst);
+        std::iter::from_fn(move || {
+            // Safety: Participants are only de-allocated when the domain is dropped.
+            let n = unsafe { node.as_ref() }?;
+            node = n.next.load(Ordering::SeqCst);
+            Some(n)
+        })
+    }
+
+    // Move the global epoch forward if every pinned participant has seen the current one.
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let pinned_now = (epoch << 1) | 1;
+        for p in self.participants() {
+            let pinned = p.pinned.load(Ordering::SeqCst);
+            if pinned != 0 && pinned != pinned_now {
+                return epoch;
+            }
+        }
+        match self
+            .epoch
+            .compare_exchange(epoch, epoch + 1, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => epoch + 1,
+            Err(epoch) => epoch,
+        }
+    }
+
+    fn collect(&self) -> usize {
+        let epoch = self.try_advance();
+        // Any reader that could still see an object retired in epoch e was pinned in epoch e or
+        // earlier, and the epoch cannot move past e + 1 until all such readers have unpinned.
+        let reclaimable: Vec<_> = {
+            let mut garbage = self.garbage.lock().unwrap();
+            let (reclaimable, remaining) = garbage.drain(..).partition(|g| g.epoch + 2 <= epoch);
+            *garbage = remaining;
+            reclaimable
+        };
+        let n = reclaimable.len();
+        for g in reclaimable {
+            // Safety: no reader can access g.ptr anymore, and it is only ever deleted once.
+            // g.deleter is valid for it by the safety contract of retire.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        n
+    }
+}
+
+impl Default for EpochDomain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for EpochDomain {
+    type Slot = &'static Participant;
+
+    fn acquire(&'static self) -> Self::Slot {
+        for p in self.participants() {
+            if !p.active.load(Ordering::SeqCst)
+                && p.active
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                return p;
+            }
+        }
+
+        let p = Box::leak(Box::new(Participant {
+            pinned: AtomicUsize::new(0),
+            active: AtomicBool::new(true),
+            next: AtomicPtr::new(std::ptr::null_mut()),
+        }));
+        let mut head = self.participants.load(Ordering::SeqCst);
+        loop {
+            p.next.store_mut(head);
+            match self.participants.compare_exchange_weak(
+                head,
+                p,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break p,
+                Err(head_now) => head = head_now,
+            }
+        }
+    }
+
+    fn release(&'static self, slot: &Self::Slot) {
+        self.reset(slot);
+        slot.active.store(false, Ordering::SeqCst);
+    }
+
+    unsafe fn protect<T>(&self, slot: &Self::Slot, src: &AtomicPtr<T>) -> *mut T {
+        // (Re-)pin to the current epoch. Anything protected by this slot before is no longer
+        // accessed, so moving forward is fine.
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        slot.pinned.store((epoch << 1) | 1, Ordering::SeqCst);
+        src.load(Ordering::SeqCst)
+    }
+
+    fn reset(&self, slot: &Self::Slot) {
+        slot.pinned.store(0, Ordering::SeqCst);
+    }
+
+    unsafe fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        self.garbage.lock().unwrap().push(Garbage {
+            ptr,
+            deleter,
+            epoch,
+        });
+        self.collect();
+    }
+
+    fn eager_reclaim(&self, block: bool) -> usize {
+        let mut reclaimed = self.collect();
+        while block && !self.garbage.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+            reclaimed += self.collect();
+        }
+        reclaimed
+    }
+}
+
+impl Drop for EpochDomain {
+    fn drop(&mut self) {
+        // Nobody can be reading anymore, since Participants are only handed out for 'static
+        // domains, which are never dropped.
+        for g in self.garbage.get_mut().unwrap().drain(..) {
+            // Safety: as in collect.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        let mut node = self.participants.load_mut();
+        while !node.is_null() {
+            // Safety: Participants are allocated with Box, and nobody can reach them anymore.
+            let p = unsafe { Box::from_raw(node) };
+            node = p.next.load(Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! A hazard-eras reclamation backend.
+//!
+//! Like hazard pointers, every reader announces what it may be accessing in a slot of its own,
+//! but the announcement is the current value of a global era clock rather than an address. The
+//! clock only ticks when objects are retired, so a reader traversing a long chain of nodes only
+//! has to update its slot when a writer retired something in the meantime, not once per node.
+//!
+//! In return, a reader holds up every object that was alive in the era it announced, not just
+//! the one it is accessing, so reclamation is somewhat delayed compared to hazard pointers. It
+//! is still bounded, unlike with epochs: a stalled reader only holds up objects that were
+//! already alive when it stalled.
+
+use super::Backend;
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use crate::sync::{AtomicMut, Mutex};
+use crate::Deleter;
+
+/// Means that a slot announces no era.
+const NONE: u64 = 0;
+
+/// A domain that reclaims objects once no reader has announced an era in which they were
+/// alive.
+pub struct EraDomain {
+    era: AtomicU64,
+    slots: AtomicPtr<EraSlot>,
+    garbage: Mutex<Vec<Garbage>>,
+}
+
+/// A reader's announcement of the era it is in.
+pub struct EraSlot {
+    era: AtomicU64,
+    active: AtomicBool,
+    next: AtomicPtr<EraSlot>,
+}
+
+struct Garbage {
+    ptr: *mut dyn Drop,
+    deleter: &'static dyn Deleter,
+    birth: u64,
+    retired: u64,
+}
+
+// Safety: retired objects are no longer accessed by whoever retired them, and deleters are
+// already invoked from whichever thread happens to reclaim.
+unsafe impl Send for Garbage {}
+
+impl EraDomain {
+    const_fn! {
+        pub const fn new() -> Self {
+            Self {
+                era: AtomicU64::new(NONE + 1),
+                slots: AtomicPtr::new(std::ptr::null_mut()),
+                garbage: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    /// The current era, to be recorded as the birth era of an object that is about to be
+    /// published, for use with [`EraDomain::retire_with_birth`].
+    pub fn birth_era(&self) -> u64 {
+        self.era.load(Ordering::SeqCst)
+    }
+
+    /// Like [`Backend::retire`], but for an object known to have been created in era `birth`
+    /// (as returned by [`EraDomain::birth_era`] before the object was published).
+    ///
+    /// Objects retired through [`Backend::retire`] are conservatively assumed to have been
+    /// alive since the first era, so any reader that is still in an old era holds them up. A
+    /// birth era lets readers that started after the object was created not matter.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Backend::retire`], and `birth` must not be later than the era the object was
+    /// first published in.
+    pub unsafe fn retire_with_birth(
+        &self,
+        ptr: *mut dyn Drop,
+        deleter: &'static dyn Deleter,
+        birth: u64,
+    ) {
+        let retired = self.era.fetch_add(1, Ordering::SeqCst);
+        self.garbage.lock().unwrap().push(Garbage {
+            ptr,
+            deleter,
+            birth,
+            retired,
+        });
+        self.collect();
+    }
+
+    fn slots(&self) -> impl Iterator<Item = &EraSlot> {
+        let mut node = self.slots.load(Ordering::SeqCst);
+        std::iter::from_fn(move || {
+            // Safety: EraSlots are only de-allocated when the domain is dropped.
+            let n = unsafe { node.as_ref() }?;
+            node = n.next.load(Ordering::SeqCst);
+            Some(n)
+        })
+    }
+
+    fn collect(&self) -> usize {
+        // Only objects retired before the scan starts are considered: any reader that could
+        // still access one of those has already announced itself by then.
+        let scan_era = self.era.load(Ordering::SeqCst);
+        let announced: Vec<u64> = self
+            .slots()
+            .map(|s| s.era.load(Ordering::SeqCst))
+            .filter(|&era| era != NONE)
+            .collect();
+        // A reader in era e may access exactly the objects that were born in or before e, and
+        // not retired before e.
+        let reclaimable: Vec<_> = {
+            let mut garbage = self.garbage.lock().unwrap();
+            let (reclaimable, remaining) = garbage.drain(..).partition(|g: &Garbage| {
+                g.retired < scan_era
+                    && !announced
+                        .iter()
+                        .any(|&era| g.birth <= era && era <= g.retired)
+            });
+            *garbage = remaining;
+            reclaimable
+        };
+        let n = reclaimable.len();
+        for g in reclaimable {
+            // Safety: no reader can access g.ptr anymore, and it is only ever deleted once.
+            // g.deleter is valid for it by the safety contract of retire.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        n
+    }
+}
+
+impl Default for EraDomain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for EraDomain {
+    type Slot = &'static EraSlot;
+
+    fn acquire(&'static self) -> Self::Slot {
+        for s in self.slots() {
+            if !s.active.load(Ordering::SeqCst)
+                && s.active
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                return s;
+            }
+        }
+
+        let s = Box::leak(Box::new(EraSlot {
+            era: AtomicU64::new(NONE),
+            active: AtomicBool::new(true),
+            next: AtomicPtr::new(std::ptr::null_mut()),
+        }));
+        let mut head = self.slots.load(Ordering::SeqCst);
+        loop {
+            s.next.store_mut(head);
+            match self
+                .slots
+                .compare_exchange_weak(head, s, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break s,
+                Err(head_now) => head = head_now,
+            }
+        }
+    }
+
+    fn release(&'static self, slot: &Self::Slot) {
+        self.reset(slot);
+        slot.active.store(false, Ordering::SeqCst);
+    }
+
+    unsafe fn protect<T>(&self, slot: &Self::Slot, src: &AtomicPtr<T>) -> *mut T {
+        // Only touch the slot if the era moved on since it was last announced.
+        let mut announced = slot.era.load(Ordering::SeqCst);
+        loop {
+            let ptr = src.load(Ordering::SeqCst);
+            let era = self.era.load(Ordering::SeqCst);
+            if era == announced {
+                break ptr;
+            }
+            slot.era.store(era, Ordering::SeqCst);
+            announced = era;
+        }
+    }
+
+    fn reset(&self, slot: &Self::Slot) {
+        slot.era.store(NONE, Ordering::SeqCst);
+    }
+
+    unsafe fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        // Safety: by the safety contract of retire, and every object was born in or after the
+        // first era.
+        unsafe { self.retire_with_birth(ptr, deleter, NONE + 1) }
+    }
+
+    fn eager_reclaim(&self, block: bool) -> usize {
+        let mut reclaimed = self.collect();
+        while block && !self.garbage.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+            reclaimed += self.collect();
+        }
+        reclaimed
+    }
+}
+
+impl Drop for EraDomain {
+    fn drop(&mut self) {
+        // Nobody can be reading anymore, since EraSlots are only handed out for 'static
+        // domains, which are never dropped.
+        for g in self.garbage.get_mut().unwrap().drain(..) {
+            // Safety: as in collect.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        let mut node = self.slots.load_mut();
+        while !node.is_null() {
+            // Safety: EraSlots are allocated with Box, and nobody can reach them anymore.
+            let s = unsafe { Box::from_raw(node) };
+            node = s.next.load(Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! An interval-based reclamation (IBR) backend, in the style of 2GE-IBR.
+//!
+//! Like with hazard eras, a global era clock ticks whenever an object is retired, and objects
+//! remember the eras they were born and retired in. Instead of announcing a single era, each
+//! reader reserves an interval of eras: it starts at the era the reader first protected
+//! something in, and grows whenever the reader protects something in a later era, until the
+//! reader resets. An object may be reclaimed once its lifetime overlaps no reader's interval.
+//!
+//! A reserved interval keeps everything the reader protected since its last reset alive, much
+//! like an epoch pin, so reads stay nearly as cheap as with epochs. But since objects born after
+//! a stalled reader's interval ended are not held up by it, memory use stays bounded, like with
+//! hazard pointers.
+
+use super::Backend;
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use crate::sync::{AtomicMut, Mutex};
+use crate::Deleter;
+
+/// Means that a slot has no interval reserved.
+const NONE: u64 = 0;
+
+/// A domain that reclaims objects once their lifetime overlaps no reader's reserved interval
+/// of eras.
+pub struct IbrDomain {
+    era: AtomicU64,
+    slots: AtomicPtr<IbrSlot>,
+    garbage: Mutex<Vec<Garbage>>,
+}
+
+/// A reader's reserved interval of eras.
+pub struct IbrSlot {
+    // NONE if the reader has nothing reserved.
+    lower: AtomicU64,
+    upper: AtomicU64,
+    active: AtomicBool,
+    next: AtomicPtr<IbrSlot>,
+}
+
+struct Garbage {
+    ptr: *mut dyn Drop,
+    deleter: &'static dyn Deleter,
+    birth: u64,
+    retired: u64,
+}
+
+// Safety: retired objects are no longer accessed by whoever retired them, and deleters are
+// already invoked from whichever thread happens to reclaim.
+unsafe impl Send for Garbage {}
+
+impl IbrDomain {
+    const_fn! {
+        pub const fn new() -> Self {
+            Self {
+                era: AtomicU64::new(NONE + 1),
+                slots: AtomicPtr::new(std::ptr::null_mut()),
+                garbage: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    /// The current era, to be recorded as the birth era of an object that is about to be
+    /// published, for use with [`IbrDomain::retire_with_birth`].
+    pub fn birth_era(&self) -> u64 {
+        self.era.load(Ordering::SeqCst)
+    }
+
+    /// Like [`Backend::retire`], but for an object known to have been created in era `birth`
+    /// (as returned by [`IbrDomain::birth_era`] before the object was published).
+    ///
+    /// Objects retired through [`Backend::retire`] are conservatively assumed to have been
+    /// alive since the first era, so any reader whose interval started before they were retired
+    /// holds them up. A birth era lets readers whose interval ended before the object was
+    /// created not matter.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Backend::retire`], and `birth` must not be later than the era the object was
+    /// first published in.
+    pub unsafe fn retire_with_birth(
+        &self,
+        ptr: *mut dyn Drop,
+        deleter: &'static dyn Deleter,
+        birth: u64,
+    ) {
+        let retired = self.era.fetch_add(1, Ordering::SeqCst);
+        self.garbage.lock().unwrap().push(Garbage {
+            ptr,
+            deleter,
+            birth,
+            retired,
+        });
+        self.collect();
+    }
+
+    fn slots(&self) -> impl Iterator<Item = &IbrSlot> {
+        let mut node = self.slots.load(Ordering::SeqCst);
+        std::iter::from_fn(move || {
+            // Safety: IbrSlots are only de-allocated when the domain is dropped.
+            let n = unsafe { node.as_ref() }?;
+            node = n.next.load(Ordering::SeqCst);
+            Some(n)
+        })
+    }
+
+    fn collect(&self) -> usize {
+        // Only objects retired before the scan starts are considered: any reader that could
+        // still access one of those has already announced itself by then.
+        let scan_era = self.era.load(Ordering::SeqCst);
+        let reserved: Vec<(u64, u64)> = self
+            .slots()
+            .filter_map(|s| {
+                // Readers set upper before lower when starting an interval, and clear lower
+                // first when ending it, so this never sees a stale upper for a live interval.
+                let lower = s.lower.load(Ordering::SeqCst);
+                let upper = s.upper.load(Ordering::SeqCst);
+                (lower != NONE).then_some((lower, upper))
+            })
+            .collect();
+        // A reader with the interval [lower, upper] may access exactly the objects born in or
+        // before upper, and not retired before lower.
+        let reclaimable: Vec<_> = {
+            let mut garbage = self.garbage.lock().unwrap();
+            let (reclaimable, remaining) = garbage.drain(..).partition(|g: &Garbage| {
+                g.retired < scan_era
+                    && !reserved
+                        .iter()
+                        .any(|&(lower, upper)| g.birth <= upper && lower <= g.retired)
+            });
+            *garbage = remaining;
+            reclaimable
+        };
+        let n = reclaimable.len();
+        for g in reclaimable {
+            // Safety: no reader can access g.ptr anymore, and it is only ever deleted once.
+            // g.deleter is valid for it by the safety contract of retire.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        n
+    }
+}
+
+impl Default for IbrDomain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for IbrDomain {
+    type Slot = &'static IbrSlot;
+
+    fn acquire(&'static self) -> Self::Slot {
+        for s in self.slots() {
+            if !s.active.load(Ordering::SeqCst)
+                && s.active
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            {
+                return s;
+            }
+        }
+
+        let s = Box::leak(Box::new(IbrSlot {
+            lower: AtomicU64::new(NONE),
+            upper: AtomicU64::new(NONE),
+            active: AtomicBool::new(true),
+            next: AtomicPtr::new(std::ptr::null_mut()),
+        }));
+        let mut head = self.slots.load(Ordering::SeqCst);
+        loop {
+            s.next.store_mut(head);
+            match self
+                .slots
+                .compare_exchange_weak(head, s, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break s,
+                Err(head_now) => head = head_now,
+            }
+        }
+    }
+
+    fn release(&'static self, slot: &Self::Slot) {
+        self.reset(slot);
+        slot.active.store(false, Ordering::SeqCst);
+    }
+
+    unsafe fn protect<T>(&self, slot: &Self::Slot, src: &AtomicPtr<T>) -> *mut T {
+        let mut upper = slot.upper.load(Ordering::SeqCst);
+        if slot.lower.load(Ordering::SeqCst) == NONE {
+            // Start a new interval.
+            upper = self.era.load(Ordering::SeqCst);
+            slot.upper.store(upper, Ordering::SeqCst);
+            slot.lower.store(upper, Ordering::SeqCst);
+        }
+        // Only touch the slot if the era moved on since the interval last grew.
+        loop {
+            let ptr = src.load(Ordering::SeqCst);
+            let era = self.era.load(Ordering::SeqCst);
+            if era == upper {
+                break ptr;
+            }
+            slot.upper.store(era, Ordering::SeqCst);
+            upper = era;
+        }
+    }
+
+    fn reset(&self, slot: &Self::Slot) {
+        slot.lower.store(NONE, Ordering::SeqCst);
+        slot.upper.store(NONE, Ordering::SeqCst);
+    }
+
+    unsafe fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        // Safety: by the safety contract of retire, and every object was born in or after the
+        // first era.
+        unsafe { self.retire_with_birth(ptr, deleter, NONE + 1) }
+    }
+
+    fn eager_reclaim(&self, block: bool) -> usize {
+        let mut reclaimed = self.collect();
+        while block && !self.garbage.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+            reclaimed += self.collect();
+        }
+        reclaimed
+    }
+}
+
+impl Drop for IbrDomain {
+    fn drop(&mut self) {
+        // Nobody can be reading anymore, since IbrSlots are only handed out for 'static
+        // domains, which are never dropped.
+        for g in self.garbage.get_mut().unwrap().drain(..) {
+            // Safety: as in collect.
+            unsafe { g.deleter.delete(g.ptr) };
+        }
+        let mut node = self.slots.load_mut();
+        while !node.is_null() {
+            // Safety: IbrSlots are allocated with Box, and nobody can reach them anymore.
+            let s = unsafe { Box::from_raw(node) };
+            node = s.next.load(Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! The asymmetric fence between readers and reclaimers.
+//!
+//! A reader's hazard store followed by its validating re-load of the source, and a reclaimer's
+//! unlinking of retired objects followed by its scan of the hazards, must not both miss each
+//! other. That takes a full fence on each side, but readers protect objects far more often than
+//! reclaimers scan. With the `membarrier` feature on Linux, readers only keep the compiler from
+//! reordering, and reclaimers make up for it by having the kernel run a full barrier on every
+//! thread of the process with `membarrier(2)`. On Windows, `FlushProcessWriteBuffers` does the
+//! same, and is always available.
+//!
+//! Everywhere else, under loom and miri, and while a Linux process isn't (yet) registered for
+//! expedited membarriers, both sides issue a full fence.
+
+use crate::sync::atomic::{fence, Ordering};
+
+/// The reader's side: between storing a hazard and re-loading the source to validate it.
+#[inline]
+pub(crate) fn light() {
+    if os::registered() {
+        std::sync::atomic::compiler_fence(Ordering::SeqCst);
+    } else {
+        fence(Ordering::SeqCst);
+    }
+}
+
+/// The reclaimer's side: between unlinking retired objects and scanning the hazards.
+pub(crate) fn heavy() {
+    if !os::heavy_barrier() {
+        fence(Ordering::SeqCst);
+    }
+}
+
+#[cfg(all(feature = "membarrier", target_os = "linux", not(loom), not(miri)))]
+mod os {
+    use std::sync::OnceLock;
+
+    // Whether the process registered for expedited private membarriers. Only reclaimers
+    // register, so readers use full fences until the first scan.
+    static REGISTERED: OnceLock<bool> = OnceLock::new();
+
+    fn membarrier(cmd: libc::c_int) -> bool {
+        // Safety: membarrier only takes integer arguments.
+        unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0 as libc::c_uint) == 0 }
+    }
+
+    pub(super) fn registered() -> bool {
+        REGISTERED.get() == Some(&true)
+    }
+
+    /// Run a full barrier on every thread of the process, or return false if the kernel doesn't
+    /// support that.
+    pub(super) fn heavy_barrier() -> bool {
+        let registered =
+            *REGISTERED.get_or_init(|| membarrier(libc::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED));
+        if !registered {
+            return false;
+        }
+        // Readers may be relying on us now, so there is no falling back to a fence of our own.
+        assert!(
+            membarrier(libc::MEMBARRIER_CMD_PRIVATE_EXPEDITED),
+            "membarrier failed after registration"
+        );
+        true
+    }
+}
+
+#[cfg(all(windows, not(loom), not(miri)))]
+mod os {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn FlushProcessWriteBuffers();
+    }
+
+    pub(super) fn registered() -> bool {
+        true
+    }
+
+    /// Run a full barrier on every thread of the process.
+    pub(super) fn heavy_barrier() -> bool {
+        // Safety: FlushProcessWriteBuffers has no preconditions.
+        unsafe { FlushProcessWriteBuffers() };
+        true
+    }
+}
+
+#[cfg(not(any(
+    all(feature = "membarrier", target_os = "linux", not(loom), not(miri)),
+    all(windows, not(loom), not(miri)),
+)))]
+mod os {
+    pub(super) fn registered() -> bool {
+        false
+    }
+
+    pub(super) fn heavy_barrier() -> bool {
+        false
+    }
+}
@@ -0,0 +1,244 @@
+use crate::sync::atomic::AtomicPtr;
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// An invariant lifetime that stands for one particular [`BrandedDomain`].
+///
+/// Only [`branded_domain!`](crate::branded_domain) creates these, each with a lifetime no other
+/// brand can be unified with.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Brand<'brand>(PhantomData<fn(&'brand ()) -> &'brand ()>);
+
+impl Brand<'_> {
+    /// # Safety
+    ///
+    /// The brand must be anchored with [`BrandAnchor::new`] right away, as
+    /// [`branded_domain!`](crate::branded_domain) does.
+    #[doc(hidden)]
+    pub unsafe fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Keeps a brand's lifetime alive until the end of the scope it was created in.
+///
+/// Dropping it at the end of that scope makes the borrow checker extend the brand's lifetime to
+/// all of the scope, which no other brand from a different `let` can share.
+#[doc(hidden)]
+pub struct BrandAnchor<'brand>(PhantomData<&'brand Brand<'brand>>);
+
+impl<'brand> BrandAnchor<'brand> {
+    /// # Safety
+    ///
+    /// Only for use by [`branded_domain!`](crate::branded_domain).
+    #[doc(hidden)]
+    pub unsafe fn new(_: &'brand Brand<'brand>) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl Drop for BrandAnchor<'_> {
+    fn drop(&mut self) {}
+}
+
+/// A static domain together with a brand, a lifetime unique to it, that the holders and objects
+/// made from it carry as well.
+///
+/// Where [`Family`](crate::Family) tells domains apart by a type parameter, a branded domain does
+/// so by its lifetime, without declaring a type for it. A holder of one branded domain only
+/// loads objects with the same brand, and a domain only retires objects with its own brand, so
+/// mixing them up with another domain's fails to compile rather than at runtime.
+///
+/// Branded domains are created with [`branded_domain!`](crate::branded_domain), and can't leave
+/// the scope they were created in.
+///
+/// ```
+/// use std::sync::atomic::{AtomicPtr, Ordering};
+///
+/// haphazard::branded_domain!(domain);
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(domain.wrap(42))));
+/// let mut h = domain.holder();
+/// // Safety: src only ever holds Boxes, retired on domain.
+/// let object = unsafe { h.load(&src) }.unwrap();
+/// assert_eq!(**object, 42);
+///
+/// let old = src.swap(std::ptr::null_mut(), Ordering::SeqCst);
+/// // Safety: old came from a Box, and is no longer reachable from src.
+/// unsafe { domain.retire(old) };
+/// ```
+///
+/// Objects of one branded domain can't be loaded with another's holder:
+///
+/// ```compile_fail
+/// use std::sync::atomic::AtomicPtr;
+///
+/// haphazard::branded_domain!(a);
+/// haphazard::branded_domain!(b);
+///
+/// let src = AtomicPtr::new(Box::into_raw(Box::new(a.wrap(42))));
+/// let mut h = b.holder();
+/// let _ = unsafe { h.load(&src) };
+/// ```
+#[derive(Clone, Copy)]
+pub struct BrandedDomain<'brand> {
+    domain: &'static HazPtrDomain,
+    _brand: Brand<'brand>,
+}
+
+impl<'brand> BrandedDomain<'brand> {
+    /// # Safety
+    ///
+    /// Only for use by [`branded_domain!`](crate::branded_domain), which makes sure no other
+    /// domain has the same brand.
+    #[doc(hidden)]
+    pub unsafe fn new_unchecked(domain: &'static HazPtrDomain, brand: Brand<'brand>) -> Self {
+        Self {
+            domain,
+            _brand: brand,
+        }
+    }
+
+    /// The underlying domain.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Create a holder that protects objects of this domain.
+    pub fn holder(&self) -> BrandedHolder<'brand> {
+        BrandedHolder {
+            inner: HazPtrHolder::for_domain(self.domain),
+            _brand: self._brand,
+        }
+    }
+
+    /// Make `value` into an object of this domain.
+    pub fn wrap<T>(&self, value: T) -> Branded<'brand, T> {
+        Branded {
+            inner: HazPtrObjectWrapper::with_domain(self.domain, value),
+            _brand: self._brand,
+        }
+    }
+
+    /// Retire an object of this domain.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    pub unsafe fn retire<T: 'static>(&self, ptr: *mut Branded<'brand, T>) {
+        // Safety: Branded is a transparent wrapper, and by the safety contract of retire.
+        unsafe { ptr.cast::<HazPtrObjectWrapper<T>>().retire() };
+    }
+}
+
+/// Create a [`BrandedDomain`] named `$name`, with a brand unique to this scope.
+///
+/// `branded_domain!(name)` brands a fresh static domain of its own for this place in the code,
+/// like [`unique_domain!`](crate::unique_domain). `branded_domain!(name = domain)` brands an
+/// existing `&'static HazPtrDomain` instead. Either way, `name` is only usable until the end of
+/// the enclosing block.
+#[macro_export]
+macro_rules! branded_domain {
+    ($name:ident) => {
+        $crate::branded_domain!($name = $crate::unique_domain!())
+    };
+    ($name:ident = $domain:expr) => {
+        let domain: &'static $crate::HazPtrDomain = $domain;
+        // Safety: the brand is anchored right away.
+        let brand = unsafe { $crate::Brand::new() };
+        #[allow(unused)]
+        // Safety: we are branded_domain!.
+        let anchor = unsafe { $crate::BrandAnchor::new(&brand) };
+        // Safety: the anchor makes the brand unique.
+        let $name = unsafe { $crate::BrandedDomain::new_unchecked(domain, brand) };
+    };
+}
+
+/// A holder for objects of a [`BrandedDomain`], created with [`BrandedDomain::holder`].
+pub struct BrandedHolder<'brand> {
+    inner: HazPtrHolder,
+    _brand: Brand<'brand>,
+}
+
+impl<'brand> BrandedHolder<'brand> {
+    /// Like [`HazPtrHolder::load`], for objects of this holder's domain.
+    ///
+    /// # Safety
+    ///
+    /// Caller must guarantee that the address in `src` is valid as a reference, or null, and that
+    /// it will only be deallocated through [`BrandedDomain::retire`].
+    pub unsafe fn load<'l, T>(
+        &'l mut self,
+        src: &'_ AtomicPtr<Branded<'brand, T>>,
+    ) -> Option<&'l Branded<'brand, T>> {
+        // Safety: by the safety contract of load.
+        unsafe { self.inner.load(src) }
+    }
+
+    /// Stop protecting whatever this holder protects.
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+/// An object of a [`BrandedDomain`], created with [`BrandedDomain::wrap`].
+#[repr(transparent)]
+pub struct Branded<'brand, T> {
+    inner: HazPtrObjectWrapper<T>,
+    _brand: Brand<'brand>,
+}
+
+impl<T> Deref for Branded<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Branded<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::sync::atomic::{AtomicPtr, Ordering};
+    use crate::HazPtrDomain;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn branded_retire() {
+        crate::branded_domain!(domain = &DOMAIN);
+        assert!(std::ptr::eq(domain.domain(), &DOMAIN));
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let object = domain.wrap(CountDrops(Arc::clone(&drops)));
+        let src = AtomicPtr::new(Box::into_raw(Box::new(object)));
+        let mut h = domain.holder();
+        // Safety: src only ever holds Boxes, retired on domain.
+        let object = unsafe { h.load(&src) }.unwrap();
+        let old = src.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: old came from a Box, and is no longer reachable from src.
+        unsafe { domain.retire(old) };
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert!(Arc::ptr_eq(&object.0, &drops));
+
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::AtomicMut;
+use crate::{HazPtrDomain, HazPtrObject, Retired, BATCH_SIZE};
+
+/// Groups the objects one data structure retires, so that they are reclaimed together when the
+/// structure goes away.
+///
+/// Objects retired through a cohort wait in the cohort rather than on the domain's retired list,
+/// and join the domain in batches. When the cohort is dropped, the objects still waiting in it
+/// join the domain, and the domain reclaims whatever isn't protected right away, whether or not
+/// its reclaim threshold has been reached. A structure that keeps a cohort next to its nodes
+/// thereby doesn't leave its garbage behind for someone else's retirements to clean up.
+///
+/// ```
+/// use haphazard::{Cohort, HazPtrDomain, HazPtrObjectWrapper};
+///
+/// static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+/// let cohort = DOMAIN.cohort();
+/// let node = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 1)));
+/// // Safety: node came from a Box, and was never shared.
+/// unsafe { cohort.retire(node) };
+/// drop(cohort);
+/// ```
+pub struct Cohort {
+    domain: &'static HazPtrDomain,
+    head: AtomicPtr<Retired>,
+    count: AtomicUsize,
+}
+
+impl HazPtrDomain {
+    /// Create a [`Cohort`] that retires objects on this domain.
+    pub fn cohort(&'static self) -> Cohort {
+        Cohort {
+            domain: self,
+            head: AtomicPtr::new(std::ptr::null_mut()),
+            count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Cohort {
+    /// The domain objects retired through this cohort end up on.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Retire `object` into this cohort.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    ///
+    /// # Panics
+    ///
+    /// If `object` does not belong to this cohort's domain.
+    pub unsafe fn retire<T: HazPtrObject>(&self, object: *mut T) {
+        // Safety: by the safety contract of retire, object is valid.
+        let domain = unsafe { &*object }.domain();
+        assert!(
+            std::ptr::eq(domain, self.domain),
+            "object belongs to a different domain"
+        );
+        if !std::mem::needs_drop::<T>() {
+            return;
+        }
+        self.domain.check_confined();
+        let ptr = object as *mut dyn Drop;
+        let Some(retired) = self.domain.alloc_retired(ptr as *mut u8, ptr, T::DELETER) else {
+            // Out of memory; the domain knows how to deal with that.
+            return self.domain.retire(ptr, T::DELETER);
+        };
+        let mut head = self.head.load(Ordering::SeqCst);
+        loop {
+            // Safety: retired was never shared, so &mut is ok.
+            unsafe { (*retired).next.store_mut(head) };
+            match self
+                .head
+                .compare_exchange_weak(head, retired, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(now) => head = now,
+            }
+        }
+        if self.count.fetch_add(1, Ordering::SeqCst) + 1 >= BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    /// Hand the objects waiting in this cohort to the domain.
+    pub fn flush(&self) {
+        let head = self.head.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        let (mut tail, mut count) = (head, 0);
+        while !tail.is_null() {
+            count += 1;
+            // Safety: we took the list, so its nodes are ours.
+            let next = unsafe { (*tail).next.load_mut() };
+            if next.is_null() {
+                break;
+            }
+            tail = next;
+        }
+        self.count.fetch_sub(count, Ordering::SeqCst);
+        // Safety: as above, and the chain is count long.
+        unsafe { self.domain.push_retired_chain(head, tail, count) };
+    }
+}
+
+impl Drop for Cohort {
+    fn drop(&mut self) {
+        self.flush();
+        self.domain.eager_reclaim(false);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{HazPtrHolder, HazPtrObjectWrapper, ReclaimThreshold};
+    use std::sync::Arc;
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn reclaimed_when_dropped() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        // Retiring on the domain alone wouldn't reclaim anything for a while.
+        DOMAIN.set_reclaim_threshold(ReclaimThreshold {
+            fixed: 100,
+            per_hazard: 0,
+        });
+        let drops = Arc::new(AtomicUsize::new(0));
+        let nodes: Vec<_> = (0..3)
+            .map(|_| {
+                Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                    &DOMAIN,
+                    CountDrops(Arc::clone(&drops)),
+                )))
+            })
+            .collect();
+        let x = AtomicPtr::new(nodes[0]);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x holds a valid Box, only freed by retiring it.
+        unsafe { h.load(&x) }.expect("not null");
+        x.store(std::ptr::null_mut(), Ordering::SeqCst);
+
+        let cohort = DOMAIN.cohort();
+        for &node in &nodes {
+            // Safety: the nodes came from Boxes, and are no longer reachable through x.
+            unsafe { cohort.retire(node) };
+        }
+        // The objects wait in the cohort, out of the domain's reach.
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        drop(cohort);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // The protected one is left to the domain.
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+}
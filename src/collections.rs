@@ -0,0 +1,14 @@
+//! Concurrent data structures built on hazard pointers.
+
+mod hash_map;
+mod list;
+mod ordered_set;
+mod queue;
+mod stack;
+mod watch;
+
+pub use hash_map::HashMap;
+pub use ordered_set::OrderedSet;
+pub use queue::Queue;
+pub use stack::Stack;
+pub use watch::{Changed, WatchCell};
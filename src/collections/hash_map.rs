@@ -0,0 +1,167 @@
+use super::list::List;
+use crate::{HazPtrDomain, SHARED_DOMAIN};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+/// A lock-free hash map with a fixed number of buckets (Michael's hash map).
+///
+/// Every bucket is a lock-free linked list, whose nodes are retired on the map's domain once
+/// their entry is removed. Lookups protect the nodes they traverse in their bucket with hazard
+/// pointers, and only hand out the value while it is protected.
+///
+/// The map never resizes, so lookups slow down once it holds many more entries than it has
+/// buckets.
+///
+/// ```
+/// use haphazard::collections::HashMap;
+///
+/// let map = HashMap::new();
+/// assert!(map.insert("a", 1));
+/// assert!(!map.insert("a", 2));
+/// assert_eq!(map.get(&"a"), Some(1));
+/// assert!(map.remove(&"a"));
+/// assert_eq!(map.get(&"a"), None);
+/// ```
+pub struct HashMap<K, V> {
+    buckets: Box<[List<K, V>]>,
+    hasher: RandomState,
+}
+
+impl<K, V> HashMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    const DEFAULT_BUCKETS: usize = 64;
+
+    /// Create a map whose nodes are retired on the global domain.
+    pub fn new() -> Self {
+        Self::with_domain(&SHARED_DOMAIN)
+    }
+
+    /// Create a map whose nodes are retired on `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain) -> Self {
+        Self::with_buckets(Self::DEFAULT_BUCKETS, domain)
+    }
+
+    /// Create a map with `buckets` buckets, whose nodes are retired on `domain`.
+    ///
+    /// # Panics
+    ///
+    /// If `buckets` is zero.
+    pub fn with_buckets(buckets: usize, domain: &'static HazPtrDomain) -> Self {
+        assert_ne!(buckets, 0, "a map needs at least one bucket");
+        Self {
+            buckets: (0..buckets)
+                .map(|_| List::new(domain, |k, key| k == key))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn bucket(&self, key: &K) -> &List<K, V> {
+        let hash = self.hasher.hash_one(key);
+        &self.buckets[(hash % self.buckets.len() as u64) as usize]
+    }
+
+    /// Add an entry for `key`, and return whether there wasn't one already.
+    ///
+    /// An existing entry is left as it is.
+    pub fn insert(&self, key: K, value: V) -> bool {
+        self.bucket(&key).insert(key, value)
+    }
+
+    /// Remove the entry for `key`, and return whether there was one.
+    pub fn remove(&self, key: &K) -> bool {
+        self.bucket(key).remove(key)
+    }
+
+    /// Whether the map has an entry for `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get_with(key, |_| ()).is_some()
+    }
+
+    /// Call `f` on the value for `key`, if there is one, while it is protected.
+    pub fn get_with<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.bucket(key).get_with(key, f)
+    }
+
+    /// Get a clone of the value for `key`, if there is one.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get_with(key, V::clone)
+    }
+
+    /// Whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(List::is_empty)
+    }
+}
+
+impl<K, V> Default for HashMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        // Few buckets, so that most keys share one.
+        let map = HashMap::with_buckets(2, &DOMAIN);
+        assert!(map.is_empty());
+        for k in 0..10 {
+            assert!(map.insert(k, k * 10));
+        }
+        assert!(!map.insert(3, 0));
+        assert_eq!(map.get(&3), Some(30));
+        assert!(map.remove(&3));
+        assert!(!map.remove(&3));
+        assert!(!map.contains_key(&3));
+        assert!((0..10)
+            .filter(|k| *k != 3)
+            .all(|k| map.get(&k) == Some(k * 10)));
+        for k in (0..10).filter(|k| *k != 3) {
+            assert!(map.remove(&k));
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn stress() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let map = HashMap::with_buckets(8, &DOMAIN);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let map = &map;
+                s.spawn(move || {
+                    // Threads fight over the same keys, but each leaves its own odd keys in.
+                    for i in 0..500 {
+                        let k = (i * 7 + t) % 64;
+                        map.insert(k, k.to_string());
+                        if let Some(v) = map.get(&k) {
+                            assert_eq!(v, k.to_string());
+                        }
+                        if k % 2 == 0 {
+                            map.remove(&k);
+                        }
+                    }
+                });
+            }
+        });
+        for k in 0..64 {
+            assert_eq!(map.contains_key(&k), k % 2 == 1, "key {}", k);
+        }
+        assert!(map.buckets.iter().all(List::is_clean));
+    }
+}
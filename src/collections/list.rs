@@ -0,0 +1,240 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sync::AtomicMut;
+use crate::tagged;
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrHolderArray, HazPtrObject, HazPtrObjectWrapper};
+
+type NodePtr<K, V> = *mut HazPtrObjectWrapper<Node<K, V>>;
+
+/// A lock-free linked list of entries with distinct keys (Harris-Michael linked list).
+///
+/// An entry is removed in two steps: first the `next` pointer of its node is marked with a tag,
+/// so that no node can be inserted after it anymore, and then the node is unlinked, by the
+/// remover or by any traversal that comes across it. Whoever unlinks a node retires it on the
+/// list's domain.
+///
+/// Traversals protect three nodes at a time: the one whose `next` pointer they may swing, the
+/// current one, and the one after it.
+///
+/// Where the traversal for a key ends is up to `stop`, which is called with the key of each node
+/// and the key looked for: at the first key that isn't smaller for an ordered list, or at the key
+/// itself for an unordered one, where new entries go at the end.
+pub(super) struct List<K, V> {
+    head: AtomicPtr<HazPtrObjectWrapper<Node<K, V>>>,
+    domain: &'static HazPtrDomain,
+    stop: fn(&K, &K) -> bool,
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // Tagged once the node is being removed.
+    next: AtomicPtr<HazPtrObjectWrapper<Node<K, V>>>,
+}
+
+// Where a key is, or would be, in the list.
+struct Position<K, V> {
+    // The pointer to cur, in the head or in a protected node.
+    prev: *const AtomicPtr<HazPtrObjectWrapper<Node<K, V>>>,
+    // The first node find stopped at, protected, or null.
+    cur: NodePtr<K, V>,
+    // The node after cur, untagged.
+    next: NodePtr<K, V>,
+}
+
+/// # Safety
+///
+/// `ptr` must point to a node that is protected, or otherwise can't be freed while the returned
+/// reference is in use.
+unsafe fn node<'a, K, V>(ptr: NodePtr<K, V>) -> &'a Node<K, V> {
+    // Safety: by the safety contract of node.
+    unsafe { &*ptr }
+}
+
+impl<K: PartialEq, V> Position<K, V> {
+    fn holds(&self, key: &K) -> bool {
+        // Safety: find left cur protected.
+        !self.cur.is_null() && unsafe { &node(self.cur).key } == key
+    }
+}
+
+impl<K: PartialEq + Send + Sync + 'static, V: Send + Sync + 'static> List<K, V> {
+    pub(super) fn new(domain: &'static HazPtrDomain, stop: fn(&K, &K) -> bool) -> Self {
+        Self {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+            domain,
+            stop,
+        }
+    }
+
+    /// Find the first node whose key `stop` accepts, unlinking any removed nodes on the way,
+    /// and leave it and the node containing `prev` protected by `holders`.
+    fn find(&self, holders: &mut [HazPtrHolder; 3], stop: impl Fn(&K) -> bool) -> Position<K, V> {
+        'retry: loop {
+            let (mut h_prev, mut h_cur, mut h_next) = (0, 1, 2);
+            let mut prev = &self.head as *const AtomicPtr<_>;
+            // Safety: the list only holds nodes allocated by insert, which are only freed by
+            // retiring them on our domain, and prev is the head.
+            let (cur, _) = unsafe { holders[h_cur].load_tagged(&*prev) };
+            let mut cur = cur.map_or(std::ptr::null_mut(), |cur| cur as *const _ as NodePtr<K, V>);
+            loop {
+                if cur.is_null() {
+                    return Position {
+                        prev,
+                        cur,
+                        next: std::ptr::null_mut(),
+                    };
+                }
+                // Safety: cur is protected, and as above.
+                let (next, mark) = unsafe { holders[h_next].load_tagged(&node(cur).next) };
+                let next = next.map_or(std::ptr::null_mut(), |next| {
+                    next as *const _ as NodePtr<K, V>
+                });
+                // Safety: prev is the head, or in a protected node.
+                if unsafe { &*prev }.load(Ordering::SeqCst) != cur {
+                    // cur was unlinked, or prev's node was marked, since we read prev. Either
+                    // way, cur may already be retired, and next with it.
+                    continue 'retry;
+                }
+                if mark == 0 {
+                    // Safety: cur is protected.
+                    if stop(unsafe { &node(cur).key }) {
+                        return Position { prev, cur, next };
+                    }
+                    // Safety: as above.
+                    prev = unsafe { &node(cur).next };
+                    (h_prev, h_cur, h_next) = (h_cur, h_next, h_prev);
+                } else {
+                    // cur is being removed; help unlink it.
+                    // Safety: as above.
+                    let unlinked = unsafe { &*prev }
+                        .compare_exchange(cur, next, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_ok();
+                    if !unlinked {
+                        continue 'retry;
+                    }
+                    // Safety: cur came from a Box in insert, and we unlinked it.
+                    unsafe { cur.retire() };
+                    (h_cur, h_next) = (h_next, h_cur);
+                }
+                cur = next;
+            }
+        }
+    }
+
+    /// Add an entry for `key`, and return whether there wasn't one already.
+    pub(super) fn insert(&self, key: K, value: V) -> bool {
+        let mut holders = HazPtrHolderArray::<3>::for_domain(self.domain);
+        let new = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            self.domain,
+            Node {
+                key,
+                value,
+                next: AtomicPtr::new(std::ptr::null_mut()),
+            },
+        )));
+        loop {
+            // Safety: node isn't shared until it is linked in below.
+            let key = unsafe { &node(new).key };
+            let pos = self.find(holders.holders(), |k| (self.stop)(k, key));
+            if pos.holds(key) {
+                // Safety: as above.
+                drop(unsafe { Box::from_raw(new) });
+                return false;
+            }
+            // Safety: as above.
+            unsafe { node(new).next.store(pos.cur, Ordering::SeqCst) };
+            // Safety: prev is the head, or in a node that find left protected.
+            if unsafe { &*pos.prev }
+                .compare_exchange(pos.cur, new, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                return true;
+            }
+        }
+    }
+
+    /// Remove the entry for `key`, and return whether there was one.
+    pub(super) fn remove(&self, key: &K) -> bool {
+        let mut holders = HazPtrHolderArray::<3>::for_domain(self.domain);
+        let stop = |k: &K| (self.stop)(k, key);
+        loop {
+            let pos = self.find(holders.holders(), stop);
+            if !pos.holds(key) {
+                return false;
+            }
+            // Mark the node as removed, which also keeps anyone from inserting after it. The
+            // exchange fails if someone else marked it first, or inserted after it.
+            // Safety: find left cur protected.
+            let marked = unsafe { &node(pos.cur).next }
+                .compare_exchange(
+                    pos.next,
+                    tagged::with_tag(pos.next, 1),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok();
+            if !marked {
+                continue;
+            }
+            // Safety: as in insert.
+            let unlinked = unsafe { &*pos.prev }
+                .compare_exchange(pos.cur, pos.next, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok();
+            if unlinked {
+                // Safety: cur came from a Box in insert, and we unlinked it.
+                unsafe { pos.cur.retire() };
+            } else {
+                // Leave it to a traversal, which unlinks marked nodes as it finds them.
+                self.find(holders.holders(), stop);
+            }
+            return true;
+        }
+    }
+
+    /// Call `f` on the value for `key` while it is protected, if there is one.
+    pub(super) fn get_with<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        let mut holders = HazPtrHolderArray::<3>::for_domain(self.domain);
+        let pos = self.find(holders.holders(), |k| (self.stop)(k, key));
+        // Safety: find left cur protected, and holders outlives the call.
+        pos.holds(key).then(|| f(unsafe { &node(pos.cur).value }))
+    }
+
+    /// Whether the list has no entries.
+    pub(super) fn is_empty(&self) -> bool {
+        let mut holders = HazPtrHolderArray::<3>::for_domain(self.domain);
+        // Removed nodes that are still linked in don't count.
+        self.find(holders.holders(), |_| true).cur.is_null()
+    }
+
+    /// Whether no removed nodes are still linked in, which holds once all removals are done.
+    #[cfg(all(test, not(loom)))]
+    pub(super) fn is_clean(&self) -> bool {
+        let mut n = self.head.load(Ordering::SeqCst);
+        while !n.is_null() {
+            // Safety: callers don't run this concurrently with removals, so nothing linked in
+            // is retired.
+            let next = unsafe { node(n) }.next.load(Ordering::SeqCst);
+            if tagged::tag(next) != 0 {
+                return false;
+            }
+            n = next;
+        }
+        true
+    }
+}
+
+impl<K, V> Drop for List<K, V> {
+    fn drop(&mut self) {
+        let mut node = self.head.load_mut();
+        while !node.is_null() {
+            // Safety: we have exclusive access to the list, so the nodes still linked in are
+            // ours. Nodes that were unlinked have been retired by whoever unlinked them.
+            let mut n = unsafe { Box::from_raw(node) };
+            node = tagged::untagged(n.next.load_mut());
+        }
+    }
+}
+
+// Safety: entries are shared with readers on any thread, and dropped on any thread.
+unsafe impl<K: Send + Sync, V: Send + Sync> Send for List<K, V> {}
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for List<K, V> {}
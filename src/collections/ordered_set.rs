@@ -0,0 +1,126 @@
+use super::list::List;
+use crate::{HazPtrDomain, SHARED_DOMAIN};
+
+/// A lock-free set of keys kept in order (Harris-Michael linked list).
+///
+/// A key is removed in two steps: first the `next` pointer of its node is marked with a tag, so
+/// that no node can be inserted after it anymore, and then the node is unlinked, by the remover
+/// or by any traversal that comes across it. Whoever unlinks a node retires it on the set's
+/// domain.
+///
+/// Traversals protect three nodes at a time: the one whose `next` pointer they may swing, the
+/// current one, and the one after it.
+///
+/// ```
+/// use haphazard::collections::OrderedSet;
+///
+/// let set = OrderedSet::new();
+/// assert!(set.insert(2));
+/// assert!(set.insert(1));
+/// assert!(!set.insert(2));
+/// assert!(set.contains(&1));
+/// assert!(set.remove(&1));
+/// assert!(!set.contains(&1));
+/// ```
+pub struct OrderedSet<K> {
+    list: List<K, ()>,
+}
+
+impl<K: Ord + Send + Sync + 'static> OrderedSet<K> {
+    /// Create a set whose nodes are retired on the global domain.
+    pub fn new() -> Self {
+        Self::with_domain(&SHARED_DOMAIN)
+    }
+
+    /// Create a set whose nodes are retired on `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain) -> Self {
+        Self {
+            list: List::new(domain, |k, key| k >= key),
+        }
+    }
+
+    /// Add `key` to the set, and return whether it wasn't in the set already.
+    pub fn insert(&self, key: K) -> bool {
+        self.list.insert(key, ())
+    }
+
+    /// Remove `key` from the set, and return whether it was in the set.
+    pub fn remove(&self, key: &K) -> bool {
+        self.list.remove(key)
+    }
+
+    /// Whether `key` is in the set.
+    pub fn contains(&self, key: &K) -> bool {
+        self.list.get_with(key, |_| ()).is_some()
+    }
+
+    /// Whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+impl<K: Ord + Send + Sync + 'static> Default for OrderedSet<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_remove_contains() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let set = OrderedSet::with_domain(&DOMAIN);
+        assert!(set.is_empty());
+        for k in [5, 1, 3, 2, 4] {
+            assert!(set.insert(k));
+        }
+        assert!(!set.insert(3));
+        assert!(!set.is_empty());
+        assert!(set.remove(&3));
+        assert!(!set.remove(&3));
+        assert!(!set.contains(&3));
+        assert!((1..=5).filter(|k| *k != 3).all(|k| set.contains(&k)));
+        for k in [1, 2, 4, 5] {
+            assert!(set.remove(&k));
+        }
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn stress() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let set = OrderedSet::with_domain(&DOMAIN);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let set = &set;
+                s.spawn(move || {
+                    // Threads fight over the same keys, but each leaves its own odd keys in.
+                    for i in 0..500 {
+                        let k = (i * 7 + t) % 64;
+                        set.insert(k);
+                        set.contains(&k);
+                        if k % 2 == 0 {
+                            set.remove(&k);
+                        }
+                    }
+                    for i in 0..500 {
+                        let k = (i * 7 + t) % 64;
+                        if k % 2 == 1 {
+                            assert!(set.contains(&k));
+                        }
+                    }
+                });
+            }
+        });
+        for k in 0..64 {
+            let expected = k % 2 == 1 && (0..4).any(|t| (0..500).any(|i| (i * 7 + t) % 64 == k));
+            assert_eq!(set.contains(&k), expected, "key {}", k);
+        }
+        // Removed nodes have all been unlinked and retired.
+        assert!(set.list.is_clean());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sync::AtomicMut;
+use crate::{HazPtrDomain, HazPtrHolderArray, HazPtrObject, HazPtrObjectWrapper, SHARED_DOMAIN};
+use std::mem::MaybeUninit;
+
+/// A lock-free, unbounded, multi-producer multi-consumer queue (Michael-Scott queue).
+///
+/// The queue always starts with a sentinel node, and the values live in the nodes after it.
+/// Dequeuing protects the sentinel and the node after it, makes that node the new sentinel, and
+/// retires the old one on the queue's domain.
+///
+/// ```
+/// use haphazard::collections::Queue;
+///
+/// let queue = Queue::new();
+/// queue.enqueue(1);
+/// queue.enqueue(2);
+/// assert_eq!(queue.dequeue(), Some(1));
+/// assert_eq!(queue.dequeue(), Some(2));
+/// assert_eq!(queue.dequeue(), None);
+/// ```
+pub struct Queue<T> {
+    head: AtomicPtr<HazPtrObjectWrapper<Node<T>>>,
+    tail: AtomicPtr<HazPtrObjectWrapper<Node<T>>>,
+    domain: &'static HazPtrDomain,
+}
+
+struct Node<T> {
+    // Uninitialized in the first sentinel. Moved out by whoever makes the node the sentinel, so
+    // it is never dropped with the node.
+    value: MaybeUninit<T>,
+    next: AtomicPtr<HazPtrObjectWrapper<Node<T>>>,
+}
+
+impl<T: Send + 'static> Queue<T> {
+    /// Create a queue whose nodes are retired on the global domain.
+    pub fn new() -> Self {
+        Self::with_domain(&SHARED_DOMAIN)
+    }
+
+    /// Create a queue whose nodes are retired on `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain) -> Self {
+        let sentinel = Self::node(domain, MaybeUninit::uninit());
+        Self {
+            head: AtomicPtr::new(sentinel),
+            tail: AtomicPtr::new(sentinel),
+            domain,
+        }
+    }
+
+    fn node(
+        domain: &'static HazPtrDomain,
+        value: MaybeUninit<T>,
+    ) -> *mut HazPtrObjectWrapper<Node<T>> {
+        Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            domain,
+            Node {
+                value,
+                next: AtomicPtr::new(std::ptr::null_mut()),
+            },
+        )))
+    }
+
+    /// Add `value` to the back of the queue.
+    pub fn enqueue(&self, value: T) {
+        let node = Self::node(self.domain, MaybeUninit::new(value));
+        let mut holders = HazPtrHolderArray::<1>::for_domain(self.domain);
+        let [h] = holders.holders();
+        loop {
+            // Safety: tail only holds nodes allocated by node, which are only freed by retiring
+            // them on our domain.
+            let tail = unsafe { h.load(&self.tail) }.expect("never null");
+            let tail_ptr = tail as *const HazPtrObjectWrapper<Node<T>> as *mut _;
+            let next = tail.next.load(Ordering::SeqCst);
+            if !next.is_null() {
+                // The tail is lagging behind; help move it along.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail_ptr, next, Ordering::SeqCst, Ordering::SeqCst);
+                continue;
+            }
+            if tail
+                .next
+                .compare_exchange(next, node, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                // Whoever fails here found the tail already moved along by someone else.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail_ptr, node, Ordering::SeqCst, Ordering::SeqCst);
+                return;
+            }
+        }
+    }
+
+    /// Remove the value at the front of the queue, or return `None` if it is empty.
+    pub fn dequeue(&self) -> Option<T> {
+        let mut holders = HazPtrHolderArray::<2>::for_domain(self.domain);
+        let [h_head, h_next] = holders.holders();
+        loop {
+            // Safety: as in enqueue.
+            let head = unsafe { h_head.load(&self.head) }.expect("never null");
+            let head_ptr = head as *const HazPtrObjectWrapper<Node<T>> as *mut _;
+            // Safety: as in enqueue.
+            let next = unsafe { h_next.load(&head.next) };
+            // next is only retired once the head has moved past it, which it can't have if the
+            // head is still where we found it.
+            if self.head.load(Ordering::SeqCst) != head_ptr {
+                continue;
+            }
+            let next = next?;
+            let next_ptr = next as *const HazPtrObjectWrapper<Node<T>> as *mut _;
+            let tail = self.tail.load(Ordering::SeqCst);
+            if tail == head_ptr {
+                // The tail is lagging behind the node we are about to dequeue; move it along
+                // first, so that it never points to a retired node.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next_ptr, Ordering::SeqCst, Ordering::SeqCst);
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head_ptr, next_ptr, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                // Safety: we made next the sentinel, so nobody else takes its value, and it was
+                // initialized by enqueue.
+                let value = unsafe { next.value.assume_init_read() };
+                h_head.reset();
+                h_next.reset();
+                // Safety: the old sentinel came from a Box, and is no longer reachable.
+                unsafe { head_ptr.retire() };
+                return Some(value);
+            }
+        }
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        let mut holders = HazPtrHolderArray::<1>::for_domain(self.domain);
+        let [h] = holders.holders();
+        // Safety: as in enqueue.
+        let head = unsafe { h.load(&self.head) }.expect("never null");
+        head.next.load(Ordering::SeqCst).is_null()
+    }
+}
+
+impl<T: Send + 'static> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        let mut node = self.head.load_mut();
+        let mut sentinel = true;
+        while !node.is_null() {
+            // Safety: we have exclusive access to the queue, so the nodes are ours, and readers
+            // of a queue never hold on to a node past the operation that protected it.
+            let mut n = unsafe { Box::from_raw(node) };
+            if !sentinel {
+                // Safety: every node but the sentinel holds a value that was never dequeued.
+                unsafe { n.value.assume_init_drop() };
+            }
+            sentinel = false;
+            node = n.next.load_mut();
+        }
+    }
+}
+
+// Safety: values are moved in and out of the queue on any thread, but never shared.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn fifo_order() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let queue = Queue::with_domain(&DOMAIN);
+        assert!(queue.is_empty());
+        for i in 0..10 {
+            queue.enqueue(i);
+        }
+        assert!(!queue.is_empty());
+        assert_eq!(
+            std::iter::from_fn(|| queue.dequeue()).collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn stress() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let queue = Queue::with_domain(&DOMAIN);
+        let received: Vec<_> = std::thread::scope(|s| {
+            for t in 0..4 {
+                let queue = &queue;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        queue.enqueue((t, i));
+                    }
+                });
+            }
+            let consumers: Vec<_> = (0..4)
+                .map(|_| {
+                    let queue = &queue;
+                    s.spawn(move || {
+                        let mut received = Vec::new();
+                        while received.len() < 1000 {
+                            if let Some(v) = queue.dequeue() {
+                                received.push(v);
+                            }
+                        }
+                        received
+                    })
+                })
+                .collect();
+            consumers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+
+        // Every consumer sees each producer's values in the order they were enqueued.
+        let mut all = Vec::new();
+        for received in received {
+            for t in 0..4 {
+                let from_t: Vec<_> = received.iter().filter(|v| v.0 == t).collect();
+                assert!(from_t.windows(2).all(|w| w[0].1 < w[1].1));
+            }
+            all.extend(received);
+        }
+        all.sort_unstable();
+        assert_eq!(all.len(), 4000);
+        all.dedup();
+        assert_eq!(all.len(), 4000);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn drop_drops_values() {
+        let value = Arc::new(());
+        let queue = Queue::new();
+        for _ in 0..3 {
+            queue.enqueue(Arc::clone(&value));
+        }
+        drop(queue.dequeue());
+        drop(queue);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn enqueue_races_dequeue() {
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(2);
+        builder.check(|| {
+            let domain: &'static HazPtrDomain = Box::leak(Box::new(HazPtrDomain::new()));
+            let queue = Arc::new(Queue::with_domain(domain));
+            queue.enqueue(1);
+
+            let producer = thread::spawn({
+                let queue = Arc::clone(&queue);
+                move || queue.enqueue(2)
+            });
+            let first = queue.dequeue();
+            producer.join().unwrap();
+            let second = queue.dequeue();
+
+            assert_eq!(first, Some(1));
+            assert_eq!(second, Some(2));
+            assert!(queue.dequeue().is_none());
+            domain.eager_reclaim(false);
+        });
+    }
+}
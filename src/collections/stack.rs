@@ -0,0 +1,167 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::sync::AtomicMut;
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, SHARED_DOMAIN};
+use std::mem::ManuallyDrop;
+
+/// A lock-free stack (Treiber stack).
+///
+/// Popping protects the top node with a hazard pointer while reading the node below it, so the
+/// top can't be freed, and its address reused, between that read and swinging the top over to
+/// the node below. Popped nodes are retired on the stack's domain.
+///
+/// ```
+/// use haphazard::collections::Stack;
+///
+/// let stack = Stack::new();
+/// stack.push(1);
+/// stack.push(2);
+/// assert_eq!(stack.pop(), Some(2));
+/// assert_eq!(stack.pop(), Some(1));
+/// assert_eq!(stack.pop(), None);
+/// ```
+pub struct Stack<T> {
+    head: AtomicPtr<HazPtrObjectWrapper<Node<T>>>,
+    domain: &'static HazPtrDomain,
+}
+
+struct Node<T> {
+    // Moved out by whoever pops the node, so it is never dropped with the node.
+    value: ManuallyDrop<T>,
+    next: *mut HazPtrObjectWrapper<Node<T>>,
+}
+
+impl<T: Send + 'static> Stack<T> {
+    /// Create a stack whose nodes are retired on the global domain.
+    pub fn new() -> Self {
+        Self::with_domain(&SHARED_DOMAIN)
+    }
+
+    /// Create a stack whose nodes are retired on `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain) -> Self {
+        Self {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+            domain,
+        }
+    }
+
+    /// Push `value` onto the stack.
+    pub fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            self.domain,
+            Node {
+                value: ManuallyDrop::new(value),
+                next: std::ptr::null_mut(),
+            },
+        )));
+        let mut head = self.head.load(Ordering::SeqCst);
+        loop {
+            // Safety: node isn't shared until the exchange below succeeds.
+            unsafe { (&mut *node).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(now) => head = now,
+            }
+        }
+    }
+
+    /// Pop the value on top of the stack, or return `None` if it is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        loop {
+            // Safety: head only holds nodes allocated by push, which are only freed by retiring
+            // them on our domain.
+            let top = unsafe { holder.load(&self.head) }?;
+            let ptr = top as *const HazPtrObjectWrapper<Node<T>> as *mut _;
+            if self
+                .head
+                .compare_exchange(ptr, top.next, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                // Safety: we unlinked the node, so nobody else takes its value, and the node
+                // never drops it.
+                let value = unsafe { std::ptr::read(&*top.value) };
+                holder.reset();
+                // Safety: the node came from a Box in push, and is no longer reachable.
+                unsafe { ptr.retire() };
+                return Some(value);
+            }
+        }
+    }
+
+    /// Whether the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::SeqCst).is_null()
+    }
+}
+
+impl<T: Send + 'static> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        let mut node = self.head.load_mut();
+        while !node.is_null() {
+            // Safety: we have exclusive access to the stack, so the nodes are ours, and readers
+            // of a stack never hold on to a node past the pop that protected it.
+            let mut n = unsafe { Box::from_raw(node) };
+            // Safety: the value was never popped.
+            unsafe { ManuallyDrop::drop(&mut n.value) };
+            node = n.next;
+        }
+    }
+}
+
+// Safety: values are moved in and out of the stack on any thread, but never shared.
+unsafe impl<T: Send> Send for Stack<T> {}
+unsafe impl<T: Send> Sync for Stack<T> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_push_pop() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let stack = Stack::with_domain(&DOMAIN);
+        let popped = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (stack, popped) = (&stack, &popped);
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        stack.push(t * 1000 + i);
+                        if let Some(v) = stack.pop() {
+                            popped.fetch_add(v, Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
+        });
+        let mut rest = 0;
+        while let Some(v) = stack.pop() {
+            rest += v;
+        }
+        assert_eq!(popped.into_inner() + rest, (0..4000).sum::<usize>());
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn drop_drops_values() {
+        let value = Arc::new(());
+        let stack = Stack::new();
+        for _ in 0..3 {
+            stack.push(Arc::clone(&value));
+        }
+        drop(stack.pop());
+        drop(stack);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use crate::sync::{AtomicMut, Mutex};
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, SHARED_DOMAIN};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A value that writers replace and readers can both read and wait on.
+///
+/// Readers get at the latest value through a [`HazPtrHolder`], exactly as with an
+/// [`AtomicBox`](crate::AtomicBox), and can additionally wait for the next [`WatchCell::store`]
+/// with [`WatchCell::changed`]. Every store bumps a version number, which readers use to tell
+/// which values they have already seen.
+///
+/// Replaced values are retired, and may be dropped on any thread, so `T` must be `Send`.
+pub struct WatchCell<T: Send + 'static> {
+    value: AtomicPtr<HazPtrObjectWrapper<T>>,
+    version: AtomicU64,
+    // Only bumped with this lock held, so waiters can't miss a store between checking the
+    // version and registering their waker.
+    wakers: Mutex<Vec<Waker>>,
+    domain: &'static HazPtrDomain,
+}
+
+impl<T: Send + 'static> WatchCell<T> {
+    /// Create a `WatchCell` whose values belong to the global domain.
+    pub fn new(value: T) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, value)
+    }
+
+    /// Create a `WatchCell` whose values belong to `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain, value: T) -> Self {
+        Self {
+            value: AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                domain, value,
+            )))),
+            version: AtomicU64::new(0),
+            wakers: Mutex::new(Vec::new()),
+            domain,
+        }
+    }
+
+    /// The number of stores so far.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Protect the latest value with `holder`, and return a reference to it.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this cell's domain.
+    pub fn load<'l>(&self, holder: &'l mut HazPtrHolder) -> &'l T {
+        self.load_versioned(holder).0
+    }
+
+    /// Like [`WatchCell::load`], but also returns the version of the value, or a later one.
+    ///
+    /// Passing that version to [`WatchCell::changed`] waits for a value newer than the one
+    /// returned.
+    pub fn load_versioned<'l>(&self, holder: &'l mut HazPtrHolder) -> (&'l T, u64) {
+        assert!(
+            std::ptr::eq(holder.domain, self.domain),
+            "holder is for a different domain"
+        );
+        // Read the version first: stores publish the value before bumping the version, so the
+        // value we load is at least as new as this.
+        let version = self.version();
+        // Safety: value always holds a valid Box, only deallocated by retiring it in our
+        // domain.
+        let value = unsafe { holder.load(&self.value) }.expect("never null");
+        (value, version)
+    }
+
+    /// Replace the value, and wake everyone waiting for a change.
+    pub fn store(&self, value: T) {
+        let new = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            self.domain,
+            value,
+        )));
+        let old = self.value.swap(new, Ordering::SeqCst);
+
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap();
+            self.version.fetch_add(1, Ordering::SeqCst);
+            std::mem::take(&mut *wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+
+        // Safety: old came from a Box, is no longer reachable, and drop_box matches it.
+        unsafe { old.retire() };
+    }
+
+    /// Wait until the version is no longer `seen`, and return the new version.
+    pub fn changed(&self, seen: u64) -> Changed<'_, T> {
+        Changed { cell: self, seen }
+    }
+}
+
+impl<T: Send + 'static> Drop for WatchCell<T> {
+    fn drop(&mut self) {
+        let old = self.value.load_mut();
+        // Safety: as in store, and we're going away.
+        unsafe { old.retire() };
+    }
+}
+
+// Safety: values are shared with readers on any thread, and stored and dropped on any thread.
+unsafe impl<T: Send + Sync + 'static> Send for WatchCell<T> {}
+unsafe impl<T: Send + Sync + 'static> Sync for WatchCell<T> {}
+
+/// The future returned by [`WatchCell::changed`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Changed<'a, T: Send + 'static> {
+    cell: &'a WatchCell<T>,
+    seen: u64,
+}
+
+impl<T: Send + 'static> Future for Changed<'_, T> {
+    type Output = u64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+        let mut wakers = self.cell.wakers.lock().unwrap();
+        let version = self.cell.version();
+        if version != self.seen {
+            return Poll::Ready(version);
+        }
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(v) => break v,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn wait_for_changes() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let cell = WatchCell::with_domain(&DOMAIN, 0);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        assert_eq!(cell.load_versioned(&mut h), (&0, 0));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut h = HazPtrHolder::for_domain(&DOMAIN);
+                let mut seen = 0;
+                while seen < 10 {
+                    seen = block_on(cell.changed(seen));
+                    let (&value, version) = cell.load_versioned(&mut h);
+                    assert!(version >= seen);
+                    assert!(value >= seen as i32);
+                }
+            });
+            for i in 1..=10 {
+                cell.store(i);
+            }
+        });
+        h.reset();
+        assert_eq!(*cell.load(&mut h), 10);
+        assert_eq!(cell.version(), 10);
+    }
+}
@@ -0,0 +1,184 @@
+//! `extern "C"` functions for sharing a domain with C and C++ code.
+//!
+//! Domains and holders are opaque to C, and only handled through pointers these functions hand
+//! out. Objects are retired with an [`AbiDeleter`], as through the [`abi`](crate::abi) module,
+//! and readers on either side protect them by the address they were retired by. Sources are
+//! plain `void *` slots that both sides only access atomically (an `_Atomic(void *)` in C, or a
+//! `std::atomic<void *>` in C++), which have the layout of an `AtomicPtr<c_void>`.
+//!
+//! The declarations are in `include/haphazard.h`, which `cbindgen` regenerates from this module
+//! with the settings in `cbindgen.toml`. To link C code against them, build a `staticlib` or
+//! `cdylib` crate that depends on this one with the `ffi` feature.
+
+use crate::abi::AbiDeleter;
+use crate::{HazPtrDomain, HazPtrHolder};
+use std::ffi::c_void;
+use std::sync::atomic::AtomicPtr;
+
+/// Create a new domain. It lives until passed to `haphazard_domain_free`.
+#[no_mangle]
+pub extern "C" fn haphazard_domain_new() -> *mut HazPtrDomain {
+    Box::into_raw(Box::new(HazPtrDomain::new()))
+}
+
+/// The global domain, which is never freed.
+#[no_mangle]
+pub extern "C" fn haphazard_domain_global() -> *const HazPtrDomain {
+    HazPtrDomain::global()
+}
+
+/// Reclaim every object retired on `domain`, and free it.
+///
+/// # Safety
+///
+/// `domain` must have come from `haphazard_domain_new`, and no holder for it may be left, nor
+/// any object of it be retired after this.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_domain_free(domain: *mut HazPtrDomain) {
+    // Safety: by the safety contract of haphazard_domain_free.
+    drop(unsafe { Box::from_raw(domain) });
+}
+
+/// Retire `object` on `domain`, to be freed with `deleter` once no holder protects it anymore.
+///
+/// # Safety
+///
+/// `domain` must be valid. `object` must no longer be reachable by new readers, must not have
+/// been retired before, and must be freed by `deleter`. `deleter.context` must stay valid until
+/// the deleter has run, and `deleter` may be run on any thread.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_retire(
+    domain: *const HazPtrDomain,
+    object: *mut c_void,
+    deleter: AbiDeleter,
+) {
+    // Safety: domains handed to C live until they are freed, which the caller hasn't done yet.
+    let domain: &'static HazPtrDomain = unsafe { &*domain };
+    // Safety: by the safety contract of haphazard_retire.
+    unsafe { domain.abi().retire(object, deleter) };
+}
+
+/// Reclaim the objects retired on `domain` that aren't protected, and return how many there
+/// were.
+///
+/// # Safety
+///
+/// `domain` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_eager_reclaim(domain: *const HazPtrDomain) -> usize {
+    // Safety: by the safety contract of haphazard_eager_reclaim.
+    unsafe { &*domain }.eager_reclaim(false)
+}
+
+/// Create a holder for `domain`. It protects at most one object at a time, and must be freed
+/// with `haphazard_holder_free`.
+///
+/// # Safety
+///
+/// `domain` must be valid, and stay valid until the holder is freed.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_holder_new(domain: *const HazPtrDomain) -> *mut HazPtrHolder {
+    // Safety: by the safety contract of haphazard_holder_new.
+    let domain: &'static HazPtrDomain = unsafe { &*domain };
+    Box::into_raw(Box::new(HazPtrHolder::for_domain(domain)))
+}
+
+/// Release whatever `holder` protects, and free it.
+///
+/// # Safety
+///
+/// `holder` must have come from `haphazard_holder_new`, and not be used after this.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_holder_free(holder: *mut HazPtrHolder) {
+    // Safety: by the safety contract of haphazard_holder_free.
+    drop(unsafe { Box::from_raw(holder) });
+}
+
+/// Protect the object in `src` with `holder`, in place of whatever it protected before, and
+/// return it. The object stays valid until the holder is reset, protects another object, or is
+/// freed. Returns null, with nothing protected, if `src` holds null.
+///
+/// `src` is an atomic slot, passed as a plain pointer to it since C++ has no `_Atomic`. In C,
+/// pass an `_Atomic(void *)` as `(void *const *)&slot`; in C++, pass a `std::atomic<void *>`
+/// as `reinterpret_cast<void *const *>(&slot)`.
+///
+/// # Safety
+///
+/// `holder` must be valid and not in use by another thread. `src` must be valid, and only ever
+/// be accessed atomically. The objects in it must only be freed by retiring them on the
+/// holder's domain.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_holder_protect(
+    holder: *mut HazPtrHolder,
+    src: *const *mut c_void,
+) -> *mut c_void {
+    // Safety: by the safety contract of haphazard_holder_protect, and AtomicPtr has the same
+    // layout as a pointer.
+    let (holder, src) = unsafe { (&mut *holder, &*(src as *const AtomicPtr<c_void>)) };
+    // Safety: by the safety contract of haphazard_holder_protect.
+    let protected = unsafe { holder.load(src) };
+    protected.map_or(std::ptr::null_mut(), |object| {
+        object as *const c_void as *mut c_void
+    })
+}
+
+/// Stop protecting whatever `holder` protects.
+///
+/// # Safety
+///
+/// `holder` must be valid and not in use by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn haphazard_holder_reset(holder: *mut HazPtrHolder) {
+    // Safety: by the safety contract of haphazard_holder_reset.
+    unsafe { &mut *holder }.reset();
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn free_u64(_: *mut c_void, object: *mut c_void) {
+        // Safety: the test only retires Box<u64>s with this deleter.
+        drop(unsafe { Box::from_raw(object as *mut u64) });
+        FREED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn as_seen_from_c() {
+        let domain = haphazard_domain_new();
+        let src: *mut c_void = Box::into_raw(Box::new(42u64)).cast();
+        let src = AtomicPtr::new(src);
+        let src_ptr = &src as *const AtomicPtr<c_void> as *const *mut c_void;
+
+        // Safety: the domain is freed last, src only holds a Box<u64>, retired with free_u64,
+        // and the holder is only used here.
+        unsafe {
+            let holder = haphazard_holder_new(domain);
+            let object = haphazard_holder_protect(holder, src_ptr);
+            assert_eq!(*(object as *const u64), 42);
+
+            let deleter = AbiDeleter {
+                context: std::ptr::null_mut(),
+                delete: free_u64,
+            };
+            haphazard_retire(
+                domain,
+                src.swap(std::ptr::null_mut(), Ordering::SeqCst),
+                deleter,
+            );
+            assert_eq!(haphazard_eager_reclaim(domain), 0);
+            assert_eq!(*(object as *const u64), 42);
+
+            haphazard_holder_reset(holder);
+            assert_eq!(haphazard_eager_reclaim(domain), 1);
+            assert_eq!(FREED.load(Ordering::SeqCst), 1);
+            assert!(haphazard_holder_protect(holder, src_ptr).is_null());
+
+            haphazard_holder_free(holder);
+            haphazard_domain_free(domain);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! An operations model for structure-aware fuzzing of the reclamation machinery.
+//!
+//! A fuzz target feeds a sequence of [`Op`]s (typically derived with [`arbitrary`]) to [`run`],
+//! which interprets them against the shared domain using a handful of simulated reader threads
+//! and one writer, and panics if a domain invariant is ever violated:
+//!
+//!  - an object is never dropped while a reader has it protected;
+//!  - an object is never dropped twice;
+//!  - once all protections are gone, a blocking reclaim reclaims every retired object.
+//!
+//! The simulated threads are interleaved deterministically on the calling thread, so failing
+//! inputs reproduce reliably. The same model doubles as the scheduler for the Kani proof
+//! harnesses, which explore all short operation sequences instead of fuzzed ones.
+
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::{HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, SHARED_DOMAIN};
+#[cfg(fuzzing)]
+use arbitrary::Arbitrary;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// The number of simulated reader threads.
+///
+/// This is kept smaller under Kani to bound the state space.
+pub const READERS: usize = if cfg!(kani) { 2 } else { 4 };
+
+/// A single step of the model.
+#[cfg_attr(fuzzing, derive(Arbitrary))]
+#[cfg_attr(kani, derive(kani::Arbitrary))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// The given reader protects the current value (dropping any earlier protection).
+    Protect(u8),
+    /// The given reader resets its holder.
+    Reset(u8),
+    /// The writer replaces the current value and retires the old one.
+    Swap,
+    /// Run a non-blocking reclamation pass.
+    Reclaim,
+    /// Reclaim a single object with [`HazPtrDomain::step`](crate::HazPtrDomain::step).
+    Step,
+}
+
+/// The observable state of the model after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    /// The number of objects retired on the domain but not yet reclaimed.
+    pub retired: usize,
+    /// The ids of all objects that have been allocated but not yet dropped.
+    pub live: HashSet<u64>,
+    /// The id of the object each reader has protected, if any.
+    pub protected: [Option<u64>; READERS],
+    /// The id of the current value.
+    pub current: u64,
+}
+
+struct Tracked {
+    id: u64,
+    live: Arc<Mutex<HashSet<u64>>>,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        assert!(
+            self.live.lock().unwrap().remove(&self.id),
+            "object {} dropped twice",
+            self.id
+        );
+    }
+}
+
+/// Interpret `ops`, panicking if any invariant is violated.
+pub fn run(ops: &[Op]) {
+    run_with(ops, |_, _| {})
+}
+
+/// Like [`run`], but calls `observe` with the state of the model after each operation.
+pub fn run_with<F>(ops: &[Op], mut observe: F)
+where
+    F: FnMut(&Op, &State),
+{
+    let live = Arc::new(Mutex::new(HashSet::new()));
+    let mut next_id = 0;
+    let mut new_object = || {
+        let id = next_id;
+        next_id += 1;
+        live.lock().unwrap().insert(id);
+        Box::into_raw(Box::new(HazPtrObjectWrapper::with_default_domain(
+            Tracked {
+                id,
+                live: Arc::clone(&live),
+            },
+        )))
+    };
+
+    let slot = AtomicPtr::new(new_object());
+    let mut holders: Vec<_> = (0..READERS).map(|_| HazPtrHolder::default()).collect();
+    let mut protected: [Option<*const Tracked>; READERS] = [None; READERS];
+
+    for op in ops {
+        match *op {
+            Op::Protect(reader) => {
+                let reader = reader as usize % READERS;
+                // Safety: slot always holds a valid Box, and is only retired below.
+                let tracked = unsafe { holders[reader].load(&slot) }.expect("never null");
+                protected[reader] = Some(&**tracked as *const Tracked);
+            }
+            Op::Reset(reader) => {
+                let reader = reader as usize % READERS;
+                holders[reader].reset();
+                protected[reader] = None;
+            }
+            Op::Swap => {
+                let old = slot.swap(new_object(), Ordering::SeqCst);
+                // Safety: old came from a Box, and is no longer accessible through slot.
+                unsafe { old.retire() };
+            }
+            Op::Reclaim => {
+                SHARED_DOMAIN.eager_reclaim(false);
+            }
+            Op::Step => {
+                SHARED_DOMAIN.step();
+            }
+        }
+
+        let mut state = State {
+            retired: SHARED_DOMAIN.retired.count.load(Ordering::SeqCst),
+            live: live.lock().unwrap().clone(),
+            protected: [None; READERS],
+            current: 0,
+        };
+        for (reader, tracked) in protected.iter().enumerate() {
+            if let Some(tracked) = tracked {
+                // Safety: the reader's holder still protects tracked, so it must be valid.
+                // If it isn't, that's exactly the bug we're looking for.
+                let id = unsafe { &**tracked }.id;
+                assert!(
+                    state.live.contains(&id),
+                    "protected object {} was dropped",
+                    id
+                );
+                state.protected[reader] = Some(id);
+            }
+        }
+        // Safety: slot always holds a valid Box, and nothing retires it while we look.
+        state.current = unsafe { &*slot.load(Ordering::SeqCst) }.id;
+        observe(op, &state);
+    }
+
+    drop(holders);
+    SHARED_DOMAIN.eager_reclaim(true);
+    let remaining = live.lock().unwrap().clone();
+    // Safety: slot holds a valid Box that was never retired.
+    let current = unsafe { Box::from_raw(slot.into_inner()) };
+    assert_eq!(
+        remaining,
+        std::iter::once(current.id).collect(),
+        "retired objects were not reclaimed"
+    );
+}
@@ -0,0 +1,129 @@
+use crate::{AtomicBox, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, Protected};
+use std::ops::Deref;
+
+/// A value that can be read and replaced concurrently, without any `unsafe`.
+///
+/// A `HazardBox` is an [`AtomicBox`] in the global domain that manages hazard pointers itself:
+/// every read hands out a [`HazardGuard`] with a holder of its own, and replaced values are
+/// retired automatically.
+///
+/// ```
+/// use haphazard::HazardBox;
+///
+/// let config = HazardBox::new(String::from("v1"));
+/// let old = config.load();
+/// config.store(String::from("v2"));
+/// assert_eq!(*old, "v1");
+/// assert_eq!(*config.load(), "v2");
+/// ```
+///
+/// Replaced values are dropped on whichever thread reclaims them, so `T` must be `Send`:
+///
+/// ```compile_fail
+/// let b = haphazard::HazardBox::new(std::rc::Rc::new(1));
+/// ```
+///
+/// A [`HazardGuard`] hands out `&T`, so it can only move to another thread if `T` is `Sync`:
+///
+/// ```compile_fail
+/// let b = haphazard::HazardBox::new(std::cell::Cell::new(1));
+/// let guard = b.load();
+/// std::thread::spawn(move || guard.get()).join().unwrap();
+/// ```
+pub struct HazardBox<T: Send + 'static> {
+    inner: AtomicBox<HazPtrObjectWrapper<T>>,
+}
+
+/// A protected value read from a [`HazardBox`], which stays valid until the guard is dropped.
+pub struct HazardGuard<T: 'static> {
+    inner: Protected<HazPtrObjectWrapper<T>>,
+}
+
+impl<T: Send + 'static> HazardBox<T> {
+    /// Create a `HazardBox` holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: AtomicBox::new(HazPtrObjectWrapper::with_default_domain(value)),
+        }
+    }
+
+    /// Read the current value.
+    pub fn load(&self) -> HazardGuard<T> {
+        HazardGuard {
+            inner: self.inner.load_owned().expect("HazardBox is never empty"),
+        }
+    }
+
+    /// Replace the current value with `value`. The old value is dropped once no guard refers to
+    /// it anymore.
+    pub fn store(&self, value: T) {
+        drop(self.swap(value));
+    }
+
+    /// Replace the current value with `value`, and return a guard for the old one.
+    pub fn swap(&self, value: T) -> HazardGuard<T> {
+        let old = self
+            .inner
+            .replace(HazPtrObjectWrapper::with_default_domain(value));
+        let mut holder = HazPtrHolder::default();
+        // Nobody else can retire old, so it needs no validation before we do.
+        holder.hazptr().protect(old as *mut u8);
+        // Safety:
+        //  1. old came from a Box, and has not been retired yet.
+        //  2. It is no longer reachable through the box.
+        //  3. drop_box is the right deleter for a Box.
+        unsafe { old.retire() };
+        HazardGuard {
+            inner: Protected {
+                holder,
+                // Safety: old came from a Box.
+                ptr: unsafe { std::ptr::NonNull::new_unchecked(old) },
+            },
+        }
+    }
+}
+
+impl<T: 'static> Deref for HazardGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: std::fmt::Debug + 'static> std::fmt::Debug for HazardGuard<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::HazPtrDomain;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountDrops(u32, Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn safe_reads_and_writes() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let b = HazardBox::new(CountDrops(1, Arc::clone(&drops)));
+
+        let first = b.load();
+        let second = b.swap(CountDrops(2, Arc::clone(&drops)));
+        b.store(CountDrops(3, Arc::clone(&drops)));
+        assert_eq!((first.0, second.0, b.load().0), (1, 1, 3));
+
+        drop((first, second));
+        drop(b);
+        HazPtrDomain::global().eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,163 @@
+//! Hazard pointers for slot indices into an arena, rather than addresses.
+//!
+//! Node layouts that link nodes by 32-bit indices into an arena or slab can't be protected by
+//! address. Instead, an [`AtomicIndex`] takes the place of the `AtomicPtr`, hazards record the
+//! protected index, and retired indices are handed back to an [`IndexResolver`] (typically the
+//! arena's free list) once no reader protects them anymore.
+//!
+//! Hazards for indices are indistinguishable from hazards for small addresses, and from the help
+//! requests of [`HazPtrHolder::load_bounded`], so indices must be protected and retired on a
+//! domain of their own, which is used for nothing else.
+
+use crate::sync::atomic::{AtomicU32, Ordering};
+use crate::{deleters, HazPtrDomain, HazPtrHolder};
+
+/// Frees arena slots once their index is no longer protected.
+pub trait IndexResolver: Sync {
+    /// Free the slot at `index`. No reader protects it anymore, and it is no longer reachable.
+    fn free(&self, index: u32);
+}
+
+/// An `AtomicU32` holding an arena index, which readers protect through a [`HazPtrHolder`].
+#[derive(Debug)]
+pub struct AtomicIndex {
+    index: AtomicU32,
+}
+
+impl AtomicIndex {
+    /// The index that stands for "no slot", like a null pointer.
+    pub const NONE: u32 = u32::MAX;
+
+    const_fn! {
+        pub const fn new(index: u32) -> Self {
+            Self {
+                index: AtomicU32::new(index),
+            }
+        }
+    }
+
+    /// Protect the current index with `holder`, and return it, or `None` for [`Self::NONE`].
+    ///
+    /// The index stays protected until `holder` is reset or used to protect something else.
+    ///
+    /// # Safety
+    ///
+    /// `holder` must be for the domain the indices in `self` are retired on, and that domain must
+    /// only ever be used for indices: nothing else may be protected or retired on it.
+    pub unsafe fn protect(&self, holder: &mut HazPtrHolder) -> Option<u32> {
+        let hazptr = holder.hazptr();
+        let mut index1 = self.index.load(Ordering::SeqCst);
+        loop {
+            if index1 == Self::NONE {
+                hazptr.reset();
+                break None;
+            }
+            hazptr.protect(encode(index1));
+            crate::barrier::light();
+            let index2 = self.index.load(Ordering::SeqCst);
+            if index1 == index2 {
+                break Some(index1);
+            }
+            index1 = index2;
+        }
+    }
+
+    /// Load the index without protecting it.
+    pub fn load(&self, ordering: Ordering) -> u32 {
+        self.index.load(ordering)
+    }
+
+    /// Store `index`. Whatever index it replaces is up to the caller to retire.
+    pub fn store(&self, index: u32, ordering: Ordering) {
+        self.index.store(index, ordering)
+    }
+
+    /// Store `index`, and return the index it replaced, for the caller to retire.
+    pub fn swap(&self, index: u32, ordering: Ordering) -> u32 {
+        self.index.swap(index, ordering)
+    }
+
+    /// Store `new` if the current index is `current`, like
+    /// [`AtomicU32::compare_exchange`](std::sync::atomic::AtomicU32::compare_exchange).
+    ///
+    /// On success, returns `current`, which is up to the caller to retire. On failure, returns
+    /// the index that is there instead.
+    pub fn compare_exchange(
+        &self,
+        current: u32,
+        new: u32,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u32, u32> {
+        self.index.compare_exchange(current, new, success, failure)
+    }
+}
+
+/// Hazards record index `i` as the address `i + 1`, so that index 0 is not mistaken for an
+/// empty slot.
+fn encode(index: u32) -> *mut u8 {
+    std::ptr::without_provenance_mut(index as usize + 1)
+}
+
+// An index waiting to be handed back to its resolver.
+struct RetiredIndex {
+    index: u32,
+    resolver: &'static dyn IndexResolver,
+}
+
+impl Drop for RetiredIndex {
+    fn drop(&mut self) {
+        self.resolver.free(self.index);
+    }
+}
+
+impl HazPtrDomain {
+    /// Retire the arena slot at `index`, to be freed through `resolver` once no reader protects
+    /// it anymore.
+    ///
+    /// # Safety
+    ///
+    /// `index` must no longer be reachable through any [`AtomicIndex`] (though readers may still
+    /// protect it), and must not have been retired before without being freed and reused since.
+    /// This domain must only ever be used for indices: nothing else may be protected or retired on
+    /// it, as its hazards could otherwise be mistaken for each other's.
+    pub unsafe fn retire_index(&self, index: u32, resolver: &'static dyn IndexResolver) {
+        let retired = Box::into_raw(Box::new(RetiredIndex { index, resolver }));
+        self.retire_at(encode(index), retired, &deleters::drop_box);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct FreeList(Mutex<Vec<u32>>);
+
+    impl IndexResolver for FreeList {
+        fn free(&self, index: u32) {
+            self.0.lock().unwrap().push(index);
+        }
+    }
+
+    #[test]
+    fn protected_indices_are_not_freed() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static FREE: FreeList = FreeList(Mutex::new(Vec::new()));
+
+        let head = AtomicIndex::new(0);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: indices in head are retired on DOMAIN.
+        assert_eq!(unsafe { head.protect(&mut h) }, Some(0));
+
+        let old = head.swap(AtomicIndex::NONE, Ordering::SeqCst);
+        // Safety: old is no longer reachable through head.
+        unsafe { DOMAIN.retire_index(old, &FREE) };
+        assert!(FREE.0.lock().unwrap().is_empty());
+
+        // Safety: as above.
+        assert_eq!(unsafe { head.protect(&mut h) }, None);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(*FREE.0.lock().unwrap(), [0]);
+    }
+}
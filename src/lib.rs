@@ -1,32 +1,184 @@
 #![feature(arbitrary_self_types)]
+#![feature(strict_provenance_lints)]
+#![deny(fuzzy_provenance_casts, lossy_provenance_casts)]
 #![deny(unsafe_op_in_unsafe_fn)]
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+use std::alloc::{GlobalAlloc, Layout};
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use std::sync::atomic::Ordering;
-use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
-
-static SHARED_DOMAIN: HazPtrDomain = HazPtrDomain {
-    hazptrs: HazPtrs {
-        head: AtomicPtr::new(std::ptr::null_mut()),
-    },
-    retired: RetiredList {
-        head: AtomicPtr::new(std::ptr::null_mut()),
-        count: AtomicUsize::new(0),
-    },
-};
+use std::sync::{Arc, OnceLock};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+use sync::atomic::Ordering;
+use sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize};
+use sync::{AtomicMut, Condvar, Mutex, RwLock};
 
-#[derive(Default)]
-pub struct HazPtrHolder(Option<&'static HazPtr>);
+// First, so that its macros are visible in the other modules.
+#[macro_use]
+mod sync;
+#[macro_use]
+mod trace;
+
+#[cfg(feature = "abi")]
+pub mod abi;
+mod atomic_arc;
+mod atomic_box;
+pub mod backend;
+mod barrier;
+mod brand;
+mod cohort;
+pub mod collections;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(fuzzing, kani))]
+pub mod fuzzing;
+mod hazard_box;
+pub mod index;
+mod linked;
+#[cfg(all(feature = "mmap", unix))]
+pub mod mmap;
+mod pin;
+#[cfg(all(feature = "memory-pressure", target_os = "linux"))]
+pub mod pressure;
+#[cfg(kani)]
+mod proofs;
+mod registry;
+pub mod scan;
+mod seqlock;
+pub mod snapshot;
+mod stats;
+pub mod tagged;
+mod traverse;
+mod wheel;
+
+pub use atomic_arc::AtomicArc;
+pub use atomic_box::{AtomicBox, AtomicDynBox, RawAtomicBox};
+#[doc(hidden)]
+pub use brand::{Brand, BrandAnchor};
+pub use brand::{Branded, BrandedDomain, BrandedHolder};
+pub use cohort::Cohort;
+pub use hazard_box::{HazardBox, HazardGuard};
+pub use linked::Linked;
+pub use pin::{pin, PinGuard};
+pub use registry::DomainId;
+use scan::{HazardSet, ScanStrategy};
+pub use seqlock::SeqLockBox;
+use stats::Stats;
+pub use stats::{AgeHistogram, HighWater, Peaks, AGE_BUCKETS};
+pub use traverse::{Traverse, Unlinked};
+use wheel::TimerWheel;
+
+#[cfg(not(loom))]
+static SHARED_DOMAIN: HazPtrDomain = HazPtrDomain::new();
+#[cfg(loom)]
+loom::lazy_static! {
+    static ref SHARED_DOMAIN: HazPtrDomain = HazPtrDomain::new();
+}
+
+/// A small, process-unique index for the calling thread.
+fn thread_index() -> usize {
+    // Not modelled under loom; this only spreads threads over batches.
+    static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    thread_local! {
+        static INDEX: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    INDEX.with(|i| *i)
+}
+
+/// How many released hazard records each thread keeps for reuse.
+const CACHED_HAZPTRS: usize = 8;
+
+/// Hazard records the thread has released, still marked active so that no other thread takes
+/// them, for the thread to reacquire without walking its domain's list. While cached, a record's
+/// owner is [`CACHED`], so that nothing mistakes it for one in use.
+///
+/// Records are kept by the id of their domain, like in [`BatchedDomains`], since the domain may
+/// be dropped while they are cached, and another domain may take its place in memory.
+struct HazPtrCache(Vec<(DomainId, &'static HazPtr)>);
+
+impl Drop for HazPtrCache {
+    fn drop(&mut self) {
+        // The thread is exiting, so hand the records back to their domains, if they are still
+        // around. Records of dropped domains have been freed with them.
+        for (id, hazptr) in self.0.drain(..) {
+            id.while_registered(|_| hazptr.active.store(false, Ordering::SeqCst));
+        }
+    }
+}
+
+/// The domains the thread has retired objects into batches of, so that it can hand its batches
+/// over to their retired lists when it exits, rather than leave the objects waiting for others
+/// to retire more.
+///
+/// Domains are recorded by id, which only `'static` domains have, so that one dropped before
+/// the thread exits is simply skipped.
+struct BatchedDomains(Vec<DomainId>);
+
+impl Drop for BatchedDomains {
+    fn drop(&mut self) {
+        for id in self.0.drain(..) {
+            if let Some(domain) = id.lookup() {
+                domain.flush_thread_batches();
+            }
+        }
+    }
+}
+
+sync::thread_local! {
+    // Loom's thread_local! doesn't take const initializers.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static HAZPTR_CACHE: std::cell::RefCell<HazPtrCache> =
+        std::cell::RefCell::new(HazPtrCache(Vec::new()));
+    #[allow(clippy::missing_const_for_thread_local)]
+    static BATCHED_DOMAINS: std::cell::RefCell<BatchedDomains> =
+        std::cell::RefCell::new(BatchedDomains(Vec::new()));
+}
+
+/// The CPU the calling thread is running on, for batching retirements.
+#[cfg(all(feature = "cpu-local", target_os = "linux"))]
+fn current_cpu() -> usize {
+    // Safety: sched_getcpu has no preconditions.
+    match unsafe { libc::sched_getcpu() } {
+        cpu if cpu >= 0 => cpu as usize,
+        _ => thread_index(),
+    }
+}
+
+/// Without a way to ask for the current CPU, threads at least don't share batches with
+/// themselves on other CPUs.
+#[cfg(not(all(feature = "cpu-local", target_os = "linux")))]
+fn current_cpu() -> usize {
+    thread_index()
+}
+
+pub struct HazPtrHolder {
+    hazptr: Option<&'static HazPtr>,
+    domain: &'static HazPtrDomain,
+}
+
+impl Default for HazPtrHolder {
+    fn default() -> Self {
+        Self::for_domain(&SHARED_DOMAIN)
+    }
+}
 
 impl HazPtrHolder {
+    /// Create a holder that protects objects retired on `domain`.
+    pub fn for_domain(domain: &'static HazPtrDomain) -> Self {
+        Self {
+            hazptr: None,
+            domain,
+        }
+    }
+
     fn hazptr(&mut self) -> &'static HazPtr {
-        if let Some(hazptr) = self.0 {
+        if let Some(hazptr) = self.hazptr {
             hazptr
         } else {
-            let hazptr = SHARED_DOMAIN.acquire();
-            self.0 = Some(hazptr);
+            let hazptr = self.domain.acquire();
+            self.hazptr = Some(hazptr);
             hazptr
         }
     }
@@ -41,362 +193,5345 @@ impl HazPtrHolder {
         let hazptr = self.hazptr();
         let mut ptr1 = ptr.load(Ordering::SeqCst);
         loop {
-            hazptr.protect(ptr1 as *mut u8);
-            let ptr2 = ptr.load(Ordering::SeqCst);
-            if ptr1 == ptr2 {
-                // All good -- protected
-                break std::ptr::NonNull::new(ptr1).map(|nn| {
-                    // Safety: this is safe because:
-                    //
-                    //  1. Target of ptr1 will not be deallocated for the returned lifetime since
-                    //     our hazard pointer is active and pointing at ptr1.
-                    //  2. Pointer address is valid by the safety contract of load.
-                    unsafe { nn.as_ref() }
-                });
-            } else {
-                ptr1 = ptr2;
+            match Self::validate(hazptr, ptr1, ptr) {
+                // Safety: by the safety contract of load.
+                Ok(ptr) => break unsafe { Self::as_ref(ptr) },
+                Err(ptr2) => ptr1 = ptr2,
             }
         }
     }
 
-    pub fn reset(&mut self) {
-        if let Some(hazptr) = self.0 {
-            hazptr.ptr.store(std::ptr::null_mut(), Ordering::SeqCst);
+    /// Make a single attempt at protecting `ptr1`, which the caller loaded from `src`.
+    ///
+    /// Returns a [`ProtectError`] with the value currently in `src` if it no longer holds
+    /// `ptr1`, in which case nothing is protected and the caller may try again with
+    /// [`ProtectError::retry`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn try_protect<'l, T>(
+        &'l mut self,
+        ptr1: *mut T,
+        src: &'_ AtomicPtr<T>,
+    ) -> Result<Option<&'l T>, ProtectError<T>> {
+        let hazptr = self.hazptr();
+        match Self::validate(hazptr, ptr1, src) {
+            // Safety: by the safety contract of try_protect.
+            Ok(ptr) => Ok(unsafe { Self::as_ref(ptr) }),
+            Err(ptr2) => {
+                hazptr.reset();
+                Err(ProtectError::new(ptr2, self.domain))
+            }
         }
     }
-}
 
-impl Drop for HazPtrHolder {
-    fn drop(&mut self) {
-        self.reset();
+    /// Like [`HazPtrHolder::load`], but gives up once `timeout` has elapsed.
+    ///
+    /// Returns a [`ProtectError`] with the last observed pointer if a writer kept changing `src`
+    /// for the whole duration. At least one attempt is always made, even for a zero `timeout`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn try_protect_for<'l, T>(
+        &'l mut self,
+        src: &'_ AtomicPtr<T>,
+        timeout: Duration,
+    ) -> Result<Option<&'l T>, ProtectError<T>> {
+        let deadline = Instant::now() + timeout;
+        let hazptr = self.hazptr();
+        let mut ptr1 = src.load(Ordering::SeqCst);
+        loop {
+            match Self::validate(hazptr, ptr1, src) {
+                // Safety: by the safety contract of try_protect_for.
+                Ok(ptr) => break Ok(unsafe { Self::as_ref(ptr) }),
+                Err(ptr2) if Instant::now() >= deadline => {
+                    hazptr.reset();
+                    break Err(ProtectError::new(ptr2, self.domain));
+                }
+                Err(ptr2) => ptr1 = ptr2,
+            }
+        }
+    }
 
-        // Return self.0 to domain if Some
-        if let Some(hazptr) = self.0 {
-            hazptr.active.store(false, Ordering::SeqCst);
+    /// Like [`HazPtrHolder::load`], but wait-free: after `attempts` failed validations, asks the
+    /// writers of `src` for help rather than retrying, so it finishes in a bounded number of
+    /// steps no matter how often `src` changes.
+    ///
+    /// Asking for help announces `src` in this holder's slot. Every writer that changes `src`
+    /// then answers the announcement by protecting the current value of `src` on the reader's
+    /// behalf, which it must do before retiring the value it replaced. Writers do so by using
+    /// [`HazPtrDomain::swap_helping`], or by calling [`HazPtrDomain::help_readers`] after each
+    /// other kind of update.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], and every writer to `src` must help readers of this
+    /// holder's domain as described above.
+    pub unsafe fn load_bounded<'l, T>(
+        &'l mut self,
+        src: &'_ AtomicPtr<T>,
+        attempts: usize,
+    ) -> Option<&'l T> {
+        let hazptr = self.hazptr();
+        let mut ptr1 = src.load(Ordering::SeqCst);
+        for _ in 0..attempts {
+            match Self::validate(hazptr, ptr1, src) {
+                // Safety: by the safety contract of load_bounded.
+                Ok(ptr) => return unsafe { Self::as_ref(ptr) },
+                Err(ptr2) => ptr1 = ptr2,
+            }
         }
+
+        // Writers only look for requests while someone might be asking.
+        self.domain.help_requests.fetch_add(1, Ordering::SeqCst);
+        let request = help_request(src);
+        // Writers look for requests without a heavy barrier, so this takes a full one.
+        hazptr.ptr.store(request, Ordering::SeqCst);
+        // Answer our own request, unless a writer beat us to it. Either way, whatever answered
+        // it was in src at the time, and had not been retired yet.
+        hazptr.answer(request, src.load(Ordering::SeqCst) as *mut u8);
+        self.domain.help_requests.fetch_sub(1, Ordering::SeqCst);
+        let ptr = hazptr.ptr.load(Ordering::SeqCst) as *mut T;
+        // Safety: ptr is protected, and by the safety contract of load_bounded.
+        unsafe { Self::as_ref(ptr) }
     }
-}
 
-pub struct HazPtr {
-    ptr: AtomicPtr<u8>,
-    next: AtomicPtr<HazPtr>,
-    active: AtomicBool,
-}
+    /// Like [`HazPtrHolder::load`], but if `src` is null, blocks until a non-null pointer is
+    /// published to it through `publisher`.
+    ///
+    /// Only publications made with [`Publisher::publish`] wake waiting readers, so writers to
+    /// `src` must go through `publisher` for as long as readers may be waiting.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn protect_or_wait<'l, T>(
+        &'l mut self,
+        src: &'_ AtomicPtr<T>,
+        publisher: &Publisher,
+    ) -> &'l T {
+        let hazptr = self.hazptr();
+        let mut ptr1 = src.load(Ordering::SeqCst);
+        loop {
+            if ptr1.is_null() {
+                hazptr.reset();
+                ptr1 = publisher.wait(src);
+            }
+            match Self::validate(hazptr, ptr1, src) {
+                // Safety: by the safety contract of protect_or_wait.
+                Ok(ptr) if !ptr.is_null() => break unsafe { &*ptr },
+                Ok(ptr) | Err(ptr) => ptr1 = ptr,
+            }
+        }
+    }
 
-impl HazPtr {
-    fn protect(&self, ptr: *mut u8) {
-        self.ptr.store(ptr, Ordering::SeqCst);
+    /// Protect the child object that `parent` points to through the field selected by `child`.
+    ///
+    /// `parent` must stay protected (typically by another holder) for the duration of this call,
+    /// which the borrow of `parent` enforces. That guarantees the field can always be read, even
+    /// if `parent` is concurrently unlinked and retired. The child itself still has to be
+    /// validated, because a writer may unlink it from `parent` and retire it at any time.
+    ///
+    /// The returned reference is tied only to this holder, not to `parent`: once the child is
+    /// protected, `parent`'s protection may be released (e.g., to move on to the next node).
+    ///
+    /// # Safety
+    ///
+    /// The field selected by `child` must satisfy the safety contract of [`HazPtrHolder::load`].
+    pub unsafe fn protect_child<'l, P, T, F>(&'l mut self, parent: &P, child: F) -> Option<&'l T>
+    where
+        F: for<'p> FnOnce(&'p P) -> &'p AtomicPtr<T>,
+    {
+        // Safety: by the safety contract of protect_child.
+        unsafe { self.load(child(parent)) }
     }
-}
 
-pub trait Deleter {
+    /// Like [`HazPtrHolder::load`], but hands the holder over to the returned [`Protected`], so
+    /// the protected object can be kept without borrowing the holder. Returns `None`, and
+    /// releases the holder's slot, if `src` is null.
+    ///
     /// # Safety
-    /// `ptr` must have been allocated by the corresponding allocation method.
-    /// delete must be called at most once for each `ptr`.
-    unsafe fn delete(&self, ptr: *mut dyn Drop);
-}
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn load_owned<T>(mut self, src: &AtomicPtr<T>) -> Option<Protected<T>> {
+        // Safety: by the safety contract of load_owned.
+        let ptr = std::ptr::NonNull::from(unsafe { self.load(src) }?);
+        Some(Protected { holder: self, ptr })
+    }
 
-impl Deleter for unsafe fn(*mut (dyn Drop + 'static)) {
-    unsafe fn delete(&self, ptr: *mut dyn Drop) {
-        unsafe { (*self)(ptr) }
+    /// Like [`HazPtrHolder::load`], but returns a [`ProtectedRef`] guard rather than a bare
+    /// reference. The guard resets the holder when it is dropped, so the object can't be used
+    /// past the end of its protection.
+    ///
+    /// `src` can be an `AtomicPtr`, or any other [`ProtectSource`]. Sources that uphold the
+    /// safety contract themselves can be protected from without `unsafe` through
+    /// [`HazPtrHolder::protect_safe`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], for the pointer in `src`.
+    pub unsafe fn protect<'l, S: ProtectSource + ?Sized>(
+        &'l mut self,
+        src: &'_ S,
+    ) -> Option<ProtectedRef<'l, S::Target>> {
+        // Safety: by the safety contract of protect.
+        let ptr = std::ptr::NonNull::from(unsafe { src.load_with(self) }?);
+        Some(ProtectedRef { holder: self, ptr })
     }
-}
 
-pub mod deleters {
-    unsafe fn _drop_in_place(ptr: *mut dyn Drop) {
-        // Safe by the contract on HazPtrObject::retire.
-        unsafe { std::ptr::drop_in_place(ptr) };
+    /// Like [`HazPtrHolder::protect`], for sources such as [`AtomicBox`] that only ever hold
+    /// objects that are safe to protect.
+    ///
+    /// # Panics
+    ///
+    /// If this holder is for a different domain than `src`.
+    ///
+    /// ```
+    /// use haphazard::{AtomicBox, HazPtrHolder, HazPtrObjectWrapper};
+    ///
+    /// let x = AtomicBox::new(HazPtrObjectWrapper::with_default_domain(1));
+    /// let mut h = HazPtrHolder::default();
+    /// assert_eq!(**h.protect_safe(&x).unwrap(), 1);
+    /// ```
+    pub fn protect_safe<'l, S: SafeProtectSource + ?Sized>(
+        &'l mut self,
+        src: &'_ S,
+    ) -> Option<ProtectedRef<'l, S::Target>> {
+        // Safety: SafeProtectSource promises that load_with is always safe to call.
+        unsafe { self.protect(src) }
     }
 
-    /// Always safe to use given requirements on HazPtrObject::retire,
-    /// but may lead to memory leaks if the pointer type itself needs drop.
-    #[allow(non_upper_case_globals)]
-    pub static drop_in_place: unsafe fn(*mut dyn Drop) = _drop_in_place;
+    /// Protect the object `protected` refers to with this holder too, without loading it again.
+    ///
+    /// The new protection outlives `protected`, so the object can be handed to code that has a
+    /// holder of its own.
+    ///
+    /// # Panics
+    ///
+    /// If this holder is for a different domain than the one `protected` is.
+    pub fn duplicate_protection<'l, T>(
+        &'l mut self,
+        protected: &ProtectedRef<'_, T>,
+    ) -> ProtectedRef<'l, T> {
+        assert!(
+            std::ptr::eq(self.domain, protected.holder.domain),
+            "holder is for a different domain"
+        );
+        self.duplicate(protected.ptr);
+        ProtectedRef {
+            holder: self,
+            ptr: protected.ptr,
+        }
+    }
 
-    unsafe fn _drop_box(ptr: *mut dyn Drop) {
-        // Safety: Safe by the safety gurantees of retire and because it's only used when
-        // retiring Box objects.
-        let _ = unsafe { Box::from_raw(ptr) };
+    /// Protect `ptr`, which is already protected by another holder of this domain that stays
+    /// active until we return.
+    ///
+    /// This waits for the walks of the domain's hazard list that started before it to end, so
+    /// it must never be called while the calling thread walks the list itself, such as from
+    /// inside a scan. Walks that start while it waits don't hold it up.
+    fn duplicate<T>(&mut self, ptr: std::ptr::NonNull<T>) {
+        let hazptr = self.hazptr();
+        hazptr.protect(ptr.as_ptr() as *mut u8);
+        crate::barrier::light();
+        // A scan that is already walking the slots may have passed ours before we stored to it,
+        // and find the original slot released once the caller lets go of it. Scans that start
+        // from here on see our slot, so once those already underway are done, ours suffices.
+        self.domain.hazptrs.wait_for_walks();
     }
 
+    /// Acquire this holder's hazard slot now, rather than on first use.
+    ///
+    /// Acquiring a slot may allocate, so code that must not allocate while protecting (such as
+    /// a signal handler) should prepare its holder up front and use
+    /// [`HazPtrHolder::load_prepared`].
+    pub fn prepare(&mut self) {
+        self.hazptr();
+    }
+
+    /// Like [`HazPtrHolder::load`], but only ever uses a slot acquired earlier through
+    /// [`HazPtrHolder::prepare`] (or a previous `load`).
+    ///
+    /// This performs nothing but atomic loads and stores to the source and the already-acquired
+    /// slot: it never locks, allocates, or touches the domain. It is therefore
+    /// async-signal-safe, provided the signal handler has exclusive use of this holder (e.g.,
+    /// the interrupted code is not itself in the middle of using it).
+    ///
+    /// Returns `Err(NotPrepared)` if this holder does not have a slot yet.
+    ///
     /// # Safety
     ///
-    /// Can only be used on values that were originally derived from a Box.
-    #[allow(non_upper_case_globals)]
-    pub static drop_box: unsafe fn(*mut dyn Drop) = _drop_box;
-}
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn load_prepared<'l, T>(
+        &'l mut self,
+        ptr: &'_ AtomicPtr<T>,
+    ) -> Result<Option<&'l T>, NotPrepared> {
+        let hazptr = self.hazptr.ok_or(NotPrepared(()))?;
+        let mut ptr1 = ptr.load(Ordering::SeqCst);
+        loop {
+            match Self::validate(hazptr, ptr1, ptr) {
+                // Safety: by the safety contract of load_prepared.
+                Ok(ptr) => break Ok(unsafe { Self::as_ref(ptr) }),
+                Err(ptr2) => ptr1 = ptr2,
+            }
+        }
+    }
 
-#[allow(drop_bounds)]
-pub trait HazPtrObject
-where
-    Self: Sized + Drop + 'static,
-{
-    fn domain(&self) -> &HazPtrDomain;
+    fn validate<T>(hazptr: &HazPtr, ptr1: *mut T, src: &AtomicPtr<T>) -> Result<*mut T, *mut T> {
+        hazptr.protect(ptr1 as *mut u8);
+        barrier::light();
+        let ptr2 = src.load(Ordering::SeqCst);
+        if ptr1 == ptr2 {
+            // All good -- protected
+            trace_event!(addr = ptr1.addr(), "protected");
+            Ok(ptr1)
+        } else {
+            Err(ptr2)
+        }
+    }
 
     /// # Safety
     ///
-    /// 1. Caller must guarantee that pointer is a valid reference.
-    /// 2. Caller must guarantee that Self is no longer accessible to readers.
-    /// 3. Caller must guarantee that the deleter is a valid deleter for Self.
-    /// It is okay for existing readers to still refer to Self.
-    ///   
-    unsafe fn retire(self: *mut Self, deleter: &'static dyn Deleter) {
-        if !std::mem::needs_drop::<Self>() {
-            return;
+    /// `ptr` must be protected by this holder and satisfy the safety contract of `load`.
+    unsafe fn as_ref<'l, T>(ptr: *mut T) -> Option<&'l T> {
+        std::ptr::NonNull::new(ptr).map(|nn| {
+            // Safety: this is safe because:
+            //
+            //  1. Target of ptr will not be deallocated for the returned lifetime since
+            //     our hazard pointer is active and pointing at ptr.
+            //  2. Pointer address is valid by the safety contract of load.
+            unsafe { nn.as_ref() }
+        })
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(hazptr) = self.hazptr {
+            trace_event!("reset");
+            hazptr.reset();
         }
-        unsafe { &*self }
-            .domain()
-            .retire(self as *mut dyn Drop, deleter);
     }
 }
 
-pub struct HazPtrObjectWrapper<T> {
-    inner: T,
-    // domain: HazPtrDomain,
+/// A [`HazPtrHolder`] that can only protect values of type `T`.
+///
+/// Useful where `AtomicPtr`s to several different node types are in play, since it rules out
+/// protecting through a source of the wrong type.
+pub struct TypedHazPtrHolder<T> {
+    inner: HazPtrHolder,
+    _type: PhantomData<fn(&T)>,
 }
 
-impl<T> HazPtrObjectWrapper<T> {
-    pub fn with_default_domain(t: T) -> Self {
-        Self { inner: t }
+impl<T> Default for TypedHazPtrHolder<T> {
+    fn default() -> Self {
+        Self {
+            inner: HazPtrHolder::default(),
+            _type: PhantomData,
+        }
     }
 }
 
-impl<T: 'static> HazPtrObject for HazPtrObjectWrapper<T> {
-    fn domain(&self) -> &HazPtrDomain {
-        &SHARED_DOMAIN
+impl<T> TypedHazPtrHolder<T> {
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn load<'l>(&'l mut self, ptr: &'_ AtomicPtr<T>) -> Option<&'l T> {
+        // Safety: by the safety contract of load.
+        unsafe { self.inner.load(ptr) }
+    }
+
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn try_protect<'l>(
+        &'l mut self,
+        ptr1: *mut T,
+        src: &'_ AtomicPtr<T>,
+    ) -> Result<Option<&'l T>, ProtectError<T>> {
+        // Safety: by the safety contract of try_protect.
+        unsafe { self.inner.try_protect(ptr1, src) }
+    }
+
+    pub fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    /// Turn this back into an untyped holder, keeping any hazard slot it has acquired.
+    pub fn into_inner(self) -> HazPtrHolder {
+        self.inner
     }
 }
 
-// TODO: get rid of this requirement
-impl<T> Drop for HazPtrObjectWrapper<T> {
-    fn drop(&mut self) {}
+/// An object protected by a hazard slot that the guard owns, as returned by
+/// [`HazPtrHolder::load_owned`] and [`AtomicBox::load_owned`].
+///
+/// Unlike the reference returned by [`HazPtrHolder::load`], a `Protected` does not borrow a
+/// holder, so it can be returned from functions and stored in structs. Dropping it releases the
+/// slot.
+pub struct Protected<T> {
+    holder: HazPtrHolder,
+    ptr: std::ptr::NonNull<T>,
 }
 
-impl<T> Deref for HazPtrObjectWrapper<T> {
+impl<T> Protected<T> {
+    /// Stop protecting the object, and get the holder back for reuse.
+    pub fn into_holder(self) -> HazPtrHolder {
+        let mut holder = self.holder;
+        holder.reset();
+        holder
+    }
+}
+
+impl<T> Clone for Protected<T> {
+    /// Protect the same object with a new holder of the same domain.
+    fn clone(&self) -> Self {
+        let mut holder = HazPtrHolder::for_domain(self.holder.domain);
+        holder.duplicate(self.ptr);
+        Self {
+            holder,
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T> Deref for Protected<T> {
     type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+
+    fn deref(&self) -> &T {
+        // Safety: the holder protects ptr for as long as we exist, and it was valid when it was
+        // loaded, by the safety contract of load_owned.
+        unsafe { self.ptr.as_ref() }
     }
 }
 
-impl<T> DerefMut for HazPtrObjectWrapper<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
+impl<T: std::fmt::Debug> std::fmt::Debug for Protected<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
     }
 }
 
-// Holds linked list of HazPtrs
-pub struct HazPtrDomain {
-    hazptrs: HazPtrs,
-    retired: RetiredList,
+// Safety: a Protected only hands out &T, and may release its slot on any thread.
+unsafe impl<T: Sync> Send for Protected<T> {}
+unsafe impl<T: Sync> Sync for Protected<T> {}
+
+/// An object protected by a borrowed holder, as returned by [`HazPtrHolder::protect`] and
+/// [`AtomicBox::protect`].
+///
+/// Dropping the guard resets the holder, which can then be used again.
+pub struct ProtectedRef<'l, T> {
+    holder: &'l mut HazPtrHolder,
+    ptr: std::ptr::NonNull<T>,
 }
 
-impl HazPtrDomain {
-    fn acquire(&self) -> &'static HazPtr {
-        let head_ptr = &self.hazptrs.head;
-        let mut node = head_ptr.load(Ordering::SeqCst);
-        loop {
-            // Safety: HazPtrs are never de-allocated.
-            while !node.is_null() && unsafe { &*node }.active.load(Ordering::SeqCst) {
-                // Safety: HazPtrs are never de-allocated.
-                node = unsafe { &*node }.next.load(Ordering::SeqCst);
-            }
-            if node.is_null() {
-                // No free HazPtrs -- need to allocate a new one
-                let hazptr = Box::into_raw(Box::new(HazPtr {
-                    ptr: AtomicPtr::new(std::ptr::null_mut()),
-                    next: AtomicPtr::new(std::ptr::null_mut()),
-                    active: AtomicBool::new(true),
-                }));
-                // And stick it at the head of the linked list
-                let mut head = head_ptr.load(Ordering::SeqCst);
-                break loop {
-                    // Safety: hazptr was never shared, so &mut is ok.
-                    *unsafe { &mut *hazptr }.next.get_mut() = head;
-                    match head_ptr.compare_exchange_weak(
-                        head,
-                        hazptr,
-                        Ordering::SeqCst,
-                        Ordering::SeqCst,
-                    ) {
-                        Ok(_) => {
-                            // Safety: HazPtrs are never de-allocated.
-                            break unsafe { &*hazptr };
-                        }
-                        Err(head_now) => {
-                            // Head has changed, try again with that as our next ptr.
-                            head = head_now
-                        }
-                    }
-                };
-            } else {
-                // Safety: HazPtrs are never de-allocated.
-                let node = unsafe { &*node };
-                if node
-                    .active
-                    .compare_exchange_weak(false, true, Ordering::SeqCst, Ordering::SeqCst)
-                    .is_ok()
-                {
-                    // It's ours!
-                    break node;
-                } else {
-                    // Someone else grabbed this node right before us.
-                    // Keep walking
-                }
-            }
-        }
+impl<T> Deref for ProtectedRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: the holder protects ptr for as long as we exist, and it was valid when it was
+        // loaded, by the safety contract of protect.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> Drop for ProtectedRef<'_, T> {
+    fn drop(&mut self) {
+        self.holder.reset();
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ProtectedRef<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// Safety: like a Protected, a ProtectedRef only hands out &T, and the holder it borrows may be
+// reset from any thread, so the guard can be moved to another thread (say, across an .await)
+// while the object stays protected.
+unsafe impl<T: Sync> Send for ProtectedRef<'_, T> {}
+unsafe impl<T: Sync> Sync for ProtectedRef<'_, T> {}
+
+/// Something a [`HazPtrHolder`] can protect objects from, with [`HazPtrHolder::protect`].
+///
+/// Protecting from a raw `AtomicPtr` is `unsafe`, since nothing stops it from holding a dangling
+/// pointer, or an object that is freed without being retired. Sources that rule this out
+/// implement [`SafeProtectSource`] as well.
+pub trait ProtectSource {
+    /// The type of the objects in the source.
+    type Target;
+
+    /// Protect the current object with `holder`, and return a reference to it, or `None` if
+    /// there is none.
+    ///
+    /// # Safety
+    ///
+    /// Unless the source implements [`SafeProtectSource`], the object in it must satisfy the
+    /// safety contract of [`HazPtrHolder::load`].
+    unsafe fn load_with<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l Self::Target>;
+}
+
+/// A [`ProtectSource`] that upholds the safety contract of [`HazPtrHolder::load`] on its own,
+/// so that [`HazPtrHolder::protect_safe`] can protect from it without `unsafe`.
+///
+/// # Safety
+///
+/// [`ProtectSource::load_with`] must be sound to call with any holder, and either protect a
+/// valid object or panic.
+pub unsafe trait SafeProtectSource: ProtectSource {}
+
+impl<T> ProtectSource for AtomicPtr<T> {
+    type Target = T;
+
+    unsafe fn load_with<'l>(&self, holder: &'l mut HazPtrHolder) -> Option<&'l T> {
+        // Safety: by the safety contract of load_with.
+        unsafe { holder.load(self) }
+    }
+}
+
+/// A fixed number of holders whose hazard slots are acquired together, for algorithms that
+/// need to protect several objects at once (such as the previous, current, and next node of a
+/// list).
+///
+/// Acquiring all slots takes a single walk of the domain's slot list, and slots that have to be
+/// allocated are added to it in one go. Each holder can be used and reset individually, and
+/// split off with a pattern, so that the objects they protect can be borrowed at the same
+/// time:
+///
+/// ```
+/// use haphazard::HazPtrHolderArray;
+///
+/// let mut holders = HazPtrHolderArray::<3>::default();
+/// let [prev, cur, next] = holders.holders();
+/// # let _ = (prev, cur, next);
+/// ```
+pub struct HazPtrHolderArray<const N: usize> {
+    holders: [HazPtrHolder; N],
+}
+
+impl<const N: usize> Default for HazPtrHolderArray<N> {
+    fn default() -> Self {
+        Self::for_domain(&SHARED_DOMAIN)
+    }
+}
+
+impl<const N: usize> HazPtrHolderArray<N> {
+    /// Create `N` holders for `domain`, with their slots already acquired.
+    pub fn for_domain(domain: &'static HazPtrDomain) -> Self {
+        Self {
+            holders: domain.acquire_many::<N>().map(|hazptr| HazPtrHolder {
+                hazptr: Some(hazptr),
+                domain,
+            }),
+        }
+    }
+
+    /// The individual holders.
+    pub fn holders(&mut self) -> &mut [HazPtrHolder; N] {
+        &mut self.holders
+    }
+
+    /// Reset every holder.
+    pub fn reset(&mut self) {
+        for h in &mut self.holders {
+            h.reset();
+        }
+    }
+
+    /// Split this array into its holders, which keep their slots.
+    pub fn into_holders(self) -> [HazPtrHolder; N] {
+        self.holders
+    }
+
+    /// Protect the object in each of `srcs` with the holder at the same index, and return them.
+    ///
+    /// Loading them one at a time issues a barrier for every hazard. This publishes all the
+    /// hazards first, and validates every source after a single barrier. If any source changed
+    /// in the meantime, the hazards are published again for the new values, so all the objects
+    /// returned were in their sources at the same point, which suits a validation across
+    /// several words.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], for each of `srcs`.
+    pub unsafe fn protect_all<'l, T>(&'l mut self, srcs: [&AtomicPtr<T>; N]) -> [Option<&'l T>; N] {
+        let mut ptrs = srcs.map(|src| src.load(Ordering::SeqCst));
+        loop {
+            for (holder, &ptr) in self.holders.iter_mut().zip(&ptrs) {
+                holder.hazptr().protect(ptr as *mut u8);
+            }
+            barrier::light();
+            let mut stable = true;
+            for (ptr, src) in ptrs.iter_mut().zip(srcs) {
+                let now = src.load(Ordering::SeqCst);
+                if now != *ptr {
+                    *ptr = now;
+                    stable = false;
+                }
+            }
+            if stable {
+                break;
+            }
+        }
+        trace_event!(count = N, "protected");
+        // Safety: every pointer is protected by its holder, and by the safety contract of
+        // protect_all.
+        ptrs.map(|ptr| unsafe { HazPtrHolder::as_ref(ptr) })
+    }
+}
+
+/// A holder that can protect objects from any domain.
+///
+/// When asked to protect an object from a domain other than the one it last used, it resets its
+/// current slot and switches to a slot from the new domain. Slots are kept around per domain, so
+/// switching back and forth does not acquire a new slot each time.
+#[derive(Default)]
+pub struct MultiHazPtrHolder {
+    current: Option<HazPtrHolder>,
+    idle: Vec<HazPtrHolder>,
+}
+
+impl MultiHazPtrHolder {
+    /// Get a holder for `domain`, releasing whatever this holder protected before.
+    pub fn holder_for(&mut self, domain: &'static HazPtrDomain) -> &mut HazPtrHolder {
+        let switch = match &self.current {
+            Some(current) => !std::ptr::eq(current.domain, domain),
+            None => true,
+        };
+        if switch {
+            if let Some(mut old) = self.current.take() {
+                old.reset();
+                self.idle.push(old);
+            }
+            let holder = match self
+                .idle
+                .iter()
+                .position(|h| std::ptr::eq(h.domain, domain))
+            {
+                Some(i) => self.idle.swap_remove(i),
+                None => HazPtrHolder::for_domain(domain),
+            };
+            self.current = Some(holder);
+        }
+        self.current.as_mut().expect("set above")
+    }
+
+    /// Protect the object in `ptr`, which belongs to `domain`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], with `domain` as the domain objects in `ptr` are retired
+    /// on.
+    pub unsafe fn load<'l, T>(
+        &'l mut self,
+        domain: &'static HazPtrDomain,
+        ptr: &'_ AtomicPtr<T>,
+    ) -> Option<&'l T> {
+        // Safety: by the safety contract of load.
+        unsafe { self.holder_for(domain).load(ptr) }
+    }
+
+    /// Protect the object in `boxed`, whichever domain it belongs to, or return `None` if the box
+    /// is empty.
+    pub fn load_box<'l, T: HazPtrObject + Send>(
+        &'l mut self,
+        boxed: &AtomicBox<T>,
+    ) -> Option<&'l T> {
+        boxed.load(self.holder_for(boxed.domain()))
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(current) = &mut self.current {
+            current.reset();
+        }
+    }
+}
+
+/// Wakes readers blocked in [`HazPtrHolder::protect_or_wait`] when a pointer is published.
+#[derive(Debug, Default)]
+pub struct Publisher {
+    lock: Mutex<()>,
+    published: Condvar,
+}
+
+impl Publisher {
+    const_fn! {
+        /// Create a publisher. This is a `const fn`, so publishers can be declared as statics.
+        pub const fn new() -> Self {
+            Self {
+                lock: Mutex::new(()),
+                published: Condvar::new(),
+            }
+        }
+    }
+
+    /// Store `ptr` in `dst`, wake any readers waiting for it to be non-null, and return the
+    /// pointer that was there before.
+    pub fn publish<T>(&self, dst: &AtomicPtr<T>, ptr: *mut T) -> *mut T {
+        let _guard = self.lock.lock().unwrap();
+        let old = dst.swap(ptr, Ordering::SeqCst);
+        self.published.notify_all();
+        old
+    }
+
+    /// Block until `src` is non-null, and return its value.
+    fn wait<T>(&self, src: &AtomicPtr<T>) -> *mut T {
+        let mut guard = self.lock.lock().unwrap();
+        loop {
+            // Publications happen under the lock, so none can slip in between this check and
+            // starting to wait.
+            let ptr = src.load(Ordering::SeqCst);
+            if !ptr.is_null() {
+                break ptr;
+            }
+            guard = self.published.wait(guard).unwrap();
+        }
+    }
+}
+
+/// The holder has not acquired a hazard slot yet; see [`HazPtrHolder::prepare`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotPrepared(());
+
+impl std::fmt::Display for NotPrepared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("holder has not acquired a hazard slot")
+    }
+}
+
+impl std::error::Error for NotPrepared {}
+
+/// The source changed before an object could be protected; see [`HazPtrHolder::try_protect`].
+pub struct ProtectError<T> {
+    observed: *mut T,
+    #[cfg(debug_assertions)]
+    domain: &'static HazPtrDomain,
+}
+
+// Safety: the observed pointer is only ever compared and handed back, never dereferenced.
+unsafe impl<T> Send for ProtectError<T> {}
+// Safety: as above.
+unsafe impl<T> Sync for ProtectError<T> {}
+
+impl<T> ProtectError<T> {
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn new(observed: *mut T, domain: &'static HazPtrDomain) -> Self {
+        Self {
+            observed,
+            #[cfg(debug_assertions)]
+            domain,
+        }
+    }
+
+    /// The pointer that was in the source at the last attempt. It is not protected.
+    pub fn observed(&self) -> *mut T {
+        self.observed
+    }
+
+    /// Whether the source was null at the last attempt.
+    pub fn was_null(&self) -> bool {
+        self.observed.is_null()
+    }
+
+    /// The domain of the holder that made the attempt.
+    ///
+    /// An object retired on some other domain is never protected by that holder, whether or not
+    /// the attempt succeeds, so a mismatch here points at the wrong holder being used. Only
+    /// available in debug builds.
+    #[cfg(debug_assertions)]
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Try again to protect the pointer that was observed, with the same holder and source.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`].
+    pub unsafe fn retry<'l>(
+        self,
+        holder: &'l mut HazPtrHolder,
+        src: &'_ AtomicPtr<T>,
+    ) -> Result<Option<&'l T>, ProtectError<T>> {
+        // Safety: by the safety contract of retry.
+        unsafe { holder.try_protect(self.observed, src) }
+    }
+}
+
+impl<T> Clone for ProtectError<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ProtectError<T> {}
+
+impl<T> PartialEq for ProtectError<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.observed == other.observed
+    }
+}
+
+impl<T> Eq for ProtectError<T> {}
+
+impl<T> std::fmt::Debug for ProtectError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("ProtectError");
+        debug.field("observed", &self.observed);
+        #[cfg(debug_assertions)]
+        debug.field("domain", &(self.domain as *const HazPtrDomain));
+        debug.finish()
+    }
+}
+
+impl<T> std::fmt::Display for ProtectError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.was_null() {
+            f.write_str("source changed to null before it could be protected")
+        } else {
+            f.write_str("source changed before it could be protected")
+        }
+    }
+}
+
+impl<T> std::error::Error for ProtectError<T> {}
+
+impl Drop for HazPtrHolder {
+    fn drop(&mut self) {
+        self.reset();
+
+        // Return self.hazptr to domain if Some
+        if let Some(hazptr) = self.hazptr {
+            self.domain.release(hazptr);
+        }
+    }
+}
+
+/// Each record gets cache lines of its own, so that readers protecting objects through
+/// neighbouring records don't invalidate each other's caches. Modern x86_64 and aarch64 CPUs
+/// prefetch cache lines in pairs, hence 128 bytes there.
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    repr(align(64))
+)]
+pub struct HazPtr {
+    ptr: AtomicPtr<u8>,
+    next: AtomicPtr<HazPtr>,
+    active: AtomicBool,
+    // The thread index of whoever last acquired this HazPtr, or CACHED while it is active only
+    // because a thread keeps it in its cache.
+    owner: AtomicUsize,
+}
+
+/// The owner of a record that a thread keeps cached rather than uses; never a thread index.
+const CACHED: usize = usize::MAX;
+
+impl HazPtr {
+    /// Whether a thread holds this record, rather than just keeps it cached.
+    fn is_held(&self) -> bool {
+        self.active.load(Ordering::SeqCst) && self.owner.load(Ordering::SeqCst) != CACHED
+    }
+
+    /// Readers have to follow this with [`barrier::light`] before validating `ptr`.
+    fn protect(&self, ptr: *mut u8) {
+        self.ptr.store(ptr, Ordering::Release);
+    }
+
+    fn reset(&self) {
+        self.ptr.store(std::ptr::null_mut(), Ordering::SeqCst);
+    }
+
+    /// Protect `ptr` if this slot still holds the help request `request`.
+    fn answer(&self, request: *mut u8, ptr: *mut u8) {
+        let _ = self
+            .ptr
+            .compare_exchange(request, ptr, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+/// What a reader puts in its slot to ask for help protecting the value in `src`.
+///
+/// An `AtomicPtr` is pointer-aligned, so its address with the lowest bit set is never that of
+/// an object a reader might protect (which would have to start inside the `AtomicPtr`).
+fn help_request<T>(src: &AtomicPtr<T>) -> *mut u8 {
+    (src as *const AtomicPtr<T> as *mut u8).map_addr(|addr| addr | 1)
+}
+
+pub trait Deleter {
+    /// # Safety
+    /// `ptr` must have been allocated by the corresponding allocation method.
+    /// delete must be called at most once for each `ptr`.
+    unsafe fn delete(&self, ptr: *mut dyn Drop);
+}
+
+impl Deleter for unsafe fn(*mut (dyn Drop + 'static)) {
+    unsafe fn delete(&self, ptr: *mut dyn Drop) {
+        unsafe { (*self)(ptr) }
+    }
+}
+
+pub mod deleters {
+    unsafe fn _drop_in_place(ptr: *mut dyn Drop) {
+        // Safe by the contract on HazPtrObject::retire.
+        unsafe { std::ptr::drop_in_place(ptr) };
+    }
+
+    /// Always safe to use given requirements on HazPtrObject::retire,
+    /// but may lead to memory leaks if the pointer type itself needs drop.
+    #[allow(non_upper_case_globals)]
+    pub static drop_in_place: unsafe fn(*mut dyn Drop) = _drop_in_place;
+
+    unsafe fn _drop_box(ptr: *mut dyn Drop) {
+        // Safety: Safe by the safety gurantees of retire and because it's only used when
+        // retiring Box objects.
+        let _ = unsafe { Box::from_raw(ptr) };
+    }
+
+    /// # Safety
+    ///
+    /// Can only be used on values that were originally derived from a Box.
+    #[allow(non_upper_case_globals)]
+    pub static drop_box: unsafe fn(*mut dyn Drop) = _drop_box;
+
+    unsafe fn _drop_arc(ptr: *mut dyn Drop) {
+        // Safety: Safe by the safety gurantees of retire and because it's only used when
+        // retiring pointers that own a strong reference to an Arc.
+        drop(unsafe { std::sync::Arc::from_raw(ptr as *const dyn Drop) });
+    }
+
+    /// Releases the strong reference the pointer owns; the object is only dropped once no other
+    /// `Arc` refers to it.
+    ///
+    /// # Safety
+    ///
+    /// Can only be used on values that were originally derived from an Arc, for example with
+    /// [`HazPtrObjectWrapper::arc_into_raw`](crate::HazPtrObjectWrapper::arc_into_raw).
+    #[allow(non_upper_case_globals)]
+    pub static drop_arc: unsafe fn(*mut dyn Drop) = _drop_arc;
+}
+
+#[allow(drop_bounds)]
+pub trait HazPtrObject
+where
+    Self: Sized + Drop + 'static,
+{
+    /// Frees objects of this type once they are reclaimed.
+    ///
+    /// This has to match how the objects are allocated, which by default is with `Box`.
+    const DELETER: &'static dyn Deleter = &deleters::drop_box;
+
+    fn domain(&self) -> &HazPtrDomain;
+
+    /// # Safety
+    ///
+    /// 1. Caller must guarantee that pointer is a valid reference.
+    /// 2. Caller must guarantee that Self is no longer accessible to readers.
+    /// 3. Caller must guarantee that the pointer was allocated the way [`HazPtrObject::DELETER`]
+    ///    expects.
+    /// 4. If Self is not `Send`, the domain must be confined to the calling thread (see
+    ///    [`HazPtrDomain::confine_to_current_thread`]), since deleters otherwise run on whichever
+    ///    thread happens to reclaim. The safe wrappers, such as [`AtomicBox`], can't know whether
+    ///    their domain is confined, so they require `Send` instead.
+    /// It is okay for existing readers to still refer to Self.
+    ///   
+    unsafe fn retire(self: *mut Self) {
+        // Safety: by the safety contract of retire.
+        unsafe { self.retire_with_deleter(Self::DELETER) };
+    }
+
+    /// Like [`HazPtrObject::retire`], but frees the object with `deleter` rather than
+    /// [`HazPtrObject::DELETER`], for objects that were allocated some other way than the rest
+    /// of their type, such as through [`HazPtrObjectWrapper::arc_into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`], with `deleter` being a valid deleter for the pointer.
+    unsafe fn retire_with_deleter(self: *mut Self, deleter: &'static dyn Deleter) {
+        if !std::mem::needs_drop::<Self>() {
+            return;
+        }
+        unsafe { &*self }
+            .domain()
+            .retire(self as *mut dyn Drop, deleter);
+    }
+
+    /// Like [`HazPtrObject::retire`], but returns how the domain's backlog compares to its
+    /// budgets afterwards, so that writers can adapt before the backlog grows out of hand.
+    ///
+    /// See [`HazPtrDomain::set_retire_budgets`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    unsafe fn retire_with_backpressure(self: *mut Self) -> Backpressure {
+        // Safety: the object is still valid until we retire it.
+        let domain = unsafe { &*self }.domain() as *const HazPtrDomain;
+        // Safety: by the safety contract of retire_with_backpressure.
+        unsafe { self.retire() };
+        // Safety: objects only ever belong to 'static domains.
+        unsafe { &*domain }.backpressure()
+    }
+
+    /// Like [`HazPtrObject::retire`], but calls `finalizer` with the object right before its
+    /// deleter runs.
+    ///
+    /// This is a last chance to look at the object, for example to flush metrics or hand
+    /// resources back to an external system, which is otherwise only possible in `Drop`, and
+    /// therefore not at all for foreign types.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    unsafe fn retire_with_finalizer<F>(self: *mut Self, finalizer: F)
+    where
+        F: FnOnce(&Self) + Send + 'static,
+    {
+        let finalized = Box::into_raw(Box::new(Finalized {
+            ptr: self,
+            deleter: Self::DELETER,
+            finalizer: Some(finalizer),
+        }));
+        // Readers protect the object itself, not the finalizer that wraps it.
+        unsafe { &*self }
+            .domain()
+            .retire_at(self as *mut u8, finalized, &deleters::drop_box);
+    }
+
+    /// Like [`HazPtrObject::retire`], but reclaims the object by calling `deleter` with it,
+    /// rather than through a `&'static dyn Deleter`.
+    ///
+    /// Unlike a static deleter, the closure can capture context, such as a pool to return the
+    /// object's memory to, or a counter of reclaimed objects.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`], with `deleter` being a valid deleter for the pointer.
+    unsafe fn retire_with<F>(self: *mut Self, deleter: F)
+    where
+        F: FnOnce(*mut Self) + Send + 'static,
+    {
+        let owned = Box::into_raw(Box::new(WithDeleter {
+            ptr: self,
+            deleter: Some(deleter),
+        }));
+        // Readers protect the object itself, not the closure that wraps it.
+        unsafe { &*self }
+            .domain()
+            .retire_at(self as *mut u8, owned, &deleters::drop_box);
+    }
+
+    /// Like [`HazPtrObject::retire`], but the deleter only ever runs on `thread`.
+    ///
+    /// For objects that own thread-local or thread-bound resources, such as GUI handles. When
+    /// another thread reclaims the object, its deleter is queued until `thread` calls
+    /// [`HazPtrDomain::run_thread_deleters`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    unsafe fn retire_on(self: *mut Self, thread: ThreadId) {
+        if !std::mem::needs_drop::<Self>() {
+            return;
+        }
+        unsafe { &*self }
+            .domain()
+            .retire_on(thread, self as *mut dyn Drop, Self::DELETER);
+    }
+
+    /// Like [`HazPtrObject::retire`], but returns an error instead of aborting if the domain
+    /// cannot allocate the memory it needs to keep track of the retired object.
+    ///
+    /// If an error is returned, `self` has _not_ been retired and the caller remains
+    /// responsible for it.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrObject::retire`].
+    unsafe fn try_retire(self: *mut Self) -> Result<(), RetireError> {
+        if !std::mem::needs_drop::<Self>() {
+            return Ok(());
+        }
+        unsafe { &*self }
+            .domain()
+            .try_retire(self as *mut dyn Drop, Self::DELETER)
+    }
+}
+
+// A retired object along with the finalizer to call before deleting it.
+struct Finalized<T: HazPtrObject, F: FnOnce(&T)> {
+    ptr: *mut T,
+    deleter: &'static dyn Deleter,
+    finalizer: Option<F>,
+}
+
+impl<T: HazPtrObject, F: FnOnce(&T)> Drop for Finalized<T, F> {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer.take() {
+            // Safety: the object is still valid, since its deleter hasn't run yet.
+            finalizer(unsafe { &*self.ptr });
+        }
+        // Safety: by the safety contract of retire_with_finalizer, no reader can access the
+        // object anymore (or we wouldn't be dropped), and the deleter matches it.
+        unsafe { self.deleter.delete(self.ptr as *mut dyn Drop) };
+    }
+}
+
+// A retired object along with the closure that deletes it.
+struct WithDeleter<T, F: FnOnce(*mut T)> {
+    ptr: *mut T,
+    deleter: Option<F>,
+}
+
+impl<T, F: FnOnce(*mut T)> Drop for WithDeleter<T, F> {
+    fn drop(&mut self) {
+        if let Some(deleter) = self.deleter.take() {
+            // By the safety contract of retire_with, no reader can access the object anymore
+            // (or we wouldn't be dropped), and the deleter matches it.
+            deleter(self.ptr);
+        }
+    }
+}
+
+/// Direct access to a domain's objects without hazard pointers; see
+/// [`HazPtrDomain::unprotected`].
+pub struct Unprotected {
+    domain: &'static HazPtrDomain,
+    // Stays on the thread that made the promises of `unprotected`.
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Unprotected {
+    /// The domain this grants access to.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Load the object in `src` without protecting it.
+    ///
+    /// # Safety
+    ///
+    /// The address in `src` must be valid as a reference, or null, and the object may only be
+    /// deallocated by retiring it on this domain.
+    pub unsafe fn load<'u, T>(&'u self, src: &'_ AtomicPtr<T>) -> Option<&'u T> {
+        // Safety: by the safety contracts of load and HazPtrDomain::unprotected, the object
+        // stays valid for as long as self does.
+        unsafe { src.load(Ordering::SeqCst).as_ref() }
+    }
+}
+
+/// The domain could not allocate memory to keep track of a retired object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetireError(());
+
+impl std::fmt::Display for RetireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("failed to allocate memory to track a retired object")
+    }
+}
+
+impl std::error::Error for RetireError {}
+
+pub struct HazPtrObjectWrapper<T> {
+    inner: T,
+    domain: DomainId,
+}
+
+impl<T> HazPtrObjectWrapper<T> {
+    pub fn with_default_domain(t: T) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, t)
+    }
+
+    pub fn with_domain(domain: &'static HazPtrDomain, t: T) -> Self {
+        Self {
+            inner: t,
+            domain: domain.id(),
+        }
+    }
+
+    /// Turn `this` into a pointer that can be stored in an `AtomicPtr` and loaded through a
+    /// holder like a boxed object, while other clones of the `Arc` stay usable.
+    ///
+    /// The pointer owns the strong reference `this` held. Retire it with
+    /// [`HazPtrObject::retire_with_deleter`] and [`deleters::drop_arc`] to release that reference.
+    pub fn arc_into_raw(this: Arc<Self>) -> *mut Self {
+        Arc::into_raw(this) as *mut Self
+    }
+}
+
+impl<T: 'static> HazPtrObject for HazPtrObjectWrapper<T> {
+    fn domain(&self) -> &HazPtrDomain {
+        self.domain.domain()
+    }
+}
+
+// TODO: get rid of this requirement
+impl<T> Drop for HazPtrObjectWrapper<T> {
+    fn drop(&mut self) {}
+}
+
+impl<T> Deref for HazPtrObjectWrapper<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for HazPtrObjectWrapper<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+// The wrapper is transparent: these all look only at the inner value, not the domain.
+
+impl<T> std::borrow::Borrow<T> for HazPtrObjectWrapper<T> {
+    fn borrow(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> std::borrow::BorrowMut<T> for HazPtrObjectWrapper<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: PartialEq> PartialEq for HazPtrObjectWrapper<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T: Eq> Eq for HazPtrObjectWrapper<T> {}
+
+impl<T: PartialOrd> PartialOrd for HazPtrObjectWrapper<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.inner.partial_cmp(&other.inner)
+    }
+}
+
+impl<T: Ord> Ord for HazPtrObjectWrapper<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
+impl<T: std::hash::Hash> std::hash::Hash for HazPtrObjectWrapper<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner.hash(state)
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for HazPtrObjectWrapper<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// A type that stands for one particular static domain.
+///
+/// Usually implemented through [`static_domain!`], which makes sure every family has a
+/// domain of its own. Code can then be generic over `F: Family` to name a domain at the type
+/// level, so that objects of one family cannot accidentally be paired with another's domain.
+pub trait Family: 'static {
+    /// The domain of this family.
+    fn domain() -> &'static HazPtrDomain;
+}
+
+/// Declare a marker type with its own, isolated static domain.
+///
+/// ```
+/// haphazard::static_domain! {
+///     /// The domain for my library's objects.
+///     pub MyDomain
+/// }
+///
+/// use haphazard::Family;
+/// let h = haphazard::HazPtrHolder::for_domain(MyDomain::domain());
+/// ```
+#[macro_export]
+macro_rules! static_domain {
+    ($(#[$attr:meta])* $vis:vis $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $vis struct $name;
+
+        impl $crate::Family for $name {
+            fn domain() -> &'static $crate::HazPtrDomain {
+                static DOMAIN: $crate::HazPtrDomain = $crate::HazPtrDomain::new();
+                &DOMAIN
+            }
+        }
+    };
+}
+
+/// Evaluate to a domain of its own for this place in the code, with no name to declare.
+///
+/// Every expansion of the macro has a separate static domain, so each evaluation of the same
+/// expansion (say, in a loop or a function called repeatedly) yields the same domain:
+///
+/// ```
+/// use haphazard::{unique_domain, HazPtrHolder};
+///
+/// let domain = unique_domain!();
+/// assert!(!std::ptr::eq(domain, unique_domain!()));
+/// let h = HazPtrHolder::for_domain(domain);
+/// ```
+#[macro_export]
+macro_rules! unique_domain {
+    () => {{
+        static DOMAIN: $crate::HazPtrDomain = $crate::HazPtrDomain::new();
+        &DOMAIN
+    }};
+}
+
+// Holds linked list of HazPtrs
+pub struct HazPtrDomain {
+    hazptrs: HazPtrs,
+    retired: RetiredList,
+    watchdog: Mutex<Option<Watchdog>>,
+    // Whether observer holds a callback, so reclamation only takes the lock when it does. Not
+    // modelled under loom, to keep it out of the reclamation paths loom explores.
+    observed: std::sync::atomic::AtomicBool,
+    observer: RwLock<Option<ReclaimObserver>>,
+    stepping: AtomicBool,
+    alloc: OnceLock<&'static (dyn GlobalAlloc + Sync)>,
+    scan: RwLock<&'static dyn ScanStrategy>,
+    single_writer: AtomicBool,
+    writer: AtomicUsize,
+    private: WriterLocal<PrivateRetired>,
+    stats: Stats,
+    fifo: AtomicBool,
+    backlog: Mutex<FifoBacklog>,
+    affine: Mutex<Vec<Queued>>,
+    confined: AtomicUsize,
+    budgets: RwLock<(RetireBudget, RetireBudget)>,
+    quarantined: AtomicBool,
+    quarantine: Mutex<Option<TimerWheel>>,
+    batching: AtomicBool,
+    batches: [CpuBatch; CPU_BATCHES],
+    // How many of the batches are in use.
+    shards: AtomicUsize,
+    help_requests: AtomicUsize,
+    threshold: RwLock<ReclaimThreshold>,
+    // The domain's DomainId, or 0 until it is first asked for. Not modelled under loom, like the
+    // registry.
+    id: std::sync::atomic::AtomicU64,
+}
+
+/// The number of per-CPU batches of a domain; CPUs beyond that share batches.
+const CPU_BATCHES: usize = 64;
+
+/// The number of retired objects a batch collects before handing them to the retired list.
+const BATCH_SIZE: usize = 32;
+
+// Objects retired on one CPU, not yet on the domain's retired list.
+struct CpuBatch {
+    head: AtomicPtr<Retired>,
+    count: AtomicUsize,
+}
+
+// Retired objects of a single-writer domain, only ever accessed by the writer thread.
+struct PrivateRetired {
+    head: *mut Retired,
+    count: usize,
+}
+
+struct WriterLocal<T>(std::cell::UnsafeCell<T>);
+
+// Retired objects of a FIFO domain, oldest first. Only accessed with the lock held, and the
+// lock is held while their deleters run, so that reclaimers can't overtake one another.
+struct FifoBacklog {
+    head: *mut Retired,
+    tail: *mut Retired,
+}
+
+// Safety: the nodes are only accessed through the Mutex the backlog lives in.
+unsafe impl Send for FifoBacklog {}
+
+// Safety: only the writer thread of a single-writer domain accesses the contents, as required by
+// HazPtrDomain::set_single_writer.
+unsafe impl<T> Sync for WriterLocal<T> {}
+unsafe impl<T> Send for WriterLocal<T> {}
+
+// Forwards to whatever the global allocator is.
+struct Global;
+
+unsafe impl GlobalAlloc for Global {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Safety: by the safety contract of GlobalAlloc::alloc.
+        unsafe { std::alloc::alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Safety: by the safety contract of GlobalAlloc::dealloc.
+        unsafe { std::alloc::dealloc(ptr, layout) }
+    }
+}
+
+/// How close a domain's backlog of retired objects is to its budgets; see
+/// [`HazPtrDomain::set_retire_budgets`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Backpressure {
+    /// The backlog is within the elevated budget.
+    Ok,
+    /// The backlog exceeds the elevated budget. Writers may want to slow down or coalesce
+    /// updates.
+    Elevated,
+    /// The backlog exceeds the critical budget. Writers should avoid retiring more objects, for
+    /// example by updating in place where possible.
+    Critical,
+}
+
+/// Configures a [`HazPtrDomain`]; see [`HazPtrDomain::builder`].
+///
+/// Holders and objects only accept `'static` domains, so a built domain is typically leaked or
+/// kept in a `OnceLock`:
+///
+/// ```
+/// use haphazard::{HazPtrDomain, HazPtrHolder, ReclaimThreshold};
+///
+/// let domain: &'static HazPtrDomain = Box::leak(Box::new(
+///     HazPtrDomain::builder()
+///         .reclaim_threshold(ReclaimThreshold { fixed: 64, per_hazard: 2 })
+///         .hazard_capacity(8)
+///         .build(),
+/// ));
+/// let h = HazPtrHolder::for_domain(domain);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HazPtrDomainBuilder {
+    threshold: ReclaimThreshold,
+    hazards: usize,
+    shards: usize,
+}
+
+impl Default for HazPtrDomainBuilder {
+    fn default() -> Self {
+        Self {
+            threshold: ReclaimThreshold::EVERY_RETIRE,
+            hazards: 0,
+            shards: 1,
+        }
+    }
+}
+
+impl HazPtrDomainBuilder {
+    /// See [`HazPtrDomain::set_reclaim_threshold`].
+    pub fn reclaim_threshold(mut self, threshold: ReclaimThreshold) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Allocate `hazards` hazard slots up front, so that the first that many holders don't have
+    /// to.
+    pub fn hazard_capacity(mut self, hazards: usize) -> Self {
+        self.hazards = hazards;
+        self
+    }
+
+    /// Spread retirements over `shards` batches, as with [`HazPtrDomain::set_cpu_batching`].
+    /// One shard, the default, retires straight to a single list.
+    ///
+    /// At most 64 shards are used.
+    pub fn retired_shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    pub fn build(self) -> HazPtrDomain {
+        let mut domain = HazPtrDomain::new();
+        *domain.threshold.get_mut().unwrap() = self.threshold;
+        domain.batching.store_mut(self.shards > 1);
+        domain.shards.store_mut(self.shards.clamp(1, CPU_BATCHES));
+        for _ in 0..self.hazards {
+            let hazptr = domain.alloc_hazptr();
+            // Safety: hazptr was never shared, so &mut is ok.
+            let h = unsafe { &mut *hazptr };
+            h.active.store_mut(false);
+            h.next.store_mut(domain.hazptrs.head.load_mut());
+            domain.hazptrs.head.store_mut(hazptr);
+        }
+        domain
+    }
+}
+
+/// How many retired objects have to pile up before retiring one triggers a reclamation pass;
+/// see [`HazPtrDomain::set_reclaim_threshold`].
+///
+/// The threshold is the larger of `fixed` and `per_hazard` times the number of hazard slots
+/// currently in use. With a `per_hazard` factor above 1, every pass is guaranteed to reclaim
+/// some objects, which is what makes reclamation cost amortized constant time per retired
+/// object in the classic hazard pointer algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReclaimThreshold {
+    /// A threshold independent of the number of readers.
+    pub fixed: usize,
+    /// A threshold per hazard slot in use.
+    pub per_hazard: usize,
+}
+
+impl ReclaimThreshold {
+    /// Reclaim whenever an object is retired. This is the default.
+    pub const EVERY_RETIRE: Self = Self {
+        fixed: 1,
+        per_hazard: 0,
+    };
+}
+
+/// A limit on the backlog of retired objects, by count and by combined size; exceeding either
+/// exceeds the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetireBudget {
+    /// The number of objects waiting to be reclaimed.
+    pub objects: usize,
+    /// The combined size of the objects waiting to be reclaimed, not counting any memory they
+    /// own indirectly.
+    pub bytes: usize,
+}
+
+impl RetireBudget {
+    /// A budget that is never exceeded.
+    pub const UNLIMITED: Self = Self {
+        objects: usize::MAX,
+        bytes: usize::MAX,
+    };
+
+    fn exceeded_by(&self, objects: usize, bytes: usize) -> bool {
+        objects > self.objects || bytes > self.bytes
+    }
+}
+
+/// When a domain should free hazard slots that are no longer in use.
+///
+/// Once more than `high_water` slots are unused, the domain frees unused slots until only
+/// `low_water` remain. The gap between the two keeps the domain from repeatedly freeing and
+/// re-allocating slots when the number of readers hovers around a single threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShrinkPolicy {
+    /// The number of unused slots above which the domain shrinks.
+    pub high_water: usize,
+    /// The number of unused slots to keep when shrinking.
+    pub low_water: usize,
+}
+
+/// The outcome of [`HazPtrDomain::eager_reclaim_budgeted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReclaimProgress {
+    /// The number of objects reclaimed.
+    pub reclaimed: usize,
+    /// The number of objects still waiting to be reclaimed.
+    pub remaining: usize,
+}
+
+/// Retired objects were left over when [`HazPtrDomain::drain_with_timeout`] gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrainIncomplete {
+    /// The number of objects that were not reclaimed.
+    pub remaining: usize,
+    /// The combined size of those objects, not counting any memory they own indirectly.
+    pub bytes: usize,
+    /// The hazards protecting those objects.
+    pub blocking: Vec<BlockingHazard>,
+}
+
+/// What [`HazPtrDomain::teardown`] did with the objects left in a domain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Teardown {
+    /// The number of objects whose deleters were run.
+    pub reclaimed: usize,
+    /// The number of objects retired with [`HazPtrObject::retire_on`] for another thread, whose
+    /// deleters could not be run and were leaked instead.
+    pub leaked: usize,
+}
+
+/// A hazard that kept a retired object from being reclaimed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockingHazard {
+    /// A stable identifier for the hazard slot (the address of its record).
+    pub slot: usize,
+    /// The index of the thread that acquired the slot.
+    pub thread: usize,
+    /// The protected address.
+    pub addr: usize,
+}
+
+impl std::fmt::Display for DrainIncomplete {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} retired objects ({} bytes) were not reclaimed, blocked by {} hazards",
+            self.remaining,
+            self.bytes,
+            self.blocking.len()
+        )
+    }
+}
+
+impl std::error::Error for DrainIncomplete {}
+
+/// A hazard that has been continuously published for longer than the watchdog threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogReport {
+    /// A stable identifier for the hazard slot (the address of its record).
+    pub slot: usize,
+    /// The index of the thread that acquired the slot.
+    pub thread: usize,
+    /// The protected address.
+    pub addr: usize,
+    /// How long the slot has been protecting `ptr`, as far as the domain's scans can tell.
+    pub held_for: Duration,
+}
+
+struct Watchdog {
+    threshold: Duration,
+    callback: Arc<dyn Fn(&WatchdogReport) + Send + Sync>,
+    // slot -> (protected address, first seen, already reported)
+    seen: HashMap<usize, (usize, Instant, bool)>,
+}
+
+type ReclaimObserver = Arc<dyn Fn(&Reclaimed) + Send + Sync>;
+
+/// An object a domain has reclaimed; see [`HazPtrDomain::set_reclaim_observer`].
+#[derive(Clone, Copy)]
+pub struct Reclaimed {
+    /// The address the object had. It has been deleted, so this is only good for comparisons.
+    pub addr: usize,
+    /// The deleter that reclaimed the object.
+    pub deleter: &'static dyn Deleter,
+}
+
+impl Reclaimed {
+    /// Whether the object was reclaimed by `deleter`.
+    pub fn deleted_by(&self, deleter: &'static dyn Deleter) -> bool {
+        std::ptr::addr_eq(self.deleter, deleter)
+    }
+}
+
+impl std::fmt::Debug for Reclaimed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reclaimed")
+            .field("addr", &self.addr)
+            .field(
+                "deleter",
+                &(self.deleter as *const dyn Deleter as *const ()),
+            )
+            .finish()
+    }
+}
+
+impl HazPtrDomain {
+    const_fn! {
+        /// Create a new domain, separate from the global one.
+        ///
+        /// This is a `const fn`, so domains can be declared as statics without any lazy
+        /// initialization. Holders and objects only accept `'static` domains.
+        pub const fn new() -> Self {
+            Self {
+                hazptrs: HazPtrs::new(),
+                retired: RetiredList {
+                    head: AtomicPtr::new(std::ptr::null_mut()),
+                    count: AtomicUsize::new(0),
+                },
+                watchdog: Mutex::new(None),
+                observed: std::sync::atomic::AtomicBool::new(false),
+                observer: RwLock::new(None),
+                stepping: AtomicBool::new(false),
+                alloc: OnceLock::new(),
+                scan: RwLock::new(&scan::Hashed),
+                single_writer: AtomicBool::new(false),
+                writer: AtomicUsize::new(usize::MAX),
+                private: WriterLocal(std::cell::UnsafeCell::new(PrivateRetired {
+                    head: std::ptr::null_mut(),
+                    count: 0,
+                })),
+                stats: Stats::new(),
+                fifo: AtomicBool::new(false),
+                backlog: Mutex::new(FifoBacklog {
+                    head: std::ptr::null_mut(),
+                    tail: std::ptr::null_mut(),
+                }),
+                affine: Mutex::new(Vec::new()),
+                confined: AtomicUsize::new(usize::MAX),
+                budgets: RwLock::new((RetireBudget::UNLIMITED, RetireBudget::UNLIMITED)),
+                quarantined: AtomicBool::new(false),
+                quarantine: Mutex::new(None),
+                batching: AtomicBool::new(false),
+                batches: atomic_array!(CpuBatch = CpuBatch {
+                    head: AtomicPtr::new(std::ptr::null_mut()),
+                    count: AtomicUsize::new(0),
+                }; CPU_BATCHES),
+                shards: AtomicUsize::new(CPU_BATCHES),
+                help_requests: AtomicUsize::new(0),
+                threshold: RwLock::new(ReclaimThreshold::EVERY_RETIRE),
+                id: std::sync::atomic::AtomicU64::new(0),
+            }
+        }
+    }
+
+    /// Configure a domain before creating it.
+    pub fn builder() -> HazPtrDomainBuilder {
+        HazPtrDomainBuilder::default()
+    }
+
+    /// The domain used by [`HazPtrHolder::default`] and
+    /// [`HazPtrObjectWrapper::with_default_domain`].
+    pub fn global() -> &'static Self {
+        &SHARED_DOMAIN
+    }
+
+    // Allocate a new, active HazPtr, not yet linked into the list.
+    fn alloc_hazptr(&self) -> *mut HazPtr {
+        let layout = Layout::new::<HazPtr>();
+        // Safety: HazPtr is not zero-sized.
+        let hazptr = unsafe { self.allocator().alloc(layout) } as *mut HazPtr;
+        if hazptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        // Safety: hazptr was just allocated with the layout of HazPtr.
+        unsafe {
+            hazptr.write(HazPtr {
+                ptr: AtomicPtr::new(std::ptr::null_mut()),
+                next: AtomicPtr::new(std::ptr::null_mut()),
+                active: AtomicBool::new(true),
+                owner: AtomicUsize::new(thread_index()),
+            })
+        };
+        hazptr
+    }
+
+    /// Like `acquire`, but for `N` slots at once: the list is walked once, and any slots that
+    /// have to be allocated are linked in with a single update of its head.
+    fn acquire_many<const N: usize>(&self) -> [&'static HazPtr; N] {
+        self.check_confined();
+        trace_event!(
+            domain = (self as *const Self).addr(),
+            count = N,
+            "acquired hazard slots"
+        );
+        let mut acquired = [std::ptr::null_mut::<HazPtr>(); N];
+        let mut n = 0;
+        while n < N {
+            match self.cached_hazptr() {
+                Some(hazptr) => acquired[n] = hazptr as *const HazPtr as *mut HazPtr,
+                None => break,
+            }
+            n += 1;
+        }
+        if n < N {
+            let _walk = self.hazptrs.walk();
+            let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+            while !node.is_null() && n < N {
+                // Safety: HazPtrs are not de-allocated while we walk the list.
+                let h = unsafe { &*node };
+                if !h.active.load(Ordering::SeqCst)
+                    && h.active
+                        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_ok()
+                {
+                    h.owner.store(thread_index(), Ordering::SeqCst);
+                    acquired[n] = node;
+                    n += 1;
+                }
+                node = h.next.load(Ordering::SeqCst);
+            }
+        }
+        if n < N {
+            // Chain up the slots we still need, and link them in all together.
+            let mut chain = std::ptr::null_mut();
+            let mut tail: *mut HazPtr = std::ptr::null_mut();
+            for slot in &mut acquired[n..] {
+                let hazptr = self.alloc_hazptr();
+                // Safety: hazptr was never shared, so &mut is ok.
+                unsafe { (*hazptr).next.store_mut(chain) };
+                if tail.is_null() {
+                    tail = hazptr;
+                }
+                chain = hazptr;
+                *slot = hazptr;
+            }
+            let head_ptr = &self.hazptrs.head;
+            let mut head = head_ptr.load(Ordering::SeqCst);
+            loop {
+                // Safety: the chain was never shared, so &mut is ok.
+                unsafe { (*tail).next.store_mut(head) };
+                match head_ptr.compare_exchange_weak(
+                    head,
+                    chain,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    Ok(_) => break,
+                    Err(head_now) => head = head_now,
+                }
+            }
+        }
+        acquired.map(|hazptr| {
+            self.stats.acquired_hazard();
+            // Safety: active HazPtrs are never de-allocated.
+            unsafe { &*hazptr }
+        })
+    }
+
+    /// Make sure at least `n` hazard slots are free, allocating the ones that are missing, so
+    /// that the next `n` holders to protect an object find a slot without allocating.
+    ///
+    /// Slots reserved beyond what a [`ShrinkPolicy`] allows are freed again by the next scan.
+    pub fn reserve_hazards(&self, n: usize) {
+        let free = {
+            let _walk = self.hazptrs.walk();
+            let mut free = 0;
+            let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+            while !node.is_null() && free < n {
+                // Safety: HazPtrs are not de-allocated while we walk the list.
+                let h = unsafe { &*node };
+                if !h.active.load(Ordering::SeqCst) {
+                    free += 1;
+                }
+                node = h.next.load(Ordering::SeqCst);
+            }
+            free
+        };
+        if free == n {
+            return;
+        }
+
+        let mut chain: *mut HazPtr = std::ptr::null_mut();
+        let mut tail: *mut HazPtr = std::ptr::null_mut();
+        for _ in free..n {
+            let hazptr = self.alloc_hazptr();
+            // Safety: hazptr was never shared, so &mut is ok.
+            let h = unsafe { &mut *hazptr };
+            h.active.store_mut(false);
+            h.next.store_mut(chain);
+            if tail.is_null() {
+                tail = hazptr;
+            }
+            chain = hazptr;
+        }
+        let head_ptr = &self.hazptrs.head;
+        let mut head = head_ptr.load(Ordering::SeqCst);
+        loop {
+            // Safety: the chain was never shared, so &mut is ok.
+            unsafe { (*tail).next.store_mut(head) };
+            match head_ptr.compare_exchange_weak(head, chain, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(head_now) => head = head_now,
+            }
+        }
+    }
+
+    fn acquire(&self) -> &'static HazPtr {
+        self.check_confined();
+        self.stats.acquired_hazard();
+        trace_event!(
+            domain = (self as *const Self).addr(),
+            "acquired hazard slot"
+        );
+        if let Some(hazptr) = self.cached_hazptr() {
+            return hazptr;
+        }
+        let _walk = self.hazptrs.walk();
+        let head_ptr = &self.hazptrs.head;
+        let mut node = head_ptr.load(Ordering::SeqCst);
+        loop {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            while !node.is_null() && unsafe { &*node }.active.load(Ordering::SeqCst) {
+                // Safety: HazPtrs are not de-allocated while we walk the list.
+                node = unsafe { &*node }.next.load(Ordering::SeqCst);
+            }
+            if node.is_null() {
+                // No free HazPtrs -- need to allocate a new one
+                let hazptr = self.alloc_hazptr();
+                // And stick it at the head of the linked list
+                let mut head = head_ptr.load(Ordering::SeqCst);
+                break loop {
+                    // Safety: hazptr was never shared, so &mut is ok.
+                    unsafe { (*hazptr).next.store_mut(head) };
+                    match head_ptr.compare_exchange_weak(
+                        head,
+                        hazptr,
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    ) {
+                        Ok(_) => {
+                            // Safety: active HazPtrs are never de-allocated.
+                            break unsafe { &*hazptr };
+                        }
+                        Err(head_now) => {
+                            // Head has changed, try again with that as our next ptr.
+                            head = head_now
+                        }
+                    }
+                };
+            } else {
+                // Safety: HazPtrs are not de-allocated while we walk the list, and once we've
+                // made it active, it won't be de-allocated at all.
+                let node = unsafe { &*node };
+                if node
+                    .active
+                    .compare_exchange_weak(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
+                    // It's ours!
+                    node.owner.store(thread_index(), Ordering::SeqCst);
+                    break node;
+                } else {
+                    // Someone else grabbed this node right before us.
+                    // Keep walking
+                }
+            }
+        }
+    }
+
+    /// Take one of this domain's records from the calling thread's cache.
+    fn cached_hazptr(&self) -> Option<&'static HazPtr> {
+        // Records are only cached for registered domains.
+        let id = self.registered_id()?;
+        HAZPTR_CACHE
+            .try_with(|cache| {
+                let mut cache = cache.borrow_mut();
+                let i = cache.0.iter().rposition(|&(d, _)| d == id)?;
+                let hazptr = cache.0.swap_remove(i).1;
+                hazptr.owner.store(thread_index(), Ordering::SeqCst);
+                Some(hazptr)
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Give back a reset `hazptr`, by way of the calling thread's cache if it has room.
+    fn release(&'static self, hazptr: &'static HazPtr) {
+        if !self.cache_hazptr(hazptr) {
+            hazptr.active.store(false, Ordering::SeqCst);
+        }
+        self.stats.released_hazard();
+    }
+
+    /// Keep the released, but still active, `hazptr` in the calling thread's cache, if it has
+    /// room.
+    fn cache_hazptr(&'static self, hazptr: &'static HazPtr) -> bool {
+        let id = self.id();
+        HAZPTR_CACHE
+            .try_with(|cache| {
+                let mut cache = cache.borrow_mut();
+                if cache.0.len() < CACHED_HAZPTRS {
+                    hazptr.owner.store(CACHED, Ordering::SeqCst);
+                    cache.0.push((id, hazptr));
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(false)
+    }
+
+    /// Release the records of this domain in the calling thread's cache.
+    fn uncache_hazptrs(&self) {
+        let Some(id) = self.registered_id() else {
+            return;
+        };
+        let _ = HAZPTR_CACHE.try_with(|cache| {
+            cache.borrow_mut().0.retain(|&(d, hazptr)| {
+                if d == id {
+                    hazptr.active.store(false, Ordering::SeqCst);
+                }
+                d != id
+            })
+        });
     }
 
     fn retire(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
-        // First, stick ptr onto the list of retired objects.
-        let retired = Box::into_raw(Box::new(Retired {
-            ptr,
-            deleter,
-            next: AtomicPtr::new(std::ptr::null_mut()),
+        self.retire_at(ptr as *mut u8, ptr, deleter)
+    }
+
+    /// Like `retire`, but for an object that readers protect by `addr` rather than by `ptr`.
+    fn retire_at(&self, addr: *mut u8, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        self.check_confined();
+        let retired = match self.alloc_retired(addr, ptr, deleter) {
+            Some(retired) => retired,
+            None => {
+                // Out of memory -- reclaiming frees up the bookkeeping of any reclaimed objects,
+                // so try that before giving up.
+                self.bulk_reclaim(0, false);
+                match self.alloc_retired(addr, ptr, deleter) {
+                    Some(retired) => retired,
+                    None if !self.fifo.load(Ordering::SeqCst)
+                        && !self.quarantined.load(Ordering::SeqCst)
+                        && !self.is_guarded(addr) =>
+                    {
+                        // No reader can get at ptr anymore, and nothing asks for it to be kept
+                        // around for longer, so there's nothing to track. Objects retired here
+                        // have no thread affinity, and check_confined made sure that a confined
+                        // domain's objects are deleted on its own thread.
+                        // Safety: by the safety guarantees of calling `retire`, ptr is no longer
+                        // accessible, has not been dropped, and matches deleter.
+                        unsafe { self.delete(ptr, deleter) };
+                        return;
+                    }
+                    None => std::alloc::handle_alloc_error(Layout::new::<Retired>()),
+                }
+            }
+        };
+        self.push_retired(retired);
+    }
+
+    fn try_retire(
+        &self,
+        ptr: *mut dyn Drop,
+        deleter: &'static dyn Deleter,
+    ) -> Result<(), RetireError> {
+        self.check_confined();
+        let retired = self
+            .alloc_retired(ptr as *mut u8, ptr, deleter)
+            .ok_or(RetireError(()))?;
+        self.push_retired(retired);
+        Ok(())
+    }
+
+    fn alloc_retired(
+        &self,
+        addr: *mut u8,
+        ptr: *mut dyn Drop,
+        deleter: &'static dyn Deleter,
+    ) -> Option<*mut Retired> {
+        // Safety: Retired is not zero-sized.
+        let retired = unsafe { self.allocator().alloc(Layout::new::<Retired>()) } as *mut Retired;
+        if retired.is_null() {
+            return None;
+        }
+        // Safety: retired was just allocated with the layout of Retired.
+        unsafe {
+            retired.write(Retired {
+                addr,
+                ptr,
+                deleter,
+                affinity: None,
+                retired_at: Instant::now(),
+                next: AtomicPtr::new(std::ptr::null_mut()),
+            })
+        };
+        trace_event!(addr = addr.addr(), "retired");
+        Some(retired)
+    }
+
+    /// Move `retired` out of its bookkeeping allocation, and free that allocation.
+    ///
+    /// # Safety
+    ///
+    /// `retired` must have come from `alloc_retired` and be exclusively owned by the caller.
+    unsafe fn take_retired(&self, retired: *mut Retired) -> Retired {
+        // Safety: retired is valid and exclusively ours, and is not used again after this.
+        let taken = unsafe { retired.read() };
+        // Safety: retired was allocated by our allocator with this layout.
+        unsafe {
+            self.allocator()
+                .dealloc(retired as *mut u8, Layout::new::<Retired>())
+        };
+        taken
+    }
+
+    /// Run the deleter of a retired object that is no longer guarded, or queue it for the thread
+    /// it has to run on.
+    ///
+    /// # Safety
+    ///
+    /// `n.ptr` must not be guarded, and must not have been deleted yet.
+    unsafe fn dispose(&self, n: Retired) {
+        self.stats.reclaimed(
+            // Safety: the retired object is still valid.
+            std::mem::size_of_val(unsafe { &*n.ptr }),
+            n.retired_at.elapsed(),
+        );
+        match n.affinity {
+            Some(thread) if thread != std::thread::current().id() => {
+                self.affine.lock().unwrap().push(Queued {
+                    thread,
+                    ptr: n.ptr,
+                    deleter: n.deleter,
+                });
+            }
+            // Safety: by the safety contract of dispose.
+            _ => unsafe { self.delete(n.ptr, n.deleter) },
+        }
+    }
+
+    /// Run `deleter` on `ptr`, and tell the reclaim observer, if any.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must not be guarded, must not have been deleted yet, and must match `deleter`.
+    unsafe fn delete(&self, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        // Safety: by the safety contract of delete.
+        unsafe { deleter.delete(ptr) };
+        if self.observed.load(std::sync::atomic::Ordering::Acquire) {
+            let observer = self.observer.read().unwrap().clone();
+            if let Some(observer) = observer {
+                observer(&Reclaimed {
+                    addr: ptr.addr(),
+                    deleter,
+                });
+            }
+        }
+    }
+
+    /// Retire `ptr` like `retire`, but only ever run its deleter on `thread`.
+    fn retire_on(&self, thread: ThreadId, ptr: *mut dyn Drop, deleter: &'static dyn Deleter) {
+        self.check_confined();
+        let retired = self
+            .alloc_retired(ptr as *mut u8, ptr, deleter)
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(Layout::new::<Retired>()));
+        // Safety: retired was just allocated, and is not shared yet.
+        unsafe { (*retired).affinity = Some(thread) };
+        self.push_retired(retired);
+    }
+
+    /// Run the deleters of objects retired with [`HazPtrObject::retire_on`] for the calling
+    /// thread that have become reclaimable, and return how many were run.
+    ///
+    /// Such objects are reclaimed like any other, but reclaiming them on another thread only
+    /// queues their deleter for this thread. A thread that others retire objects for should
+    /// call this regularly, for example from its event loop.
+    pub fn run_thread_deleters(&self) -> usize {
+        let me = std::thread::current().id();
+        let mine: Vec<_> = {
+            let mut affine = self.affine.lock().unwrap();
+            let (mine, others) = affine.drain(..).partition(|q| q.thread == me);
+            *affine = others;
+            mine
+        };
+        let n = mine.len();
+        for q in mine {
+            // Safety: q was no longer guarded when it was queued, and is only queued once.
+            unsafe { self.delete(q.ptr, q.deleter) };
+        }
+        n
+    }
+
+    /// Use `alloc` for this domain's own bookkeeping: its hazard records and the nodes that keep
+    /// track of retired objects. The retired objects themselves are unaffected.
+    ///
+    /// The allocator can only be chosen before the domain makes its first bookkeeping
+    /// allocation, and at most once. Otherwise, `alloc` is handed back as an error.
+    pub fn set_bookkeeping_allocator(
+        &self,
+        alloc: &'static (dyn GlobalAlloc + Sync),
+    ) -> Result<(), &'static (dyn GlobalAlloc + Sync)> {
+        self.alloc.set(alloc)
+    }
+
+    fn allocator(&self) -> &'static (dyn GlobalAlloc + Sync) {
+        *self.alloc.get_or_init(|| &Global)
+    }
+
+    fn is_guarded(&self, ptr: *mut u8) -> bool {
+        barrier::heavy();
+        let _walk = self.hazptrs.walk();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            if n.ptr.load(Ordering::SeqCst) == ptr {
+                return true;
+            }
+            node = n.next.load(Ordering::SeqCst);
+        }
+        false
+    }
+
+    fn push_retired(&self, retired: *mut Retired) {
+        if self.quarantined.load(Ordering::SeqCst) {
+            let mut quarantine = self.quarantine.lock().unwrap();
+            if let Some(wheel) = quarantine.as_mut() {
+                // Safety: the retired object is still valid.
+                self.stats
+                    .retired(std::mem::size_of_val(unsafe { &*(*retired).ptr }));
+                wheel.schedule(retired);
+                drop(quarantine);
+                if !self.stepping.load(Ordering::SeqCst) {
+                    self.bulk_reclaim(0, false);
+                }
+                return;
+            }
+        }
+        if self.single_writer.load(Ordering::Relaxed) {
+            return self.push_private(retired);
+        }
+        // Safety: the retired object is still valid.
+        self.stats
+            .retired(std::mem::size_of_val(unsafe { &*(*retired).ptr }));
+        if self.batching.load(Ordering::Relaxed) {
+            return self.push_batch(retired);
+        }
+        // Increment the count _before_ we give anyone a chance to reclaim it.
+        self.retired.count.fetch_add(1, Ordering::SeqCst);
+        // Stick it at the head of the linked list
+        let head_ptr = &self.retired.head;
+        let mut head = head_ptr.load(Ordering::SeqCst);
+        loop {
+            // Safety: retired was never shared, so &mut is ok.
+            unsafe { (*retired).next.store_mut(head) };
+            match head_ptr.compare_exchange_weak(head, retired, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(head_now) => {
+                    // Head has changed, try again with that as our next ptr.
+                    head = head_now
+                }
+            }
+        }
+
+        // Now, check if we need to reclaim.
+        if !self.stepping.load(Ordering::SeqCst)
+            && self.retired.count.load(Ordering::SeqCst) >= self.reclaim_threshold()
+        {
+            self.bulk_reclaim(0, false);
+        }
+    }
+
+    /// Register a callback to be invoked when any hazard has been continuously published for
+    /// longer than `threshold`.
+    ///
+    /// Hazards are sampled during reclamation scans (and [`HazPtrDomain::check_watchdog`]), so
+    /// durations are only as precise as the scan frequency, and a slot that is reset and then
+    /// protects the same address again between two scans is considered continuously held. The
+    /// callback is invoked once per continuous protection, and replaces any earlier callback.
+    ///
+    /// The callback runs on the scanning thread once the scan is done with the domain's hazard
+    /// list, so it may use the domain like any other code: protect objects, duplicate
+    /// protections, retire, reclaim, and even replace or clear the watchdog.
+    pub fn set_watchdog<F>(&self, threshold: Duration, callback: F)
+    where
+        F: Fn(&WatchdogReport) + Send + Sync + 'static,
+    {
+        *self.watchdog.lock().unwrap() = Some(Watchdog {
+            threshold,
+            callback: Arc::new(callback),
+            seen: HashMap::new(),
+        });
+    }
+
+    /// Remove the callback registered with [`HazPtrDomain::set_watchdog`].
+    pub fn clear_watchdog(&self) {
+        *self.watchdog.lock().unwrap() = None;
+    }
+
+    /// Register a callback to be invoked each time this domain reclaims an object, right after
+    /// the object's deleter has run.
+    ///
+    /// The callback runs on whichever thread reclaims the object, which for objects retired with
+    /// [`HazPtrObject::retire_on`] is the thread their deleter runs on. It replaces any earlier
+    /// callback.
+    pub fn set_reclaim_observer<F>(&self, callback: F)
+    where
+        F: Fn(&Reclaimed) + Send + Sync + 'static,
+    {
+        *self.observer.write().unwrap() = Some(Arc::new(callback));
+        self.observed
+            .store(true, std::sync::atomic::Ordering::Release);
+    }
+
+    /// Remove the callback registered with [`HazPtrDomain::set_reclaim_observer`].
+    pub fn clear_reclaim_observer(&self) {
+        self.observed
+            .store(false, std::sync::atomic::Ordering::Release);
+        *self.observer.write().unwrap() = None;
+    }
+
+    /// Scan the hazards of this domain and report any long-held ones to the watchdog.
+    pub fn check_watchdog(&self) {
+        let mut hazards = Vec::new();
+        let walk = self.hazptrs.walk();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            hazards.push(WatchedHazard::new(n));
+            node = n.next.load(Ordering::SeqCst);
+        }
+        drop(walk);
+        self.watch(&hazards);
+    }
+
+    /// Report the hazards in `hazards` that have been held for too long to the watchdog.
+    ///
+    /// Must not be called while walking the hazard list, since the callback may duplicate
+    /// protections, which waits for walks to end.
+    fn watch(&self, hazards: &[WatchedHazard]) {
+        // Never hold up a scan waiting for the watchdog.
+        let mut guard = match self.watchdog.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let watchdog = match &mut *guard {
+            Some(watchdog) => watchdog,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let mut reports = Vec::new();
+        let mut seen = HashMap::with_capacity(watchdog.seen.len());
+        for &WatchedHazard { slot, owner, ptr } in hazards {
+            if ptr.is_null() {
+                continue;
+            }
+            let (since, mut reported) = match watchdog.seen.get(&slot) {
+                Some(&(addr, since, reported)) if addr == ptr.addr() => (since, reported),
+                _ => (now, false),
+            };
+            let held_for = now - since;
+            if !reported && held_for > watchdog.threshold {
+                reported = true;
+                reports.push(WatchdogReport {
+                    slot,
+                    thread: owner,
+                    addr: ptr.addr(),
+                    held_for,
+                });
+            }
+            seen.insert(slot, (ptr.addr(), since, reported));
+        }
+        watchdog.seen = seen;
+
+        let callback = Arc::clone(&watchdog.callback);
+        drop(guard);
+        for report in &reports {
+            callback(report);
+        }
+    }
+
+    pub fn eager_reclaim(&self, block: bool) -> usize {
+        self.check_confined();
+        let reclaimed = self.bulk_reclaim(0, block);
+        if self.is_writer() {
+            reclaimed + self.reclaim_private(block, usize::MAX)
+        } else {
+            reclaimed
+        }
+    }
+
+    /// Reclaim retired objects until none are left, or `timeout` has passed.
+    ///
+    /// Intended for orderly shutdown. If objects remain at the deadline, the error describes
+    /// them and the hazards that kept them from being reclaimed.
+    pub fn drain_with_timeout(&self, timeout: Duration) -> Result<(), DrainIncomplete> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.eager_reclaim(false);
+            if self.waiting() == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(self.diagnose_drain());
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Drop the domain, reclaiming every object still retired on it, and report what happened
+    /// to them.
+    ///
+    /// Dropping a domain does the same, without the report. Since holders and objects only
+    /// accept `'static` domains, nothing can be protecting the objects anymore.
+    pub fn teardown(mut self) -> Teardown {
+        self.reclaim_all()
+    }
+
+    /// Like [`HazPtrDomain::eager_reclaim`], but run at most `max_objects` deleters, so that
+    /// latency-sensitive callers can spread reclamation over several calls.
+    ///
+    /// The returned progress says how many objects were reclaimed, and how many are still
+    /// waiting to be reclaimed, whether because they are guarded or because the budget ran out.
+    pub fn eager_reclaim_budgeted(&self, max_objects: usize) -> ReclaimProgress {
+        self.check_confined();
+        self.maybe_shrink();
+        self.release_quarantined(false);
+        self.flush_batches();
+        let mut reclaimed = 0;
+        if self.fifo.load(Ordering::SeqCst) {
+            if self.is_writer() {
+                self.hand_over_private();
+            }
+            if let Ok(mut backlog) = self.backlog.try_lock() {
+                reclaimed = self.reclaim_backlog(&mut backlog, max_objects);
+            }
+        } else {
+            let steal = self
+                .retired
+                .head
+                .swap(std::ptr::null_mut(), Ordering::SeqCst);
+            if !steal.is_null() {
+                let start = Instant::now();
+                let guarded_ptrs = self.guarded_ptrs();
+                // Safety: the stolen list is no longer reachable by anyone else.
+                let (n, remaining, tail) =
+                    unsafe { self.reclaim_unguarded(steal, &*guarded_ptrs, max_objects) };
+                self.stats.reclaim_pass(start.elapsed());
+                self.retired.count.fetch_sub(n, Ordering::SeqCst);
+                if let Some(tail) = tail {
+                    self.splice_retired(remaining, tail);
+                }
+                reclaimed = n;
+            }
+            if self.is_writer() {
+                reclaimed += self.reclaim_private(false, max_objects - reclaimed);
+            }
+        }
+        ReclaimProgress {
+            reclaimed,
+            remaining: self.waiting(),
+        }
+    }
+
+    /// The number of objects waiting to be reclaimed. Objects in a single-writer domain's
+    /// private list are only included when called from the writer thread.
+    fn waiting(&self) -> usize {
+        let mut waiting = self.retired.count.load(Ordering::SeqCst);
+        if self.is_writer() {
+            // Safety: only the writer thread accesses the private list.
+            waiting += unsafe { &*self.private.0.get() }.count;
+        }
+        if let Some(wheel) = self.quarantine.lock().unwrap().as_ref() {
+            waiting += wheel.len();
+        }
+        waiting
+    }
+
+    fn diagnose_drain(&self) -> DrainIncomplete {
+        let mut incomplete = DrainIncomplete {
+            remaining: 0,
+            bytes: 0,
+            blocking: Vec::new(),
+        };
+        self.flush_batches();
+        // Take the list so that nobody reclaims from it while we look.
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        let mut remaining = HashSet::new();
+        let mut tail = std::ptr::null_mut();
+        let mut node = steal;
+        while !node.is_null() {
+            // Safety: we have exclusive access to the stolen list.
+            let n = unsafe { &*node };
+            incomplete.remaining += 1;
+            // Safety: retired objects stay valid until they are reclaimed.
+            incomplete.bytes += std::mem::size_of_val(unsafe { &*n.ptr });
+            remaining.insert(n.addr);
+            tail = node;
+            node = n.next.load(Ordering::SeqCst);
+        }
+        if !steal.is_null() {
+            self.splice_retired(steal, tail);
+        }
+        {
+            let backlog = self.backlog.lock().unwrap();
+            let mut node = backlog.head;
+            while !node.is_null() {
+                // Safety: we hold the backlog lock.
+                let n = unsafe { &*node };
+                incomplete.remaining += 1;
+                // Safety: retired objects stay valid until they are reclaimed.
+                incomplete.bytes += std::mem::size_of_val(unsafe { &*n.ptr });
+                remaining.insert(n.addr);
+                node = n.next.load(Ordering::SeqCst);
+            }
+        }
+        if let Some(wheel) = self.quarantine.lock().unwrap().as_ref() {
+            wheel.for_each(|n| {
+                incomplete.remaining += 1;
+                // Safety: retired objects stay valid until they are reclaimed.
+                incomplete.bytes += std::mem::size_of_val(unsafe { &*n.ptr });
+                remaining.insert(n.addr);
+            });
+        }
+
+        let _walk = self.hazptrs.walk();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            let ptr = n.ptr.load(Ordering::SeqCst);
+            if remaining.contains(&ptr) {
+                incomplete.blocking.push(BlockingHazard {
+                    slot: node.addr(),
+                    thread: n.owner.load(Ordering::SeqCst),
+                    addr: ptr.addr(),
+                });
+            }
+            node = n.next.load(Ordering::SeqCst);
+        }
+        incomplete
+    }
+
+    /// Access objects of this domain without publishing hazards, for phases in which no other
+    /// thread can retire or reclaim them, such as single-threaded construction and teardown.
+    ///
+    /// Debug builds assert that no other thread holds a hazard slot of this domain. Slots that
+    /// other threads merely keep cached for reuse don't count.
+    ///
+    /// # Safety
+    ///
+    /// For as long as the returned [`Unprotected`] exists, no object loaded through it may be
+    /// retired by another thread, or reclaimed.
+    pub unsafe fn unprotected(&'static self) -> Unprotected {
+        if cfg!(debug_assertions) {
+            let me = thread_index();
+            let _walk = self.hazptrs.walk();
+            let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+            while !node.is_null() {
+                // Safety: HazPtrs are not de-allocated while we walk the list.
+                let n = unsafe { &*node };
+                assert!(
+                    !n.is_held() || n.owner.load(Ordering::SeqCst) == me,
+                    "another thread holds a hazard slot of a domain accessed unprotected"
+                );
+                node = n.next.load(Ordering::SeqCst);
+            }
+        }
+        Unprotected {
+            domain: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Enable or disable step mode, intended for deterministic tests.
+    ///
+    /// While step mode is enabled, retiring an object never triggers reclamation, so retired
+    /// objects are only reclaimed by explicit calls to [`HazPtrDomain::step`] (or
+    /// [`HazPtrDomain::eager_reclaim`]).
+    pub fn set_step_mode(&self, enabled: bool) {
+        self.stepping.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Reclaim the oldest retired object that is not currently guarded, if any.
+    ///
+    /// Returns `true` if an object was reclaimed. Repeated calls reclaim objects in the order
+    /// they were retired (skipping guarded ones), as long as no other thread retires or reclaims
+    /// objects on this domain concurrently.
+    pub fn step(&self) -> bool {
+        self.check_confined();
+        self.release_quarantined(false);
+        if self.fifo.load(Ordering::SeqCst) {
+            let mut backlog = self.backlog.lock().unwrap();
+            return self.reclaim_backlog(&mut backlog, 1) == 1;
+        }
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        if steal.is_null() {
+            return false;
+        }
+
+        // The list is newest-first, so walk it to find the oldest unguarded object.
+        let guarded_ptrs = self.guarded_ptrs();
+        let mut prev = std::ptr::null_mut::<Retired>();
+        let mut victim = None;
+        let mut node = steal;
+        while !node.is_null() {
+            // Safety: All accessors only access the head, and the head is no longer pointing here.
+            let n = unsafe { &mut *node };
+            if !guarded_ptrs.contains(n.addr) {
+                victim = Some((prev, node));
+            }
+            prev = node;
+            node = n.next.load_mut();
+        }
+        let tail = prev;
+
+        let (mut head, mut tail) = (steal, tail);
+        if let Some((prev, victim)) = victim {
+            // Safety: we have exclusive access to the stolen list.
+            let n = unsafe { self.take_retired(victim) };
+            let next = n.next.load(Ordering::SeqCst);
+            if prev.is_null() {
+                head = next;
+            } else {
+                // Safety: we have exclusive access to the stolen list.
+                unsafe { (*prev).next.store_mut(next) };
+            }
+            if tail == victim {
+                tail = prev;
+            }
+            // Safety: same as in bulk_reclaim.
+            unsafe { self.dispose(n) };
+            self.retired.count.fetch_sub(1, Ordering::SeqCst);
+        }
+        if !head.is_null() {
+            self.splice_retired(head, tail);
+        }
+        victim.is_some()
+    }
+
+    /// Use `strategy` to find guarded objects in future reclamation scans of this domain.
+    ///
+    /// See the [`scan`] module for the strategies the crate provides. The default is
+    /// [`scan::Hashed`].
+    pub fn set_scan_strategy(&self, strategy: &'static dyn ScanStrategy) {
+        *self.scan.write().unwrap() = strategy;
+    }
+
+    fn guarded_ptrs(&self) -> Box<dyn HazardSet> {
+        // Pairs with the light barrier in HazPtrHolder::validate.
+        barrier::heavy();
+        let walk = self.hazptrs.walk();
+        let mut guarded_ptrs = Vec::new();
+        let mut hazards = Vec::new();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            let hazard = WatchedHazard::new(n);
+            if !hazard.ptr.is_null() {
+                guarded_ptrs.push(hazard.ptr);
+            }
+            hazards.push(hazard);
+            node = n.next.load(Ordering::SeqCst);
+        }
+        drop(walk);
+        self.watch(&hazards);
+        let strategy = *self.scan.read().unwrap();
+        strategy.build(guarded_ptrs)
+    }
+
+    /// Store `ptr` in `dst`, help any readers waiting in [`HazPtrHolder::load_bounded`] for
+    /// `dst`, and return the pointer that was there before.
+    pub fn swap_helping<T>(&self, dst: &AtomicPtr<T>, ptr: *mut T) -> *mut T {
+        let old = dst.swap(ptr, Ordering::SeqCst);
+        self.help_readers(dst);
+        old
+    }
+
+    /// Protect the current value of `src` for every reader waiting for it in
+    /// [`HazPtrHolder::load_bounded`].
+    ///
+    /// Writers to a source that readers load with `load_bounded` must call this after every
+    /// update, before retiring the value they replaced.
+    ///
+    /// This visits every hazard slot of the domain, but only while some reader is waiting.
+    pub fn help_readers<T>(&self, src: &AtomicPtr<T>) {
+        if self.help_requests.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let request = help_request(src);
+        let _walk = self.hazptrs.walk();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            if n.ptr.load(Ordering::SeqCst) == request {
+                n.answer(request, src.load(Ordering::SeqCst) as *mut u8);
+            }
+            node = n.next.load(Ordering::SeqCst);
+        }
+    }
+
+    /// Retire the boxed slice at `ptr`, to be freed once no reader protects its first element.
+    ///
+    /// Readers protect a slice by its data pointer, for example an `AtomicPtr<T>` to the first
+    /// element next to a length that stays fixed for the lifetime of the slice.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from [`Box::into_raw`] on a `Box<[T]>`, and must no longer be reachable
+    /// by new readers. If `T` is not `Send`, the domain must be confined to the calling thread.
+    pub unsafe fn retire_boxed_slice<T: 'static>(&self, ptr: *mut [T]) {
+        // Safety: by the safety contract of retire_boxed_slice.
+        let slice = unsafe { Box::from_raw(ptr) };
+        // A dyn Drop can only point at a sized value, so the slice gets an owner that is one.
+        let owner = Box::into_raw(Box::new(BoxedSlice(slice)));
+        self.retire_at(ptr as *mut T as *mut u8, owner, &deleters::drop_box);
+    }
+
+    /// Retire all of `objects`, which are freed with `deleter`, at once.
+    ///
+    /// The objects join the retired list in a single update, rather than one at a time, which
+    /// helps writers that unlink many nodes together, such as when clearing a list. Domains
+    /// that collect retired objects elsewhere first (in quarantine, per CPU, or for a single
+    /// writer) still take them one at a time.
+    ///
+    /// # Safety
+    ///
+    /// Every object must satisfy the requirements of [`HazPtrObject::retire`], with `deleter`
+    /// being a valid deleter for it.
+    pub unsafe fn retire_many<I>(&self, objects: I, deleter: &'static dyn Deleter)
+    where
+        I: IntoIterator<Item = *mut dyn Drop>,
+    {
+        self.check_confined();
+        let (mut head, mut tail): (*mut Retired, *mut Retired) =
+            (std::ptr::null_mut(), std::ptr::null_mut());
+        let mut count = 0;
+        let mut objects = objects.into_iter();
+        for ptr in &mut objects {
+            let Some(retired) = self.alloc_retired(ptr as *mut u8, ptr, deleter) else {
+                // Out of memory; let retire deal with it, and the rest go the same way.
+                self.retire(ptr, deleter);
+                break;
+            };
+            // Safety: retired was just allocated, and is not shared yet.
+            unsafe { (*retired).next.store_mut(head) };
+            if tail.is_null() {
+                tail = retired;
+            }
+            head = retired;
+            count += 1;
+        }
+        for ptr in objects {
+            self.retire(ptr, deleter);
+        }
+        // Safety: we built the chain above, and nobody else has seen it.
+        unsafe { self.push_retired_chain(head, tail, count) };
+    }
+
+    /// Put the `count` retired objects from `head` to `tail` on the retired list in a single
+    /// update, or one at a time if the domain collects them elsewhere first.
+    ///
+    /// # Safety
+    ///
+    /// The chain must be exclusively owned by the caller, and `count` long.
+    unsafe fn push_retired_chain(&self, head: *mut Retired, tail: *mut Retired, count: usize) {
+        if count == 0 {
+            return;
+        }
+        if self.quarantined.load(Ordering::SeqCst)
+            || self.single_writer.load(Ordering::Relaxed)
+            || self.batching.load(Ordering::Relaxed)
+        {
+            let mut node = head;
+            while !node.is_null() {
+                // Safety: the chain is ours, by the safety contract of push_retired_chain.
+                let next = unsafe { (*node).next.load_mut() };
+                self.push_retired(node);
+                node = next;
+            }
+            return;
+        }
+        let mut node = head;
+        while !node.is_null() {
+            // Safety: as above, and the retired objects are still valid.
+            let n = unsafe { &mut *node };
+            self.stats
+                .retired(std::mem::size_of_val(unsafe { &*n.ptr }));
+            node = n.next.load_mut();
+        }
+        // Increment the count _before_ we give anyone a chance to reclaim them.
+        self.retired.count.fetch_add(count, Ordering::SeqCst);
+        self.splice_retired(head, tail);
+        if !self.stepping.load(Ordering::SeqCst)
+            && self.retired.count.load(Ordering::SeqCst) >= self.reclaim_threshold()
+        {
+            self.bulk_reclaim(0, false);
+        }
+    }
+
+    // Put the list from head to tail back in front of the retired list.
+    fn splice_retired(&self, head: *mut Retired, tail: *mut Retired) {
+        let head_ptr = &self.retired.head;
+        let mut head_now = head_ptr.load(Ordering::SeqCst);
+        loop {
+            // Safety: we still have exclusive access to the list, which includes tail.
+            unsafe { (*tail).next.store_mut(head_now) };
+            match head_ptr.compare_exchange_weak(head_now, head, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(head) => {
+                    // Head has changed, try again with that as our next ptr.
+                    head_now = head
+                }
+            }
+        }
+    }
+
+    /// Only reclaim when retiring an object brings the number of objects waiting to be reclaimed
+    /// to `threshold`, rather than on every retirement.
+    ///
+    /// Explicit calls like [`HazPtrDomain::eager_reclaim`] are unaffected by the threshold.
+    pub fn set_reclaim_threshold(&self, threshold: ReclaimThreshold) {
+        *self.threshold.write().unwrap() = threshold;
+    }
+
+    // The number of retired objects at which retiring triggers reclamation.
+    fn reclaim_threshold(&self) -> usize {
+        let threshold = *self.threshold.read().unwrap();
+        threshold
+            .fixed
+            .max(threshold.per_hazard.saturating_mul(self.stats.hazards()))
+    }
+
+    /// Set the budgets that [`HazPtrDomain::backpressure`] compares the backlog of retired
+    /// objects against. Both are [`RetireBudget::UNLIMITED`] by default.
+    ///
+    /// The budgets are only advisory: the domain never refuses to retire an object.
+    pub fn set_retire_budgets(&self, elevated: RetireBudget, critical: RetireBudget) {
+        *self.budgets.write().unwrap() = (elevated, critical);
+    }
+
+    /// How the current backlog of retired objects compares to the budgets set with
+    /// [`HazPtrDomain::set_retire_budgets`].
+    pub fn backpressure(&self) -> Backpressure {
+        let (objects, bytes) = self.stats.backlog();
+        let (elevated, critical) = *self.budgets.read().unwrap();
+        if critical.exceeded_by(objects, bytes) {
+            Backpressure::Critical
+        } else if elevated.exceeded_by(objects, bytes) {
+            Backpressure::Elevated
+        } else {
+            Backpressure::Ok
+        }
+    }
+
+    /// Let the domain free unused hazard slots according to `policy`, or never if `None`.
+    ///
+    /// Slots are only freed during reclamation scans, and only once no other thread is walking
+    /// the list of slots.
+    pub fn set_shrink_policy(&self, policy: Option<ShrinkPolicy>) {
+        let (high, low) = match policy {
+            Some(ShrinkPolicy {
+                high_water,
+                low_water,
+            }) => {
+                assert!(
+                    low_water <= high_water,
+                    "low water mark above high water mark"
+                );
+                (high_water, low_water)
+            }
+            None => (usize::MAX, usize::MAX),
+        };
+        // Another thread may briefly see a mix of the two policies, which is harmless.
+        self.hazptrs.shrink_low.store(low, Ordering::SeqCst);
+        self.hazptrs.shrink_high.store(high, Ordering::SeqCst);
+    }
+
+    fn maybe_shrink(&self) {
+        let high = self.hazptrs.shrink_high.load(Ordering::SeqCst);
+        let low = self.hazptrs.shrink_low.load(Ordering::SeqCst);
+        let mut unlinked = match self.hazptrs.unlinked.try_lock() {
+            Ok(unlinked) => unlinked,
+            // Someone else is already on it.
+            Err(_) => return,
+        };
+
+        if high != usize::MAX {
+            // Safety: we hold the unlinked lock.
+            let inactive = unsafe { self.unlink_inactive(usize::MAX, &mut unlinked) };
+            if inactive > high {
+                // Safety: we hold the unlinked lock.
+                unsafe { self.unlink_inactive(low, &mut unlinked) };
+            }
+        }
+
+        self.free_unlinked(&mut unlinked);
+    }
+
+    /// Free every hazard slot that is not in use right now, whatever the shrink policy.
+    ///
+    /// Slots in the calling thread's cache are released first. Slots that another thread may
+    /// still be looking at are freed by a later call, or by a later reclamation scan.
+    pub fn shrink_to_fit(&self) {
+        self.uncache_hazptrs();
+        let mut unlinked = self.hazptrs.unlinked.lock().unwrap();
+        // Safety: we hold the unlinked lock.
+        unsafe { self.unlink_inactive(0, &mut unlinked) };
+        self.free_unlinked(&mut unlinked);
+    }
+
+    /// Free the unlinked HazPtrs, unless someone is walking the list and may still reach them.
+    fn free_unlinked(&self, unlinked: &mut Vec<UnlinkedHazPtr>) {
+        if !unlinked.is_empty() && self.hazptrs.walkers.load(Ordering::SeqCst) == 0 {
+            // Anyone who starts walking the list from here on can't reach the unlinked HazPtrs.
+            for UnlinkedHazPtr(hazptr) in unlinked.drain(..) {
+                // Safety: hazptr was allocated by our allocator, and nobody can access it anymore.
+                unsafe {
+                    self.allocator()
+                        .dealloc(hazptr as *mut u8, Layout::new::<HazPtr>())
+                };
+            }
+        }
+    }
+
+    /// Unlink all but `keep` inactive HazPtrs, and return how many inactive ones were found.
+    ///
+    /// # Safety
+    ///
+    /// `unlinked` must be the locked contents of `self.hazptrs.unlinked`.
+    unsafe fn unlink_inactive(&self, keep: usize, unlinked: &mut Vec<UnlinkedHazPtr>) -> usize {
+        let head_ptr = &self.hazptrs.head;
+        let mut inactive = 0;
+        let mut prev = std::ptr::null_mut::<HazPtr>();
+        let mut node = head_ptr.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: only the holder of the unlinked lock (us) de-allocates HazPtrs, and we
+            // never de-allocate linked ones.
+            let n = unsafe { &*node };
+            let next = n.next.load(Ordering::SeqCst);
+            if !n.active.load(Ordering::SeqCst) {
+                inactive += 1;
+                if inactive > keep
+                    && n.active
+                        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_ok()
+                {
+                    // It's ours, and we'll never give it out again.
+                    self.unlink(prev, node, next);
+                    unlinked.push(UnlinkedHazPtr(node));
+                    node = next;
+                    continue;
+                }
+            }
+            prev = node;
+            node = next;
+        }
+        inactive
+    }
+
+    // Only called with the unlinked lock held, so prev and node can't be unlinked concurrently.
+    fn unlink(&self, prev: *mut HazPtr, node: *mut HazPtr, next: *mut HazPtr) {
+        if !prev.is_null() {
+            // Safety: prev is linked, so not de-allocated. Only the head pointer is modified
+            // concurrently, so nobody else is writing prev.next.
+            unsafe { &*prev }.next.store(next, Ordering::SeqCst);
+            return;
+        }
+
+        let head_ptr = &self.hazptrs.head;
+        if head_ptr
+            .compare_exchange(node, next, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            return;
+        }
+        // New HazPtrs were pushed in front of node, so find its new predecessor among them.
+        let mut prev = head_ptr.load(Ordering::SeqCst);
+        loop {
+            // Safety: prev is linked, so not de-allocated.
+            let p = unsafe { &*prev };
+            let p_next = p.next.load(Ordering::SeqCst);
+            if p_next == node {
+                p.next.store(next, Ordering::SeqCst);
+                return;
+            }
+            prev = p_next;
+        }
+    }
+
+    fn bulk_reclaim(&self, mut reclaimed: usize, block: bool) -> usize {
+        debug_span!("reclaim", domain = (self as *const Self).addr(), block);
+        self.maybe_shrink();
+        self.release_quarantined(block);
+        self.flush_batches();
+        if self.fifo.load(Ordering::SeqCst) {
+            return reclaimed + self.reclaim_fifo(block);
+        }
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        if steal.is_null() {
+            // Nothing to reclaim!
+            return reclaimed;
+        }
+
+        let start = Instant::now();
+        // Find all guarded addresses.
+        let guarded_ptrs = self.guarded_ptrs();
+
+        // Safety: the stolen list is no longer reachable by anyone else.
+        let (reclaimed_now, remaining, tail) =
+            unsafe { self.reclaim_unguarded(steal, &*guarded_ptrs, usize::MAX) };
+        self.stats.reclaim_pass(start.elapsed());
+
+        let _left = self
+            .retired
+            .count
+            .fetch_sub(reclaimed_now, Ordering::SeqCst)
+            - reclaimed_now;
+        debug_event!(reclaimed = reclaimed_now, left = _left, "reclaim pass");
+        reclaimed += reclaimed_now;
+
+        let tail = if let Some(tail) = tail {
+            assert!(!remaining.is_null());
+            tail
+        } else {
+            assert!(remaining.is_null());
+            return reclaimed;
+        };
+
+        self.splice_retired(remaining, tail);
+
+        if !remaining.is_null() && block {
+            // Caller wants to reclaim _everything_, but some were left, so try again.
+            std::thread::yield_now();
+            // NOTE: Allows tail recursion by passing down reclaimed
+            return self.bulk_reclaim(reclaimed, true);
+        }
+
+        reclaimed
+    }
+
+    /// Reclaim the objects in `list` that aren't in `guarded_ptrs`, but no more than `limit`.
+    ///
+    /// Returns the number of objects reclaimed, and the head and tail of the list of remaining
+    /// objects, in the same order as in `list`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to `list`.
+    unsafe fn reclaim_unguarded(
+        &self,
+        list: *mut Retired,
+        guarded_ptrs: &dyn HazardSet,
+        limit: usize,
+    ) -> (usize, *mut Retired, Option<*mut Retired>) {
+        // Reclaim any retired objects that aren't guarded
+        let mut node = list;
+        let mut remaining = std::ptr::null_mut();
+        let mut tail: Option<*mut Retired> = None;
+        let mut reclaimed_now = 0;
+        while !node.is_null() {
+            // Safety: All accessors only access the head, and the head is no longer pointing here.
+            let n = unsafe { &mut *node };
+            let this = node;
+            node = n.next.load_mut();
+
+            if reclaimed_now == limit || guarded_ptrs.contains(n.addr) {
+                // Not safe to reclaim -- still guarded (or out of budget).
+                // Keep it, preserving the order in which objects were retired.
+                n.next.store_mut(std::ptr::null_mut());
+                let n = this;
+                match tail {
+                    // Safety: we have exclusive access to remaining, which includes tail.
+                    Some(tail) => unsafe { (*tail).next.store_mut(n) },
+                    None => remaining = n,
+                }
+                tail = Some(n);
+            } else {
+                // Safety: we own this node exclusively, and it came from alloc_retired.
+                let n = unsafe { self.take_retired(this) };
+                // No longer guarded -- reclaim using deleter.
+                // Safety:
+                // - `n.ptr` has not yet been dropped and will not be dropped again (we have removed it from `remaining`)
+                // - `n.ptr` has been allocated the corresponding allocation method corresponding to `n.deleter`
+                //   as per the safety guarantees of calling `retire`.
+                unsafe { self.dispose(n) };
+                reclaimed_now += 1;
+            }
+        }
+        (reclaimed_now, remaining, tail)
+    }
+
+    /// Run deleters strictly in the order objects were retired, or in no particular order.
+    ///
+    /// While enabled, an object is only reclaimed once every object retired before it has been,
+    /// so a single object that stays guarded holds back all objects retired after it. Deleters
+    /// also run one at a time, never concurrently. A deleter that retires further objects is
+    /// fine: those are reclaimed later, after the objects already waiting.
+    ///
+    /// Disabling the ordering again releases any objects held back by it into the normal
+    /// retired list.
+    pub fn set_fifo_reclamation(&self, enabled: bool) {
+        let mut backlog = self.backlog.lock().unwrap();
+        self.fifo.store(enabled, Ordering::SeqCst);
+        if !enabled && !backlog.head.is_null() {
+            let head = Self::reverse(backlog.head);
+            self.splice_retired(head, backlog.head);
+            backlog.head = std::ptr::null_mut();
+            backlog.tail = std::ptr::null_mut();
+        }
+    }
+
+    /// Hold every object retired from now on for at least `delay` before considering it for
+    /// reclamation, or stop doing so with `None`.
+    ///
+    /// Objects retired moments ago are the ones readers are most likely still protecting, so a
+    /// quarantine keeps reclamation scans from visiting them over and over. Waiting objects are
+    /// kept in a timer wheel, so releasing the ones whose delay has passed takes time
+    /// proportional to the elapsed time and the released objects, not to the number of objects
+    /// waiting. Released objects keep the order they were retired in, also for
+    /// [`HazPtrDomain::set_fifo_reclamation`].
+    ///
+    /// Quarantined objects are released by subsequent retires and reclamation calls, such as
+    /// [`HazPtrDomain::eager_reclaim`] or [`HazPtrDomain::step`]. A blocking
+    /// `eager_reclaim(true)` releases all of them regardless of their delay, as does changing or
+    /// lifting the quarantine.
+    pub fn set_quarantine(&self, delay: Option<Duration>) {
+        let mut quarantine = self.quarantine.lock().unwrap();
+        self.quarantined.store(delay.is_some(), Ordering::SeqCst);
+        if let Some(mut wheel) = std::mem::replace(&mut *quarantine, delay.map(TimerWheel::new)) {
+            self.splice_released(wheel.flush());
+        }
+    }
+
+    /// The delay set with [`HazPtrDomain::set_quarantine`], if any.
+    pub fn quarantine(&self) -> Option<Duration> {
+        self.quarantine
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(TimerWheel::delay)
+    }
+
+    // Move quarantined objects whose delay has passed (or all of them, if flush) to the retired
+    // list.
+    fn release_quarantined(&self, flush: bool) {
+        if !self.quarantined.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut quarantine = self.quarantine.lock().unwrap();
+        if let Some(wheel) = quarantine.as_mut() {
+            let released = if flush {
+                wheel.flush()
+            } else {
+                wheel.advance(Instant::now())
+            };
+            self.splice_released(released);
+        }
+    }
+
+    fn splice_released(&self, released: wheel::Released) {
+        if released.count != 0 {
+            self.retired
+                .count
+                .fetch_add(released.count, Ordering::SeqCst);
+            self.splice_retired(released.head, released.tail);
+        }
+    }
+
+    /// Collect retired objects in per-CPU batches before handing them to the shared retired
+    /// list, or stop doing so.
+    ///
+    /// Domains use 64 batches, unless configured otherwise with
+    /// [`HazPtrDomainBuilder::retired_shards`].
+    ///
+    /// Without batching, every retirement updates the head of the domain's retired list, which
+    /// becomes a point of contention once many cores retire objects at the same time. With
+    /// batching, each retirement only touches the batch of the CPU it happens on (of the thread,
+    /// unless the `cpu-local` feature is enabled on Linux), and a batch joins the retired list
+    /// once it holds enough objects, in a single update. Reclamation starts by merging all
+    /// batches, so [`HazPtrDomain::eager_reclaim`] still considers every retired object.
+    ///
+    /// In return, objects may wait in a batch until enough others are retired on the same CPU,
+    /// or until the next reclamation. A thread that exits hands the batches over to the retired
+    /// list, provided the domain has an id (see [`HazPtrDomain::id`]), as the domains of
+    /// [`HazPtrObjectWrapper`]s do.
+    pub fn set_cpu_batching(&self, enabled: bool) {
+        self.batching.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            for batch in &self.batches {
+                self.flush_batch(batch);
+            }
+        }
+    }
+
+    fn push_batch(&self, retired: *mut Retired) {
+        if let Some(id) = self.registered_id() {
+            let _ = BATCHED_DOMAINS.try_with(|batched| {
+                let mut batched = batched.borrow_mut();
+                if !batched.0.contains(&id) {
+                    batched.0.push(id);
+                }
+            });
+        }
+        let batch = &self.batches[current_cpu() % self.shards.load(Ordering::Relaxed)];
+        let mut head = batch.head.load(Ordering::SeqCst);
+        loop {
+            // Safety: retired was never shared, so &mut is ok.
+            unsafe { (*retired).next.store_mut(head) };
+            match batch.head.compare_exchange_weak(
+                head,
+                retired,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(head_now) => head = head_now,
+            }
+        }
+        // If batching was disabled in the meantime, whoever disabled it may have missed us.
+        let full = batch.count.fetch_add(1, Ordering::SeqCst) + 1 >= BATCH_SIZE
+            || !self.batching.load(Ordering::SeqCst);
+        if full && self.flush_batch(batch) && !self.stepping.load(Ordering::SeqCst) {
+            self.bulk_reclaim(0, false);
+        }
+    }
+
+    /// Hand the batches over for a thread that is exiting, and reclaim if that takes the retired
+    /// list past the threshold.
+    fn flush_thread_batches(&self) {
+        self.flush_batches();
+        if !self.stepping.load(Ordering::SeqCst)
+            && self.retired.count.load(Ordering::SeqCst) >= self.reclaim_threshold()
+        {
+            self.bulk_reclaim(0, false);
+        }
+    }
+
+    // Move the objects in every batch to the retired list.
+    fn flush_batches(&self) {
+        if self.batching.load(Ordering::SeqCst) {
+            for batch in &self.batches {
+                self.flush_batch(batch);
+            }
+        }
+    }
+
+    // Move the objects in batch to the retired list, and return whether there were any.
+    fn flush_batch(&self, batch: &CpuBatch) -> bool {
+        let head = batch.head.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        if head.is_null() {
+            return false;
+        }
+        let mut count = 1;
+        let mut tail = head;
+        loop {
+            // Safety: we took the batch, so we have exclusive access to its objects.
+            let next = unsafe { &mut *tail }.next.load_mut();
+            if next.is_null() {
+                break;
+            }
+            tail = next;
+            count += 1;
+        }
+        batch.count.fetch_sub(count, Ordering::SeqCst);
+        self.retired.count.fetch_add(count, Ordering::SeqCst);
+        self.splice_retired(head, tail);
+        true
+    }
+
+    fn reclaim_fifo(&self, block: bool) -> usize {
+        let mut reclaimed = 0;
+        loop {
+            // Whoever holds the lock reclaims on everyone's behalf. That includes this thread,
+            // if it is retiring from within a deleter.
+            let mut backlog = match self.backlog.try_lock() {
+                Ok(backlog) => backlog,
+                Err(std::sync::TryLockError::WouldBlock) if block => {
+                    std::thread::yield_now();
+                    continue;
+                }
+                Err(std::sync::TryLockError::WouldBlock) => break reclaimed,
+                Err(std::sync::TryLockError::Poisoned(e)) => panic!("{}", e),
+            };
+            reclaimed += self.reclaim_backlog(&mut backlog, usize::MAX);
+            if backlog.head.is_null() || !block {
+                break reclaimed;
+            }
+            drop(backlog);
+            std::thread::yield_now();
+        }
+    }
+
+    /// Move newly retired objects to the end of the backlog, then reclaim objects from its front
+    /// until reaching a guarded one, or having reclaimed `limit` objects.
+    fn reclaim_backlog(&self, backlog: &mut FifoBacklog, limit: usize) -> usize {
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        if !steal.is_null() {
+            // The retired list is newest-first.
+            let head = Self::reverse(steal);
+            if backlog.tail.is_null() {
+                backlog.head = head;
+            } else {
+                // Safety: we hold the backlog lock.
+                unsafe { (*backlog.tail).next.store_mut(head) };
+            }
+            backlog.tail = steal;
+        }
+        if backlog.head.is_null() {
+            return 0;
+        }
+
+        let start = Instant::now();
+        let guarded_ptrs = self.guarded_ptrs();
+        let mut reclaimed = 0;
+        while !backlog.head.is_null() && reclaimed < limit {
+            // Safety: we hold the backlog lock.
+            let n = unsafe { &*backlog.head };
+            if guarded_ptrs.contains(n.addr) {
+                break;
+            }
+            // Safety: we own the front of the backlog, and it came from alloc_retired.
+            let n = unsafe { self.take_retired(backlog.head) };
+            backlog.head = n.next.load(Ordering::SeqCst);
+            // Safety: same as in bulk_reclaim.
+            unsafe { self.dispose(n) };
+            self.retired.count.fetch_sub(1, Ordering::SeqCst);
+            reclaimed += 1;
+        }
+        if backlog.head.is_null() {
+            backlog.tail = std::ptr::null_mut();
+        }
+        self.stats.reclaim_pass(start.elapsed());
+        reclaimed
+    }
+
+    /// Reverse a list of retired objects that the caller has exclusive access to, and return
+    /// the new head.
+    fn reverse(mut node: *mut Retired) -> *mut Retired {
+        let mut reversed = std::ptr::null_mut();
+        while !node.is_null() {
+            // Safety: the caller has exclusive access to the list.
+            let n = unsafe { &mut *node };
+            let next = n.next.load_mut();
+            n.next.store_mut(reversed);
+            reversed = node;
+            node = next;
+        }
+        reversed
+    }
+
+    /// The last node of a non-empty list of retired objects that the caller has exclusive
+    /// access to.
+    fn list_tail(mut node: *mut Retired) -> *mut Retired {
+        loop {
+            // Safety: the caller has exclusive access to the list.
+            let next = unsafe { &mut *node }.next.load_mut();
+            if next.is_null() {
+                break node;
+            }
+            node = next;
+        }
+    }
+
+    /// Confine this domain to the calling thread: from now on, acquiring hazard slots, retiring
+    /// objects, and reclaiming them on any other thread panics.
+    ///
+    /// Since every deleter of a confined domain runs on the confining thread, objects that
+    /// aren't `Send` (such as ones holding an `Rc`) can be retired on it. That is useful for
+    /// single-threaded structures that still need deferred destruction, for example because
+    /// their methods are re-entrant.
+    ///
+    /// Returns `false`, and changes nothing, if the domain is already confined to a thread.
+    /// Objects that were retired before the domain was confined may still be reclaimed on
+    /// another thread, so confine the domain before first using it.
+    pub fn confine_to_current_thread(&self) -> bool {
+        self.confined
+            .compare_exchange(
+                usize::MAX,
+                thread_index(),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .is_ok()
+    }
+
+    fn check_confined(&self) {
+        let confined = self.confined.load(Ordering::Relaxed);
+        assert!(
+            confined == usize::MAX || confined == thread_index(),
+            "domain is confined to another thread"
+        );
+    }
+
+    /// Declare the calling thread to be the only thread that will retire objects on this domain.
+    ///
+    /// Objects retired from then on are kept in a list private to that thread rather than the
+    /// shared retired list, which saves the atomic read-modify-write operations needed to
+    /// coordinate between writers. In turn, they are only reclaimed by that thread: whenever
+    /// it retires an object or calls [`HazPtrDomain::eager_reclaim`]. Debug builds assert that
+    /// no other thread retires objects.
+    ///
+    /// # Safety
+    ///
+    /// No other thread may retire objects on this domain after this call, and this must only be
+    /// called once.
+    pub unsafe fn set_single_writer(&self) {
+        self.writer.store(thread_index(), Ordering::SeqCst);
+        self.single_writer.store(true, Ordering::SeqCst);
+    }
+
+    /// The highest number of hazards, retired objects and bytes, and the longest reclamation
+    /// pass this domain has seen, both all-time and within the current window.
+    pub fn high_water(&self) -> HighWater {
+        self.stats.high_water()
+    }
+
+    /// How long the objects reclaimed so far had been waiting since they were retired.
+    pub fn reclaim_ages(&self) -> AgeHistogram {
+        self.stats.ages()
+    }
+
+    /// How long the oldest object that is still waiting to be reclaimed has been retired, or
+    /// `None` if there is none.
+    ///
+    /// This walks all retired objects, so it is meant for occasional monitoring rather than
+    /// hot paths. Objects in a single-writer domain's private list are only included when
+    /// called from the writer thread.
+    pub fn oldest_retired_age(&self) -> Option<Duration> {
+        let mut oldest: Option<Instant> = None;
+        let mut visit = |n: &Retired| {
+            if oldest.is_none_or(|o| n.retired_at < o) {
+                oldest = Some(n.retired_at);
+            }
+        };
+
+        self.flush_batches();
+        // Take the list so that nobody reclaims from it while we look.
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        let mut tail = std::ptr::null_mut();
+        let mut node = steal;
+        while !node.is_null() {
+            // Safety: we have exclusive access to the stolen list.
+            let n = unsafe { &*node };
+            visit(n);
+            tail = node;
+            node = n.next.load(Ordering::SeqCst);
+        }
+        if !steal.is_null() {
+            self.splice_retired(steal, tail);
+        }
+
+        // The backlog is oldest-first.
+        let backlog = self.backlog.lock().unwrap();
+        // Safety: we hold the backlog lock.
+        if let Some(n) = unsafe { backlog.head.as_ref() } {
+            visit(n);
+        }
+        drop(backlog);
+
+        if let Some(wheel) = self.quarantine.lock().unwrap().as_ref() {
+            wheel.for_each(&mut visit);
+        }
+
+        if self.is_writer() {
+            // Safety: only the writer thread accesses the private list.
+            let mut node = unsafe { &*self.private.0.get() }.head;
+            while !node.is_null() {
+                // Safety: the private list is ours alone.
+                let n = unsafe { &*node };
+                visit(n);
+                node = n.next.load(Ordering::SeqCst);
+            }
+        }
+
+        oldest.map(|o| o.elapsed())
+    }
+
+    /// Start a new high-water window, and return the peaks of the one that just ended.
+    ///
+    /// The new window starts out at the current number of hazards and retired objects, so its
+    /// peaks are never below what is live at the time.
+    pub fn reset_high_water_window(&self) -> Peaks {
+        self.stats.reset_window()
+    }
+
+    fn is_writer(&self) -> bool {
+        self.single_writer.load(Ordering::Relaxed)
+            && self.writer.load(Ordering::Relaxed) == thread_index()
+    }
+
+    fn push_private(&self, retired: *mut Retired) {
+        debug_assert!(
+            self.is_writer(),
+            "retired on a single-writer domain from another thread"
+        );
+        // Safety: only the writer thread accesses the private list.
+        let private = unsafe { &mut *self.private.0.get() };
+        // Safety: retired was never shared, so &mut is ok.
+        unsafe { (*retired).next.store_mut(private.head) };
+        private.head = retired;
+        private.count += 1;
+        // Safety: the retired object is still valid.
+        self.stats
+            .retired(std::mem::size_of_val(unsafe { &*(*retired).ptr }));
+
+        if !self.stepping.load(Ordering::SeqCst) && private.count >= self.reclaim_threshold() {
+            self.reclaim_private(false, usize::MAX);
+        }
+    }
+
+    fn reclaim_private(&self, block: bool, limit: usize) -> usize {
+        if self.fifo.load(Ordering::SeqCst) {
+            self.hand_over_private();
+            return self.reclaim_fifo(block);
+        }
+        let mut reclaimed = 0;
+        loop {
+            // Safety: only the writer thread accesses the private list.
+            let private = unsafe { &mut *self.private.0.get() };
+            if private.head.is_null() {
+                break reclaimed;
+            }
+            let start = Instant::now();
+            let guarded_ptrs = self.guarded_ptrs();
+            // Safety: the private list is ours alone.
+            let (n, remaining, _) =
+                unsafe { self.reclaim_unguarded(private.head, &*guarded_ptrs, limit - reclaimed) };
+            self.stats.reclaim_pass(start.elapsed());
+            private.head = remaining;
+            private.count -= n;
+            reclaimed += n;
+            if remaining.is_null() || !block || reclaimed == limit {
+                break reclaimed;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    // Hand the private list over to the FIFO backlog, which keeps it in order.
+    fn hand_over_private(&self) {
+        // Safety: only the writer thread accesses the private list.
+        let private = unsafe { &mut *self.private.0.get() };
+        if private.head.is_null() {
+            return;
+        }
+        let tail = Self::list_tail(private.head);
+        self.retired
+            .count
+            .fetch_add(private.count, Ordering::SeqCst);
+        self.splice_retired(private.head, tail);
+        private.head = std::ptr::null_mut();
+        private.count = 0;
+    }
+}
+
+impl Default for HazPtrDomain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HazPtrDomain {
+    /// Run the deleter of every retired object, wherever it is waiting, and free the hazard
+    /// slots. Leaves the domain empty, so that calling this again does nothing.
+    fn reclaim_all(&mut self) -> Teardown {
+        // This thread's cached slots are freed with the rest.
+        self.uncache_hazptrs();
+
+        let mut nodes = Vec::new();
+        let mut collect = |mut node: *mut Retired| {
+            while !node.is_null() {
+                nodes.push(node);
+                // Safety: we have exclusive access to the domain, and so to its lists.
+                node = unsafe { (*node).next.load_mut() };
+            }
+        };
+        collect(self.retired.head.load_mut());
+        self.retired.head.store_mut(std::ptr::null_mut());
+        self.retired.count.store_mut(0);
+        let private = self.private.0.get_mut();
+        collect(std::mem::replace(&mut private.head, std::ptr::null_mut()));
+        private.count = 0;
+        let backlog = self.backlog.get_mut().unwrap();
+        collect(std::mem::replace(&mut backlog.head, std::ptr::null_mut()));
+        backlog.tail = std::ptr::null_mut();
+        if let Some(wheel) = self.quarantine.get_mut().unwrap() {
+            collect(wheel.flush().head);
+        }
+        for batch in &mut self.batches {
+            collect(batch.head.load_mut());
+            batch.head.store_mut(std::ptr::null_mut());
+            batch.count.store_mut(0);
+        }
+
+        let me = std::thread::current().id();
+        let mut teardown = Teardown::default();
+        for node in nodes {
+            // Safety: node came from alloc_retired, and we removed it from its list above.
+            let n = unsafe { self.take_retired(node) };
+            match n.affinity {
+                Some(thread) if thread != me => teardown.leaked += 1,
+                _ => {
+                    // Safety: nothing can guard n.ptr anymore, and it was only retired once.
+                    unsafe { self.delete(n.ptr, n.deleter) };
+                    teardown.reclaimed += 1;
+                }
+            }
+        }
+        for q in std::mem::take(self.affine.get_mut().unwrap()) {
+            if q.thread == me {
+                // Safety: q was no longer guarded when it was queued, and is only queued once.
+                unsafe { self.delete(q.ptr, q.deleter) };
+                teardown.reclaimed += 1;
+            } else {
+                teardown.leaked += 1;
+            }
+        }
+
+        // Other threads may still keep slots cached, which they hand back when they exit, unless
+        // they find the domain unregistered.
+        self.unregister();
+        let mut node = self.hazptrs.head.load_mut();
+        self.hazptrs.head.store_mut(std::ptr::null_mut());
+        let unlinked = self.hazptrs.unlinked.get_mut().unwrap();
+        let unlinked = unlinked.drain(..).map(|UnlinkedHazPtr(hazptr)| hazptr);
+        let mut hazptrs: Vec<_> = unlinked.collect();
+        while !node.is_null() {
+            hazptrs.push(node);
+            // Safety: we have exclusive access to the list.
+            node = unsafe { (*node).next.load_mut() };
+        }
+        for hazptr in hazptrs {
+            // Safety: as above.
+            debug_assert!(
+                unsafe { (*hazptr).ptr.load_mut() }.is_null(),
+                "hazard still protecting an object of a dropped domain"
+            );
+            // Safety: hazptr was allocated by our allocator, and nobody can access it anymore.
+            unsafe {
+                self.allocator()
+                    .dealloc(hazptr as *mut u8, Layout::new::<HazPtr>())
+            };
+        }
+        teardown
+    }
+}
+
+impl Drop for HazPtrDomain {
+    fn drop(&mut self) {
+        self.reclaim_all();
+    }
+}
+
+struct HazPtrs {
+    head: AtomicPtr<HazPtr>,
+    // The number of threads currently walking the list. HazPtrs that have been unlinked from the
+    // list can only be de-allocated once this drops to zero.
+    walkers: AtomicUsize,
+    // Walks are also counted by the parity of the generation they started in, so that
+    // wait_for_walks can wait for the walks of one generation while new ones start in the next.
+    generation: AtomicUsize,
+    by_generation: [AtomicUsize; 2],
+    // Only one thread at a time moves on to the next generation.
+    generation_lock: Mutex<()>,
+    // HazPtrs that have been unlinked, but may still be visible to walkers.
+    // Also serves as the lock that makes sure only one thread unlinks HazPtrs at a time.
+    unlinked: Mutex<Vec<UnlinkedHazPtr>>,
+    // See ShrinkPolicy. usize::MAX means never shrink.
+    shrink_high: AtomicUsize,
+    shrink_low: AtomicUsize,
+}
+
+impl HazPtrs {
+    const_fn! {
+        const fn new() -> Self {
+            Self {
+                head: AtomicPtr::new(std::ptr::null_mut()),
+                walkers: AtomicUsize::new(0),
+                generation: AtomicUsize::new(0),
+                by_generation: [AtomicUsize::new(0), AtomicUsize::new(0)],
+                generation_lock: Mutex::new(()),
+                unlinked: Mutex::new(Vec::new()),
+                shrink_high: AtomicUsize::new(usize::MAX),
+                shrink_low: AtomicUsize::new(usize::MAX),
+            }
+        }
+    }
+
+    /// Announce that the caller is about to walk the list, until the returned guard is dropped.
+    ///
+    /// HazPtrs that are linked into the list when the walk starts stay allocated until it ends,
+    /// and active HazPtrs are never de-allocated.
+    fn walk(&self) -> Walk<'_> {
+        self.walkers.fetch_add(1, Ordering::SeqCst);
+        let parity = loop {
+            let generation = self.generation.load(Ordering::SeqCst);
+            let parity = generation % 2;
+            self.by_generation[parity].fetch_add(1, Ordering::SeqCst);
+            // If the generation moved on in between, a wait_for_walks may already have found
+            // this one's count at zero, so count the walk in the new generation instead.
+            if self.generation.load(Ordering::SeqCst) == generation {
+                break parity;
+            }
+            self.by_generation[parity].fetch_sub(1, Ordering::SeqCst);
+        };
+        Walk {
+            hazptrs: self,
+            parity,
+        }
+    }
+
+    /// Wait until every walk that started before the call has ended.
+    ///
+    /// New walks start in the next generation, so they can't keep this waiting. Must not be
+    /// called while walking the list.
+    fn wait_for_walks(&self) {
+        let _lock = self.generation_lock.lock().unwrap();
+        let generation = self.generation.load(Ordering::SeqCst);
+        // Walks still counted in the previous generation started before this one did.
+        while self.by_generation[(generation + 1) % 2].load(Ordering::SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+        self.generation.store(generation + 1, Ordering::SeqCst);
+        while self.by_generation[generation % 2].load(Ordering::SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+struct Walk<'a> {
+    hazptrs: &'a HazPtrs,
+    parity: usize,
+}
+
+impl Drop for Walk<'_> {
+    fn drop(&mut self) {
+        self.hazptrs.by_generation[self.parity].fetch_sub(1, Ordering::SeqCst);
+        self.hazptrs.walkers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A hazard as a scan found it, for the watchdog to look at once the scan is done with the
+/// hazard list, and the record may be gone.
+#[derive(Clone, Copy)]
+struct WatchedHazard {
+    slot: usize,
+    owner: usize,
+    ptr: *mut u8,
+}
+
+impl WatchedHazard {
+    fn new(hazptr: &HazPtr) -> Self {
+        Self {
+            slot: (hazptr as *const HazPtr).addr(),
+            owner: hazptr.owner.load(Ordering::SeqCst),
+            ptr: hazptr.ptr.load(Ordering::SeqCst),
+        }
+    }
+}
+
+struct UnlinkedHazPtr(*mut HazPtr);
+
+// Safety: an unlinked HazPtr is owned by whoever holds HazPtrs::unlinked.
+unsafe impl Send for UnlinkedHazPtr {}
+
+struct Retired {
+    // The address readers protect the object by; usually that of ptr.
+    addr: *mut u8,
+    ptr: *mut dyn Drop,
+    deleter: &'static dyn Deleter,
+    // The thread the deleter has to run on, if any.
+    affinity: Option<ThreadId>,
+    retired_at: Instant,
+    next: AtomicPtr<Retired>,
+}
+
+// A reclaimable object waiting for its deleter to be run on `thread`.
+struct Queued {
+    thread: ThreadId,
+    ptr: *mut dyn Drop,
+    deleter: &'static dyn Deleter,
+}
+
+// Safety: the object is not accessed until its deleter runs, on the thread it is queued for.
+unsafe impl Send for Queued {}
+
+/// Owns a slice retired with [`HazPtrDomain::retire_boxed_slice`].
+struct BoxedSlice<T>(Box<[T]>);
+
+impl<T> Drop for BoxedSlice<T> {
+    fn drop(&mut self) {}
+}
+
+struct RetiredList {
+    head: AtomicPtr<Retired>,
+    count: AtomicUsize,
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn feels_good() {
+        let drops_42 = Arc::new(AtomicUsize::new(0));
+
+        let x = AtomicPtr::new(Box::into_raw(Box::new(
+            HazPtrObjectWrapper::with_default_domain((42, CountDrops(Arc::clone(&drops_42)))),
+        )));
+
+        // As a reader:
+        let mut h = HazPtrHolder::default();
+
+        // Safety:
+        //
+        //  1. AtomicPtr points to a Box, so is always valid.
+        //  2. Writers to AtomicPtr use HazPtrObject::retire.
+        let my_x = unsafe { h.load(&x) }.expect("not null");
+        // valid:
+        assert_eq!(my_x.0, 42);
+        h.reset();
+        // invalid:
+        // let _: i32 = my_x.0;
+
+        let my_x = unsafe { h.load(&x) }.expect("not null");
+        // valid:
+        assert_eq!(my_x.0, 42);
+        drop(h);
+        // invalid:
+        // let _: i32 = my_x.0;
+
+        let mut h = HazPtrHolder::default();
+        let my_x = unsafe { h.load(&x) }.expect("not null");
+
+        let mut h_tmp = HazPtrHolder::default();
+        let _ = unsafe { h_tmp.load(&x) }.expect("not null");
+        drop(h_tmp);
+
+        // As a writer:
+        let drops_9001 = Arc::new(AtomicUsize::new(0));
+        let old = x.swap(
+            Box::into_raw(Box::new(HazPtrObjectWrapper::with_default_domain((
+                9001,
+                CountDrops(Arc::clone(&drops_9001)),
+            )))),
+            std::sync::atomic::Ordering::SeqCst,
+        );
+
+        let mut h2 = HazPtrHolder::default();
+        let my_x2 = unsafe { h2.load(&x) }.expect("not null");
+
+        assert_eq!(my_x.0, 42);
+        assert_eq!(my_x2.0, 9001);
+
+        // Safety:
+        //
+        //  1. The pointer came from Box, so is valid.
+        //  2. The old value is no longer accessible.
+        //  3. The deleter is valid for Box types.
+        unsafe { old.retire() };
+
+        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
+        assert_eq!(my_x.0, 42);
+
+        let n = SHARED_DOMAIN.eager_reclaim(false);
+        assert_eq!(n, 0);
+
+        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
+        assert_eq!(my_x.0, 42);
+
+        drop(h);
+        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
+        // _not_ drop(h2);
+
+        let n = SHARED_DOMAIN.eager_reclaim(false);
+        assert_eq!(n, 1);
+
+        assert_eq!(drops_42.load(Ordering::SeqCst), 1);
+        assert_eq!(drops_9001.load(Ordering::SeqCst), 0);
+
+        drop(h2);
+        let n = SHARED_DOMAIN.eager_reclaim(false);
+        assert_eq!(n, 0);
+        assert_eq!(drops_9001.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn try_protect() {
+        let x = AtomicPtr::new(Box::into_raw(Box::new(
+            HazPtrObjectWrapper::with_default_domain(42),
+        )));
+        let stale = Box::into_raw(Box::new(HazPtrObjectWrapper::with_default_domain(0)));
+
+        let mut h = HazPtrHolder::default();
+        // Safety: x and stale always point to valid Boxes that are never retired.
+        let err = unsafe { h.try_protect(stale, &x) }
+            .map(|_| ())
+            .expect_err("stale pointer");
+        assert_eq!(err.observed(), x.load(Ordering::SeqCst));
+        assert!(!err.was_null());
+        let my_x = unsafe { err.retry(&mut h, &x) }.expect("current pointer");
+        assert_eq!(**my_x.expect("not null"), 42);
+
+        let my_x = unsafe { h.try_protect_for(&x, Duration::from_secs(0)) }.expect("no writers");
+        assert_eq!(**my_x.expect("not null"), 42);
+        drop(h);
+
+        // Safety: nobody is protecting either pointer anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+        drop(unsafe { Box::from_raw(stale) });
+    }
+
+    #[test]
+    fn watchdog() {
+        let x = AtomicPtr::new(Box::into_raw(Box::new(
+            HazPtrObjectWrapper::with_default_domain(42),
+        )));
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_ = Arc::clone(&reports);
+        SHARED_DOMAIN.set_watchdog(Duration::from_millis(1), move |report| {
+            reports_.lock().unwrap().push(report.clone());
+        });
+
+        let mut h = HazPtrHolder::default();
+        // Safety: x always points to a valid Box that is never retired.
+        let _ = unsafe { h.load(&x) }.expect("not null");
+        let ours = |reports: &Vec<WatchdogReport>| {
+            reports
+                .iter()
+                .filter(|r| r.addr == x.load(Ordering::SeqCst).addr())
+                .count()
+        };
+
+        SHARED_DOMAIN.check_watchdog();
+        assert_eq!(ours(&reports.lock().unwrap()), 0);
+        std::thread::sleep(Duration::from_millis(5));
+        SHARED_DOMAIN.check_watchdog();
+        SHARED_DOMAIN.check_watchdog();
+        {
+            let reports = reports.lock().unwrap();
+            assert_eq!(ours(&reports), 1);
+            let report = reports
+                .iter()
+                .find(|r| r.addr == x.load(Ordering::SeqCst).addr());
+            assert_eq!(report.unwrap().thread, thread_index());
+        }
+        SHARED_DOMAIN.clear_watchdog();
+
+        drop(h);
+        // Safety: nobody is protecting x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn watchdog_duplicates_protection() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let x = AtomicBox::with_domain(&DOMAIN, HazPtrObjectWrapper::with_domain(&DOMAIN, 42));
+        let held = Arc::new(Mutex::new(x.load_owned()));
+        let copies = Arc::new(Mutex::new(Vec::new()));
+        let (held_, copies_) = (Arc::clone(&held), Arc::clone(&copies));
+        // Duplicating a protection waits for scans to be done with the hazard list, so the
+        // callback must not run while the scan still walks it.
+        DOMAIN.set_watchdog(Duration::from_millis(1), move |_| {
+            let copy = held_.lock().unwrap().clone();
+            copies_.lock().unwrap().push(copy);
+        });
+
+        DOMAIN.check_watchdog();
+        std::thread::sleep(Duration::from_millis(5));
+        DOMAIN.check_watchdog();
+        DOMAIN.clear_watchdog();
+        let copies = std::mem::take(&mut *copies.lock().unwrap());
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].as_deref().map(|x| **x), Some(42));
+    }
+
+    #[test]
+    fn reclaim_observer() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_reclaim_threshold(ReclaimThreshold {
+            fixed: 100,
+            per_hazard: 0,
+        });
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_ = Arc::clone(&seen);
+        DOMAIN.set_reclaim_observer(move |reclaimed| {
+            seen_
+                .lock()
+                .unwrap()
+                .push((reclaimed.addr, reclaimed.deleted_by(&deleters::drop_box)));
+        });
+
+        let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN, 42,
+        ))));
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x holds a valid Box, only freed by retiring it.
+        let _ = unsafe { h.load(&x) }.expect("not null");
+        let old = x.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: old came from a Box, and is no longer reachable through x.
+        unsafe { old.retire() };
+        DOMAIN.eager_reclaim(false);
+        assert!(seen.lock().unwrap().is_empty());
+
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        {
+            let seen = seen.lock().unwrap();
+            assert_eq!(*seen, [(old.addr(), true)]);
+        }
+
+        DOMAIN.clear_reclaim_observer();
+        let y = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 1)));
+        // Safety: y came from a Box, and was never shared.
+        unsafe { y.retire() };
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn step_mode() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        struct Node(usize, Arc<Mutex<Vec<usize>>>);
+        impl Drop for Node {
+            fn drop(&mut self) {
+                self.1.lock().unwrap().push(self.0);
+            }
+        }
+        impl HazPtrObject for Node {
+            fn domain(&self) -> &HazPtrDomain {
+                &DOMAIN
+            }
+        }
+
+        DOMAIN.set_step_mode(true);
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let node = Box::into_raw(Box::new(Node(i, Arc::clone(&dropped))));
+            // Safety: node came from a Box and was never shared.
+            unsafe { node.retire() };
+        }
+        assert!(dropped.lock().unwrap().is_empty());
+
+        assert!(DOMAIN.step());
+        assert_eq!(*dropped.lock().unwrap(), [0]);
+        assert!(DOMAIN.step());
+        assert!(DOMAIN.step());
+        assert_eq!(*dropped.lock().unwrap(), [0, 1, 2]);
+        assert!(!DOMAIN.step());
+    }
+
+    #[test]
+    fn load_prepared() {
+        let x = AtomicPtr::new(Box::into_raw(Box::new(
+            HazPtrObjectWrapper::with_default_domain(42),
+        )));
+
+        let mut h = HazPtrHolder::default();
+        // Safety: x always points to a valid Box that is never retired.
+        assert!(unsafe { h.load_prepared(&x) }.is_err());
+        h.prepare();
+        let my_x = unsafe { h.load_prepared(&x) }.expect("prepared");
+        assert_eq!(**my_x.expect("not null"), 42);
+        drop(h);
+
+        // Safety: nobody is protecting x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn retire_without_memory() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        struct Failing;
+        unsafe impl GlobalAlloc for Failing {
+            unsafe fn alloc(&self, _: Layout) -> *mut u8 {
+                std::ptr::null_mut()
+            }
+            unsafe fn dealloc(&self, _: *mut u8, _: Layout) {
+                unreachable!("nothing was allocated");
+            }
+        }
+        assert!(DOMAIN.set_bookkeeping_allocator(&Failing).is_ok());
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            CountDrops(Arc::clone(&drops)),
+        )));
+        // Safety: x came from a Box, and was never shared.
+        assert_eq!(unsafe { x.try_retire() }, Err(RetireError(())));
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // With nothing guarding it, retire deletes it right away rather than giving up.
+        // Safety: try_retire failed, so x is still ours.
+        unsafe { x.retire() };
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn bookkeeping_allocator() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+        struct Counting;
+        unsafe impl GlobalAlloc for Counting {
+            unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                ALLOCATED.fetch_add(1, Ordering::SeqCst);
+                unsafe { std::alloc::alloc(layout) }
+            }
+            unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                ALLOCATED.fetch_sub(1, Ordering::SeqCst);
+                unsafe { std::alloc::dealloc(ptr, layout) }
+            }
+        }
+        struct Node;
+        impl Drop for Node {
+            fn drop(&mut self) {}
+        }
+        impl HazPtrObject for Node {
+            fn domain(&self) -> &HazPtrDomain {
+                &DOMAIN
+            }
+        }
+
+        assert!(DOMAIN.set_bookkeeping_allocator(&Counting).is_ok());
+        DOMAIN.set_step_mode(true);
+        let node = Box::into_raw(Box::new(Node));
+        // Safety: node came from a Box and was never shared.
+        unsafe { node.retire() };
+        assert_eq!(ALLOCATED.load(Ordering::SeqCst), 1);
+        assert!(DOMAIN.step());
+        assert_eq!(ALLOCATED.load(Ordering::SeqCst), 0);
+        assert!(DOMAIN.set_bookkeeping_allocator(&Counting).is_err());
+    }
+
+    /// The number of hazard records linked into `domain`'s list.
+    fn hazptr_count(domain: &HazPtrDomain) -> usize {
+        let _walk = domain.hazptrs.walk();
+        let mut n = 0;
+        let mut node = domain.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            n += 1;
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            node = unsafe { &*node }.next.load(Ordering::SeqCst);
+        }
+        n
+    }
+
+    #[test]
+    fn shrink_policy() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let hazptrs: Vec<_> = (0..10).map(|_| DOMAIN.acquire()).collect();
+        let (busy, idle) = hazptrs.split_at(2);
+        for hazptr in idle {
+            hazptr.active.store(false, Ordering::SeqCst);
+        }
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(hazptr_count(&DOMAIN), 10);
+
+        DOMAIN.set_shrink_policy(Some(ShrinkPolicy {
+            high_water: 4,
+            low_water: 2,
         }));
-        // Increment the count _before_ we give anyone a chance to reclaim it.
-        self.retired.count.fetch_add(1, Ordering::SeqCst);
-        // Stick it at the head of the linked list
-        let head_ptr = &self.retired.head;
-        let mut head = head_ptr.load(Ordering::SeqCst);
-        loop {
-            // Safety: retired was never shared, so &mut is ok.
-            *unsafe { &mut *retired }.next.get_mut() = head;
-            match head_ptr.compare_exchange_weak(head, retired, Ordering::SeqCst, Ordering::SeqCst)
-            {
-                Ok(_) => break,
-                Err(head_now) => {
-                    // Head has changed, try again with that as our next ptr.
-                    head = head_now
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(hazptr_count(&DOMAIN), 4);
+        assert!(busy.iter().all(|h| h.active.load(Ordering::SeqCst)));
+        assert!(DOMAIN.hazptrs.unlinked.lock().unwrap().is_empty());
+
+        // Within the hysteresis band, nothing happens.
+        for hazptr in busy {
+            hazptr.active.store(false, Ordering::SeqCst);
+        }
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(hazptr_count(&DOMAIN), 4);
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let mut holders: Vec<_> = (0..20)
+            .map(|_| {
+                let mut h = HazPtrHolder::for_domain(&DOMAIN);
+                h.prepare();
+                h
+            })
+            .collect();
+        assert_eq!(hazptr_count(&DOMAIN), 20);
+        let busy = holders.split_off(18);
+        // Some of these end up in the thread's cache, which shrink_to_fit empties.
+        drop(holders);
+        DOMAIN.shrink_to_fit();
+        assert_eq!(hazptr_count(&DOMAIN), 2);
+        assert!(busy
+            .iter()
+            .all(|h| h.hazptr.unwrap().active.load(Ordering::SeqCst)));
+    }
+
+    #[test]
+    fn reserve_hazards() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let mut busy = HazPtrHolder::for_domain(&DOMAIN);
+        busy.prepare();
+        DOMAIN.reserve_hazards(3);
+        assert_eq!(hazptr_count(&DOMAIN), 4);
+        // Already free slots count towards the reservation.
+        DOMAIN.reserve_hazards(2);
+        assert_eq!(hazptr_count(&DOMAIN), 4);
+
+        let mut holders: Vec<_> = (0..3).map(|_| HazPtrHolder::for_domain(&DOMAIN)).collect();
+        for h in &mut holders {
+            h.prepare();
+        }
+        assert_eq!(hazptr_count(&DOMAIN), 4);
+    }
+
+    #[test]
+    fn protect_child() {
+        struct Node {
+            value: usize,
+            next: AtomicPtr<HazPtrObjectWrapper<Node>>,
+        }
+        let node = |value, next| {
+            Box::into_raw(Box::new(HazPtrObjectWrapper::with_default_domain(Node {
+                value,
+                next: AtomicPtr::new(next),
+            })))
+        };
+        let head = AtomicPtr::new(node(1, node(2, std::ptr::null_mut())));
+
+        let mut h1 = HazPtrHolder::default();
+        let mut h2 = HazPtrHolder::default();
+        // Safety: the list only contains valid Boxes, which are never retired.
+        let first = unsafe { h1.load(&head) }.expect("not null");
+        let second = unsafe { h2.protect_child(first, |n| &n.next) }.expect("not null");
+        h1.reset();
+        assert_eq!(second.value, 2);
+        assert!(unsafe { h1.protect_child(second, |n| &n.next) }.is_none());
+        drop((h1, h2));
+
+        // Safety: nobody is protecting the list anymore.
+        let first = unsafe { Box::from_raw(head.into_inner()) };
+        drop(unsafe { Box::from_raw(first.next.load(Ordering::SeqCst)) });
+    }
+
+    #[test]
+    fn single_writer() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicPtr::new(Box::into_raw(Box::new(CountDrops(Arc::clone(&drops)))));
+
+        // Safety: this thread is the only one that ever retires on DOMAIN.
+        unsafe { DOMAIN.set_single_writer() };
+        let mut h = backend::Holder::new(&DOMAIN);
+        // Safety: x always holds a valid Box, and is only retired on DOMAIN.
+        let _ = unsafe { h.load(&x) }.expect("not null");
+
+        let old = x.swap(
+            Box::into_raw(Box::new(CountDrops(Arc::clone(&drops)))),
+            Ordering::SeqCst,
+        );
+        // Safety: old came from a Box, and is no longer reachable through x.
+        unsafe { backend::Backend::retire(&DOMAIN, old, &deleters::drop_box) };
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(DOMAIN.retired.count.load(Ordering::SeqCst), 0);
+
+        // Other threads can't reclaim the writer's objects.
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                drop(h);
+                assert_eq!(DOMAIN.eager_reclaim(true), 0);
+            });
+        });
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // Safety: nobody is reading x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn static_domain() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            CountDrops(Arc::clone(&drops)),
+        ))));
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x always holds a valid Box, and is only retired through HazPtrObject::retire.
+        let _ = unsafe { h.load(&x) }.expect("not null");
+        let old = x.swap(
+            Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(Arc::clone(&drops)),
+            ))),
+            Ordering::SeqCst,
+        );
+        // Safety: old came from a Box, and is no longer reachable through x.
+        unsafe { old.retire() };
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        drop(h);
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // Safety: nobody is reading x anymore.
+        drop(unsafe { Box::from_raw(x.into_inner()) });
+    }
+
+    #[test]
+    fn static_domain_macro() {
+        static_domain!(A);
+        static_domain!(B);
+        assert!(std::ptr::eq(A::domain(), A::domain()));
+        assert!(!std::ptr::eq(A::domain(), B::domain()));
+        assert!(!std::ptr::eq(A::domain(), HazPtrDomain::global()));
+    }
+
+    #[test]
+    fn unique_domain_macro() {
+        fn domain() -> &'static HazPtrDomain {
+            unique_domain!()
+        }
+        assert!(std::ptr::eq(domain(), domain()));
+        assert!(!std::ptr::eq(domain(), unique_domain!()));
+        assert!(!std::ptr::eq(domain(), HazPtrDomain::global()));
+    }
+
+    #[test]
+    fn drain_with_timeout() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let addr = (x.load(&mut h).unwrap() as *const HazPtrObjectWrapper<CountDrops>).addr();
+        drop(x);
+
+        let incomplete = DOMAIN
+            .drain_with_timeout(Duration::from_millis(10))
+            .expect_err("still protected");
+        assert_eq!(incomplete.remaining, 1);
+        assert_eq!(
+            incomplete.bytes,
+            std::mem::size_of::<HazPtrObjectWrapper<CountDrops>>()
+        );
+        assert_eq!(incomplete.blocking.len(), 1);
+        assert_eq!(incomplete.blocking[0].addr, addr);
+        assert_eq!(incomplete.blocking[0].thread, thread_index());
+
+        drop(h);
+        assert_eq!(DOMAIN.drain_with_timeout(Duration::from_millis(10)), Ok(()));
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn protected_ref_resets_on_drop() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let guard = x.protect(&mut h).unwrap();
+        x.store(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            CountDrops(Arc::clone(&drops)),
+        ));
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(Arc::strong_count(&guard.0), 3);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        drop(guard);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        // The holder is free for reuse.
+        assert!(x.protect(&mut h).is_some());
+    }
+
+    #[test]
+    fn protect_safe() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static OTHER: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let guard = h.protect_safe(&x).unwrap();
+        x.store(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            CountDrops(Arc::clone(&drops)),
+        ));
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(guard);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let y = AtomicArc::with_domain(&DOMAIN, Some(Arc::new(7)));
+        assert_eq!(*h.protect_safe(&y).unwrap(), 7);
+
+        let mut other = HazPtrHolder::for_domain(&OTHER);
+        let wrong = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            other.protect_safe(&x).is_some()
+        }));
+        assert!(wrong.is_err());
+    }
+
+    #[test]
+    fn guards_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<HazPtrHolder>();
+        assert_send::<Protected<u32>>();
+        assert_send::<ProtectedRef<'static, u32>>();
+        assert_send::<PinGuard>();
+        assert_send::<HazardGuard<u32>>();
+
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let guard = x.protect(&mut h).unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                // The object stays protected while the guard is on this thread.
+                x.store(HazPtrObjectWrapper::with_domain(
+                    &DOMAIN,
+                    CountDrops(Arc::clone(&drops)),
+                ));
+                DOMAIN.eager_reclaim(false);
+                assert_eq!(drops.load(Ordering::SeqCst), 0);
+                assert_eq!(Arc::strong_count(&guard.0), 3);
+                drop(guard);
+                DOMAIN.eager_reclaim(false);
+                assert_eq!(drops.load(Ordering::SeqCst), 1);
+            });
+        });
+    }
+
+    #[test]
+    fn duplicate_while_others_keep_walking() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let x = AtomicBox::with_domain(&DOMAIN, HazPtrObjectWrapper::with_domain(&DOMAIN, 1));
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            // Overlapping walks, so that there is never a moment without one.
+            s.spawn(|| {
+                let mut walk = DOMAIN.hazptrs.walk();
+                while !done.load(Ordering::SeqCst) {
+                    let next = DOMAIN.hazptrs.walk();
+                    drop(std::mem::replace(&mut walk, next));
                 }
+            });
+            while DOMAIN.hazptrs.walkers.load(Ordering::SeqCst) == 0 {
+                std::thread::yield_now();
+            }
+            let mut h1 = HazPtrHolder::for_domain(&DOMAIN);
+            let mut h2 = HazPtrHolder::for_domain(&DOMAIN);
+            let original = h1.protect_safe(&x).unwrap();
+            assert_eq!(**h2.duplicate_protection(&original), 1);
+            done.store(true, Ordering::SeqCst);
+        });
+    }
+
+    #[test]
+    fn duplicate_protection() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = AtomicBox::with_domain(
+            &DOMAIN,
+            HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops))),
+        );
+        let mut h1 = HazPtrHolder::for_domain(&DOMAIN);
+        let mut h2 = HazPtrHolder::for_domain(&DOMAIN);
+        let original = x.protect(&mut h1).unwrap();
+        let duplicate = h2.duplicate_protection(&original);
+        assert!(std::ptr::eq(&*original, &*duplicate));
+        drop(original);
+        let owned = x.load_owned().unwrap();
+        let cloned = owned.clone();
+        drop(owned);
+        drop(x);
+
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(duplicate);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(cloned);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn teardown_reclaims_everything() {
+        let domain: &'static HazPtrDomain = Box::leak(Box::new(
+            HazPtrDomain::builder()
+                .reclaim_threshold(ReclaimThreshold {
+                    fixed: 100,
+                    per_hazard: 0,
+                })
+                .hazard_capacity(4)
+                .build(),
+        ));
+        domain.set_quarantine(Some(Duration::from_secs(3600)));
+        let drops = Arc::new(AtomicUsize::new(0));
+        let retire = || {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                domain,
+                CountDrops(Arc::clone(&drops)),
+            )));
+            // Safety: x came from a Box, and was never shared.
+            unsafe { x.retire() };
+        };
+        retire();
+        domain.set_quarantine(None);
+        retire();
+        let mut h = HazPtrHolder::for_domain(domain);
+        h.prepare();
+        drop(h);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // Safety: domain came from Box::leak, and nothing refers to it anymore.
+        let domain = unsafe { Box::from_raw(domain as *const HazPtrDomain as *mut HazPtrDomain) };
+        assert_eq!(
+            domain.teardown(),
+            Teardown {
+                reclaimed: 2,
+                leaked: 0
+            }
+        );
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn multi_domain_holder() {
+        static A: HazPtrDomain = HazPtrDomain::new();
+        static B: HazPtrDomain = HazPtrDomain::new();
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let a = AtomicBox::with_domain(
+            &A,
+            HazPtrObjectWrapper::with_domain(&A, (1, CountDrops(Arc::clone(&drops)))),
+        );
+        let b = AtomicBox::with_domain(&B, HazPtrObjectWrapper::with_domain(&B, 2));
+
+        let mut h = MultiHazPtrHolder::default();
+        assert_eq!(h.load_box(&a).unwrap().0, 1);
+        assert_eq!(**h.load_box(&b).unwrap(), 2);
+        assert_eq!(h.load_box(&a).unwrap().0, 1);
+        // Slots are reused rather than acquired anew.
+        assert_eq!(h.idle.len(), 1);
+
+        // Switching to B released the protection of a's object.
+        h.load_box(&b);
+        drop(a);
+        A.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn high_water() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_step_mode(true);
+
+        let mut h1 = HazPtrHolder::for_domain(&DOMAIN);
+        let mut h2 = HazPtrHolder::for_domain(&DOMAIN);
+        h1.prepare();
+        h2.prepare();
+        drop(h2);
+
+        for i in 0..3u64 {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, i)));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
+        }
+        let bytes = 3 * std::mem::size_of::<HazPtrObjectWrapper<u64>>();
+        while DOMAIN.step() {}
+
+        let peaks = DOMAIN.high_water().all_time;
+        assert_eq!(peaks.hazards, 2);
+        assert_eq!(peaks.retired, 3);
+        assert_eq!(peaks.retired_bytes, bytes);
+
+        // The new window starts at what is live now: one hazard, no retired objects.
+        assert_eq!(DOMAIN.reset_high_water_window(), peaks);
+        let window = DOMAIN.high_water().window;
+        assert_eq!(window.hazards, 1);
+        assert_eq!(window.retired, 0);
+        assert_eq!(window.retired_bytes, 0);
+        assert_eq!(DOMAIN.high_water().all_time, peaks);
+        drop(h1);
+    }
+
+    #[test]
+    fn protect_or_wait() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static PUBLISHER: Publisher = Publisher::new();
+        let slot = AtomicPtr::<HazPtrObjectWrapper<i32>>::new(std::ptr::null_mut());
+
+        std::thread::scope(|s| {
+            let reader = s.spawn(|| {
+                let mut h = HazPtrHolder::for_domain(&DOMAIN);
+                // Safety: slot only ever holds null or a valid Box, retired on DOMAIN.
+                **unsafe { h.protect_or_wait(&slot, &PUBLISHER) }
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 42)));
+            assert!(PUBLISHER.publish(&slot, x).is_null());
+            assert_eq!(reader.join().unwrap(), 42);
+        });
+
+        let x = PUBLISHER.publish(&slot, std::ptr::null_mut());
+        // Safety: x is no longer reachable, and came from a Box.
+        unsafe { x.retire() };
+    }
+
+    #[test]
+    fn unprotected() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN, 7,
+        ))));
+
+        // Slots that are only cached for reuse don't get in the way of other threads.
+        let mut cached = HazPtrHolder::for_domain(&DOMAIN);
+        cached.prepare();
+        drop(cached);
+        std::thread::spawn(|| {
+            // Safety: no other thread touches DOMAIN.
+            unsafe { DOMAIN.unprotected() };
+        })
+        .join()
+        .unwrap();
+
+        // Our own holders don't get in the way.
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        h.prepare();
+        {
+            // Safety: no other thread touches DOMAIN.
+            let u = unsafe { DOMAIN.unprotected() };
+            // Safety: x holds a valid Box, only retired below.
+            assert_eq!(unsafe { u.load(&x) }.map(|x| **x), Some(7));
+        }
+
+        // But they do get in the way of other threads.
+        std::thread::spawn(move || {
+            if cfg!(debug_assertions) {
+                let held = std::panic::catch_unwind(|| {
+                    // Safety: the domain check is expected to fail before anything is accessed.
+                    unsafe { DOMAIN.unprotected() };
+                });
+                assert!(held.is_err());
+            }
+        })
+        .join()
+        .unwrap();
+
+        let x = x.into_inner();
+        // Safety: x is no longer reachable, and came from a Box.
+        unsafe { x.retire() };
+    }
+
+    #[test]
+    fn fifo_reclamation() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_fifo_reclamation(true);
+
+        static ORDER: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+        struct Logged(u32);
+        impl Drop for Logged {
+            fn drop(&mut self) {
+                ORDER.lock().unwrap().push(self.0);
+            }
+        }
+
+        let objs: Vec<_> = (0..4)
+            .map(|i| {
+                AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                    &DOMAIN,
+                    Logged(i),
+                ))))
+            })
+            .collect();
+
+        // Keep the second object alive, which must hold back everything retired after it.
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: objs only hold valid Boxes, retired on DOMAIN.
+        unsafe { h.load(&objs[1]) };
+
+        for obj in &objs {
+            // Safety: the objects are no longer reachable (for new readers), and came from
+            // Boxes.
+            unsafe { obj.load(Ordering::SeqCst).retire() };
+        }
+        assert_eq!(*ORDER.lock().unwrap(), [0]);
+
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(*ORDER.lock().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn thread_affinity() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        thread_local! {
+            static DROPPED_HERE: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+        struct ThreadBound;
+        impl Drop for ThreadBound {
+            fn drop(&mut self) {
+                DROPPED_HERE.with(|d| d.set(d.get() + 1));
+            }
+        }
+
+        let me = std::thread::current().id();
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            ThreadBound,
+        )));
+        struct SendPtr(*mut HazPtrObjectWrapper<ThreadBound>);
+        // Safety: the object is only handed over to be retired.
+        unsafe impl Send for SendPtr {}
+        let x = SendPtr(x);
+        std::thread::spawn(move || {
+            let SendPtr(x) = x;
+            // Safety: x was never shared with readers, and came from a Box.
+            unsafe { x.retire_on(me) };
+            DOMAIN.eager_reclaim(false);
+            assert_eq!(DROPPED_HERE.with(|d| d.get()), 0);
+            assert_eq!(DOMAIN.run_thread_deleters(), 0);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(DOMAIN.run_thread_deleters(), 1);
+        assert_eq!(DROPPED_HERE.with(|d| d.get()), 1);
+    }
+
+    #[test]
+    fn thread_confined() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        std::thread::spawn(|| {
+            assert!(DOMAIN.confine_to_current_thread());
+            assert!(!DOMAIN.confine_to_current_thread());
+
+            let rc = std::rc::Rc::new(());
+            let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                std::rc::Rc::clone(&rc),
+            ))));
+            let mut h = HazPtrHolder::for_domain(&DOMAIN);
+            // Safety: x holds a valid Box, only retired on DOMAIN.
+            unsafe { h.load(&x) };
+            h.reset();
+            // Safety: x is no longer reachable, came from a Box, and DOMAIN is confined to this
+            // thread.
+            unsafe { x.into_inner().retire() };
+            DOMAIN.eager_reclaim(false);
+            assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+        })
+        .join()
+        .unwrap();
+
+        let elsewhere = std::panic::catch_unwind(|| HazPtrHolder::for_domain(&DOMAIN).prepare());
+        assert!(elsewhere.is_err());
+    }
+
+    #[test]
+    fn finalizer() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            (7, CountDrops(Arc::clone(&drops))),
+        )));
+        let src = AtomicPtr::new(x);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: src holds a valid Box, only retired on DOMAIN.
+        unsafe { h.load(&src) };
+
+        let d = Arc::clone(&drops);
+        // Safety: x is only reachable by the existing reader, and came from a Box.
+        unsafe {
+            x.retire_with_finalizer(move |x| {
+                // The deleter hasn't run yet.
+                assert_eq!(d.load(Ordering::SeqCst), 0);
+                SEEN.store(x.0, Ordering::SeqCst);
+            })
+        };
+        assert_eq!(SEEN.load(Ordering::SeqCst), 0);
+
+        h.reset();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(SEEN.load(Ordering::SeqCst), 7);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn wrapper_is_transparent() {
+        static OTHER: HazPtrDomain = HazPtrDomain::new();
+        let a = HazPtrObjectWrapper::with_default_domain(String::from("a"));
+        let b = HazPtrObjectWrapper::with_domain(&OTHER, String::from("b"));
+
+        // The domain doesn't matter.
+        assert!(a == HazPtrObjectWrapper::with_domain(&OTHER, String::from("a")));
+        assert!(a < b);
+        assert_eq!(format!("{}{}", a, b), "ab");
+
+        // Hashing agrees with Borrow, as HashMap and friends require.
+        fn hash_of<H: std::hash::Hash + ?Sized>(x: &H) -> u64 {
+            use std::hash::Hasher;
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            x.hash(&mut hasher);
+            hasher.finish()
+        }
+        assert_eq!(hash_of(&a), hash_of(&String::from("a")));
+    }
+
+    #[test]
+    fn reclaim_ages() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_step_mode(true);
+        assert_eq!(DOMAIN.oldest_retired_age(), None);
+
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 1)));
+        // Safety: x was never shared, and came from a Box.
+        unsafe { x.retire() };
+        std::thread::sleep(Duration::from_millis(2));
+        let age = DOMAIN.oldest_retired_age().expect("one object is waiting");
+        assert!(age >= Duration::from_millis(2));
+
+        assert!(DOMAIN.step());
+        assert_eq!(DOMAIN.oldest_retired_age(), None);
+        let ages = DOMAIN.reclaim_ages();
+        assert_eq!(ages.count(), 1);
+        let waited = ages.quantile(1.0).unwrap();
+        assert!(waited > Duration::from_millis(2), "{:?}", waited);
+    }
+
+    #[test]
+    fn backpressure() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_step_mode(true);
+        DOMAIN.set_retire_budgets(
+            RetireBudget {
+                objects: 1,
+                ..RetireBudget::UNLIMITED
+            },
+            RetireBudget {
+                objects: 2,
+                ..RetireBudget::UNLIMITED
+            },
+        );
+
+        let retire = || {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 0)));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire_with_backpressure() }
+        };
+        assert_eq!(retire(), Backpressure::Ok);
+        assert_eq!(retire(), Backpressure::Elevated);
+        assert_eq!(retire(), Backpressure::Critical);
+
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(DOMAIN.backpressure(), Backpressure::Ok);
+    }
+
+    #[test]
+    fn quarantine() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_quarantine(Some(Duration::from_millis(50)));
+        DOMAIN.set_fifo_reclamation(true);
+
+        static ORDER: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+        struct Logged(u32);
+        impl Drop for Logged {
+            fn drop(&mut self) {
+                ORDER.lock().unwrap().push(self.0);
             }
         }
 
-        // Now, check if we need to retire.
-        // TODO: better heuristics "once in a while"
-        if self.retired.count.load(Ordering::SeqCst) != 0 {
-            self.bulk_reclaim(0, false);
+        for i in 0..100 {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                Logged(i),
+            )));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
         }
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        assert!(ORDER.lock().unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(DOMAIN.eager_reclaim(false), 100);
+        assert_eq!(*ORDER.lock().unwrap(), (0..100).collect::<Vec<_>>());
+
+        // Lifting the quarantine releases whatever is still waiting.
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            Logged(100),
+        )));
+        // Safety: x was never shared, and came from a Box.
+        unsafe { x.retire() };
+        DOMAIN.set_quarantine(None);
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
     }
 
-    pub fn eager_reclaim(&self, block: bool) -> usize {
-        self.bulk_reclaim(0, block)
+    #[test]
+    fn cpu_batching() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_cpu_batching(true);
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(Arc::clone(&drops)),
+            )));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
+        }
+        // The objects wait in their batch until it fills up...
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert!(DOMAIN.oldest_retired_age().is_some());
+        // ...or until the next reclamation.
+        assert_eq!(DOMAIN.eager_reclaim(false), 3);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
     }
 
-    fn bulk_reclaim(&self, mut reclaimed: usize, block: bool) -> usize {
-        let steal = self
-            .retired
-            .head
-            .swap(std::ptr::null_mut(), Ordering::SeqCst);
-        if steal.is_null() {
-            // Nothing to reclaim!
-            return 0;
+    #[test]
+    fn exiting_thread_flushes_batches() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_cpu_batching(true);
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let drops_ = Arc::clone(&drops);
+        std::thread::spawn(move || {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(drops_),
+            )));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
+            assert_eq!(DOMAIN.retired.count.load(Ordering::SeqCst), 0);
+        })
+        .join()
+        .unwrap();
+        // With nothing protected, the exiting thread reclaimed its batch right away.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn load_bounded() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let new = |i: usize| Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, i)));
+        let x = AtomicPtr::new(new(0));
+
+        // With no attempts to spare, the reader answers its own request.
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x only holds valid Boxes, retired on DOMAIN after helping readers.
+        assert_eq!(unsafe { h.load_bounded(&x, 0) }.map(|v| **v), Some(0));
+
+        // A writer answers a pending request with the value it stores.
+        let request = help_request(&x);
+        h.hazptr().protect(request);
+        DOMAIN.help_requests.fetch_add(1, Ordering::SeqCst);
+        let old = DOMAIN.swap_helping(&x, new(1));
+        DOMAIN.help_requests.fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(
+            h.hazptr().ptr.load(Ordering::SeqCst),
+            x.load(Ordering::SeqCst) as *mut u8
+        );
+        // Safety: old is no longer reachable, and came from a Box.
+        unsafe { old.retire() };
+
+        // Readers never see freed values, however often writers get in their way.
+        let stop = Arc::new(AtomicBool::new(false));
+        let x = Arc::new(x);
+        let writer = {
+            let (stop, x) = (Arc::clone(&stop), Arc::clone(&x));
+            std::thread::spawn(move || {
+                for i in 2.. {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let old = DOMAIN.swap_helping(&x, new(i));
+                    // Safety: as above.
+                    unsafe { old.retire() };
+                }
+            })
+        };
+        let mut last = 0;
+        for _ in 0..10_000 {
+            // Safety: as above.
+            let v = **unsafe { h.load_bounded(&x, 1) }.unwrap();
+            assert!(v >= last);
+            last = v;
         }
+        stop.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+        h.reset();
+        let x = Arc::try_unwrap(x).unwrap();
+        // Safety: x is not used after this, and holds a Box.
+        unsafe { x.into_inner().retire() };
+        DOMAIN.eager_reclaim(true);
+    }
 
-        // Find all guarded addresses.
-        #[allow(clippy::mutable_key_type)]
-        let mut guarded_ptrs = HashSet::new();
-        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
-        while !node.is_null() {
-            // Safety: HazPtrs are never de-allocated.
-            let n = unsafe { &*node };
-            guarded_ptrs.insert(n.ptr.load(Ordering::SeqCst));
-            node = n.next.load(Ordering::SeqCst);
+    #[test]
+    fn holder_array() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        // One slot to reuse, and two to allocate.
+        HazPtrHolder::for_domain(&DOMAIN).prepare();
+        let mut holders = HazPtrHolderArray::<3>::for_domain(&DOMAIN);
+        assert_eq!(DOMAIN.snapshot().slots.len(), 3);
+        assert_eq!(DOMAIN.high_water().all_time.hazards, 3);
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let objs: Vec<_> = (0..3)
+            .map(|_| {
+                AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                    &DOMAIN,
+                    CountDrops(Arc::clone(&drops)),
+                ))))
+            })
+            .collect();
+        let [a, b, c] = holders.holders();
+        // Safety: objs only hold valid Boxes, retired on DOMAIN.
+        let protected = unsafe { [a.load(&objs[0]), b.load(&objs[1]), c.load(&objs[2])] };
+        assert!(protected.iter().all(Option::is_some));
+        for obj in &objs {
+            // Safety: the objects are no longer reachable (for new readers), and came from
+            // Boxes.
+            unsafe { obj.load(Ordering::SeqCst).retire() };
         }
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
 
-        // Reclaim any retired objects that aren't guarded
-        let mut node = steal;
-        let mut remaining = std::ptr::null_mut();
-        let mut tail = None;
-        while !node.is_null() {
-            // Safety: All accessors only access the head, and the head is no longer pointing here.
-            let mut n = unsafe { Box::from_raw(node) };
-            node = *n.next.get_mut();
-
-            if guarded_ptrs.contains(&(n.ptr as *mut u8)) {
-                // Not safe to reclaim -- still guarded.
-                *n.next.get_mut() = remaining;
-                remaining = Box::into_raw(n);
-                if tail.is_none() {
-                    tail = Some(remaining);
-                }
-            } else {
-                // No longer guarded -- reclaim using deleter.
-                // Safety:
-                // - `n.ptr` has not yet been dropped and will not be dropped again (we have removed it from `remaining`)
-                // - `n.ptr` has been allocated the corresponding allocation method corresponding to `n.deleter`
-                //   as per the safety guarantees of calling `retire`.
-                unsafe { n.deleter.delete(n.ptr) };
-                reclaimed += 1;
-            }
+        holders.holders()[1].reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        drop(holders);
+        assert_eq!(DOMAIN.eager_reclaim(false), 2);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn protect_all() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let new = || {
+            Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(Arc::clone(&drops)),
+            )))
+        };
+        let objs = [AtomicPtr::new(new()), AtomicPtr::new(std::ptr::null_mut())];
+
+        let mut holders = HazPtrHolderArray::<2>::for_domain(&DOMAIN);
+        // Safety: objs only hold valid Boxes, retired on DOMAIN.
+        let [a, b] = unsafe { holders.protect_all([&objs[0], &objs[1]]) };
+        assert!(a.is_some() && b.is_none());
+        let old = objs[0].swap(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: old is no longer reachable through objs, and came from a Box.
+        unsafe { old.retire() };
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+
+        holders.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reclaim_threshold() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_reclaim_threshold(ReclaimThreshold {
+            fixed: 2,
+            per_hazard: 2,
+        });
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let retire = || {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(Arc::clone(&drops)),
+            )));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
+        };
+
+        retire();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        retire();
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // With two readers around, it takes four.
+        let mut holders = HazPtrHolderArray::<2>::for_domain(&DOMAIN);
+        for _ in 0..3 {
+            retire();
         }
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        retire();
+        assert_eq!(drops.load(Ordering::SeqCst), 6);
+        holders.reset();
+    }
 
-        self.retired.count.fetch_sub(reclaimed, Ordering::SeqCst);
+    #[test]
+    fn builder() {
+        let domain: &'static HazPtrDomain = Box::leak(Box::new(
+            HazPtrDomain::builder()
+                .reclaim_threshold(ReclaimThreshold {
+                    fixed: 2,
+                    per_hazard: 0,
+                })
+                .hazard_capacity(3)
+                .retired_shards(4)
+                .build(),
+        ));
+        let slots = domain.snapshot().slots;
+        assert_eq!(slots.len(), 3);
+        assert!(slots.iter().all(|s| !s.active));
 
-        let tail = if let Some(tail) = tail {
-            assert!(!remaining.is_null());
-            tail
-        } else {
-            assert!(remaining.is_null());
-            return reclaimed;
+        // The holders use the slots that are already there.
+        let holders = HazPtrHolderArray::<3>::for_domain(domain);
+        assert_eq!(domain.snapshot().slots.len(), 3);
+        drop(holders);
+
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(domain, 1)));
+        // Safety: x was never shared, and came from a Box.
+        unsafe { x.retire() };
+        assert_eq!(domain.eager_reclaim(false), 1);
+    }
+
+    #[test]
+    fn arc_backed_objects() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let shared = Arc::new(HazPtrObjectWrapper::with_domain(
+            &DOMAIN,
+            CountDrops(Arc::clone(&drops)),
+        ));
+        let x = AtomicPtr::new(HazPtrObjectWrapper::arc_into_raw(Arc::clone(&shared)));
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x holds an Arc pointer, only released by retiring it with drop_arc.
+        unsafe { h.load(&x) }.expect("not null");
+        let old = x.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: old is no longer reachable through x, and came from an Arc.
+        unsafe { old.retire_with_deleter(&deleters::drop_arc) };
+
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        // Our clone keeps the object alive.
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(shared);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn boxed_slices() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let slice: Box<[CountDrops]> = (0..3).map(|_| CountDrops(Arc::clone(&drops))).collect();
+        let slice = Box::into_raw(slice);
+        let x = AtomicPtr::new(slice as *mut CountDrops);
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x points at the first element of a boxed slice, only freed by retiring it.
+        unsafe { h.load(&x) }.expect("not null");
+        x.store(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: slice came from a Box, and is no longer reachable through x.
+        unsafe { DOMAIN.retire_boxed_slice(slice) };
+
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn retire_many() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let nodes: Vec<_> = (0..5)
+            .map(|_| Box::into_raw(Box::new(CountDrops(Arc::clone(&drops)))))
+            .collect();
+        let x = AtomicPtr::new(nodes[2]);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: x holds a valid Box, only freed by retiring it.
+        unsafe { h.load(&x) }.expect("not null");
+        x.store(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: the nodes came from Boxes, and none is reachable through x anymore.
+        unsafe {
+            DOMAIN.retire_many(
+                nodes.iter().map(|&n| n as *mut dyn Drop),
+                &deleters::drop_box,
+            )
         };
 
-        let head_ptr = &self.retired.head;
-        let mut head = head_ptr.load(Ordering::SeqCst);
-        loop {
-            // Safety: we still have exclusive access to remaining, which includes tail.
-            *unsafe { &mut *tail }.next.get_mut() = head;
-            match head_ptr.compare_exchange_weak(
-                head,
-                remaining,
-                Ordering::SeqCst,
-                Ordering::SeqCst,
-            ) {
-                Ok(_) => break,
-                Err(head_now) => {
-                    // Head has changed, try again with that as our next ptr.
-                    head = head_now
-                }
+        // Retiring reclaimed everything but the protected node.
+        assert_eq!(drops.load(Ordering::SeqCst), 4);
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn retire_with_closure() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let pool = Arc::new(Mutex::new(Vec::new()));
+        let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 7)));
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let src = AtomicPtr::new(x);
+        // Safety: src holds a valid Box, only freed by retiring it.
+        unsafe { h.load(&src) }.expect("not null");
+        // Safety: x is no longer reachable by new readers, and the closure frees it as a Box.
+        unsafe {
+            x.retire_with({
+                let pool = Arc::clone(&pool);
+                move |x| pool.lock().unwrap().push(**Box::from_raw(x))
+            })
+        };
+
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        assert!(pool.lock().unwrap().is_empty());
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+        assert_eq!(*pool.lock().unwrap(), [7]);
+    }
+
+    #[test]
+    fn type_chooses_deleter() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        static SLOTS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+        // Lives in a leaked arena rather than its own Box, so it must not be freed as one.
+        struct Pooled(usize);
+        impl Drop for Pooled {
+            fn drop(&mut self) {
+                SLOTS.lock().unwrap().push(self.0);
             }
         }
+        impl HazPtrObject for Pooled {
+            const DELETER: &'static dyn Deleter = &deleters::drop_in_place;
 
-        if !remaining.is_null() && block {
-            // Caller wants to reclaim _everything_, but some were left, so try again.
-            std::thread::yield_now();
-            // NOTE: Allows tail recursion by passing down reclaimed
-            return self.bulk_reclaim(reclaimed, true);
+            fn domain(&self) -> &HazPtrDomain {
+                &DOMAIN
+            }
         }
 
-        reclaimed
+        let arena: &'static mut [Pooled] = Vec::leak((0..2).map(Pooled).collect());
+        for slot in arena.iter_mut() {
+            // Safety: the arena is never accessed again, and drop_in_place doesn't free.
+            unsafe { (slot as *mut Pooled).retire() };
+        }
+        DOMAIN.eager_reclaim(false);
+        let mut slots = SLOTS.lock().unwrap().clone();
+        slots.sort_unstable();
+        assert_eq!(slots, [0, 1]);
     }
-}
 
-impl Drop for HazPtrDomain {
-    fn drop(&mut self) {
-        todo!()
+    #[test]
+    fn cached_hazptrs() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        let first = h.hazptr() as *const HazPtr;
+        drop(h);
+        // Still reserved for this thread.
+        assert!(std::ptr::eq(
+            DOMAIN.hazptrs.head.load(Ordering::SeqCst),
+            first
+        ));
+        assert!(unsafe { &*first }.active.load(Ordering::SeqCst));
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        assert!(std::ptr::eq(h.hazptr(), first));
+        drop(h);
+
+        // Another thread has to allocate its own, and hands it back when it exits.
+        let other = std::thread::spawn(|| HazPtrHolder::for_domain(&DOMAIN).hazptr())
+            .join()
+            .unwrap();
+        assert!(!std::ptr::eq(other, first));
+        assert!(!other.active.load(Ordering::SeqCst));
+        assert_eq!(DOMAIN.snapshot().slots.len(), 2);
     }
-}
 
-struct HazPtrs {
-    head: AtomicPtr<HazPtr>,
-}
+    #[test]
+    fn teardown_with_slots_cached_elsewhere() {
+        let raw = Box::into_raw(Box::new(HazPtrDomain::new()));
+        // Safety: the domain is only freed once the other thread is done with it.
+        let domain: &'static HazPtrDomain = unsafe { &*raw };
+        let (cached_tx, cached_rx) = std::sync::mpsc::channel();
+        let (next_tx, next_rx) = std::sync::mpsc::channel::<&'static HazPtrDomain>();
+        let other = std::thread::spawn(move || {
+            let mut h = HazPtrHolder::for_domain(domain);
+            h.prepare();
+            drop(h);
+            cached_tx.send(()).unwrap();
 
-struct Retired {
-    ptr: *mut dyn Drop,
-    deleter: &'static dyn Deleter,
-    next: AtomicPtr<Retired>,
-}
+            // The slot cached for the dropped domain is never handed out for another one, even
+            // if the other one took its place in memory.
+            let next = next_rx.recv().unwrap();
+            let mut h = HazPtrHolder::for_domain(next);
+            h.prepare();
+            assert_eq!(next.snapshot().slots.len(), 1);
+            // The thread exits with the dropped domain's slot still cached.
+        });
 
-struct RetiredList {
-    head: AtomicPtr<Retired>,
-    count: AtomicUsize,
+        cached_rx.recv().unwrap();
+        // Safety: raw came from a Box, and no holder for the domain is left.
+        let teardown = unsafe { Box::from_raw(raw) }.teardown();
+        assert_eq!(teardown.leaked, 0);
+        let raw = Box::into_raw(Box::new(HazPtrDomain::new()));
+        // Safety: the domain is only freed once the other thread is done with it.
+        next_tx.send(unsafe { &*raw }).unwrap();
+        other.join().unwrap();
+        // Safety: raw came from a Box, and the other thread is gone.
+        drop(unsafe { Box::from_raw(raw) });
+    }
+
+    #[test]
+    fn hazptrs_have_own_cache_lines() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        assert!(std::mem::size_of::<HazPtr>() >= 64);
+        let holders = HazPtrHolderArray::<4>::for_domain(&DOMAIN);
+        let mut node = DOMAIN.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            assert_eq!(node.addr() % std::mem::align_of::<HazPtr>(), 0);
+            // Safety: HazPtrs are never de-allocated while the list isn't shrunk.
+            node = unsafe { &*node }.next.load(Ordering::SeqCst);
+        }
+        drop(holders);
+    }
+
+    #[test]
+    fn budgeted_reclaim() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        DOMAIN.set_step_mode(true);
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        for _ in 0..5 {
+            let x = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN,
+                CountDrops(Arc::clone(&drops)),
+            )));
+            // Safety: x was never shared, and came from a Box.
+            unsafe { x.retire() };
+        }
+
+        let progress = |reclaimed, remaining| ReclaimProgress {
+            reclaimed,
+            remaining,
+        };
+        assert_eq!(DOMAIN.eager_reclaim_budgeted(2), progress(2, 3));
+        assert_eq!(DOMAIN.eager_reclaim_budgeted(2), progress(2, 1));
+        assert_eq!(DOMAIN.eager_reclaim_budgeted(2), progress(1, 0));
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+    }
 }
 
-#[cfg(test)]
-mod tests {
+#[cfg(all(test, loom))]
+mod loom_tests {
     use super::*;
 
-    use std::sync::Arc;
+    use loom::sync::Arc;
+    use loom::thread;
+
     struct CountDrops(Arc<AtomicUsize>);
     impl Drop for CountDrops {
         fn drop(&mut self) {
@@ -404,87 +5539,123 @@ mod tests {
         }
     }
 
-    #[test]
-    fn feels_good() {
-        let drops_42 = Arc::new(AtomicUsize::new(0));
-
-        let x = AtomicPtr::new(Box::into_raw(Box::new(
-            HazPtrObjectWrapper::with_default_domain((42, CountDrops(Arc::clone(&drops_42)))),
-        )));
-
-        // As a reader:
-        let mut h = HazPtrHolder::default();
+    type Object = HazPtrObjectWrapper<CountDrops>;
 
-        // Safety:
-        //
-        //  1. AtomicPtr points to a Box, so is always valid.
-        //  2. Writers to AtomicPtr use HazPtrObject::retire.
-        let my_x = unsafe { h.load(&x) }.expect("not null");
-        // valid:
-        assert_eq!(my_x.0, 42);
-        h.reset();
-        // invalid:
-        // let _: i32 = my_x.0;
+    // Sets bit .1 in .0 when dropped.
+    struct MarkDropped(Arc<AtomicUsize>, usize);
+    impl Drop for MarkDropped {
+        fn drop(&mut self) {
+            self.0.fetch_or(1 << self.1, Ordering::SeqCst);
+        }
+    }
 
-        let my_x = unsafe { h.load(&x) }.expect("not null");
-        // valid:
-        assert_eq!(my_x.0, 42);
-        drop(h);
-        // invalid:
-        // let _: i32 = my_x.0;
+    // Every execution gets a fresh domain; holders and objects only take 'static ones.
+    fn domain() -> &'static HazPtrDomain {
+        Box::leak(Box::new(HazPtrDomain::new()))
+    }
 
-        let mut h = HazPtrHolder::default();
-        let my_x = unsafe { h.load(&x) }.expect("not null");
+    fn object(domain: &'static HazPtrDomain, drops: &Arc<AtomicUsize>) -> *mut Object {
+        Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            domain,
+            CountDrops(Arc::clone(drops)),
+        )))
+    }
 
-        let mut h_tmp = HazPtrHolder::default();
-        let _ = unsafe { h_tmp.load(&x) }.expect("not null");
-        drop(h_tmp);
+    #[test]
+    fn protect_races_retire() {
+        loom::model(|| {
+            let domain = domain();
+            let dropped = Arc::new(AtomicUsize::new(0));
+            let object = |id| {
+                Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                    domain,
+                    MarkDropped(Arc::clone(&dropped), id),
+                )))
+            };
+            let x = Arc::new(AtomicPtr::new(object(0)));
 
-        // As a writer:
-        let drops_9001 = Arc::new(AtomicUsize::new(0));
-        let old = x.swap(
-            Box::into_raw(Box::new(HazPtrObjectWrapper::with_default_domain((
-                9001,
-                CountDrops(Arc::clone(&drops_9001)),
-            )))),
-            std::sync::atomic::Ordering::SeqCst,
-        );
+            let reader = thread::spawn({
+                let x = Arc::clone(&x);
+                let dropped = Arc::clone(&dropped);
+                move || {
+                    let mut h = HazPtrHolder::for_domain(domain);
+                    // Safety: x only holds objects from Boxes, which are retired when swapped
+                    // out.
+                    let protected = unsafe { h.load(&x) }.expect("never null");
+                    // Whichever object we got can't have been reclaimed.
+                    assert_eq!(dropped.load(Ordering::SeqCst) & (1 << protected.1), 0);
+                }
+            });
 
-        let mut h2 = HazPtrHolder::default();
-        let my_x2 = unsafe { h2.load(&x) }.expect("not null");
+            let old = x.swap(object(1), Ordering::SeqCst);
+            // Safety: old is no longer reachable through x.
+            unsafe { old.retire() };
+            domain.eager_reclaim(false);
 
-        assert_eq!(my_x.0, 42);
-        assert_eq!(my_x2.0, 9001);
+            reader.join().unwrap();
+            domain.eager_reclaim(false);
+            assert_eq!(dropped.load(Ordering::SeqCst), 1);
 
-        // Safety:
-        //
-        //  1. The pointer came from Box, so is valid.
-        //  2. The old value is no longer accessible.
-        //  3. The deleter is valid for Box types.
-        unsafe { old.retire(&deleters::drop_box) };
+            // Safety: nobody else has the current object anymore.
+            drop(unsafe { Box::from_raw(x.swap(std::ptr::null_mut(), Ordering::SeqCst)) });
+        });
+    }
 
-        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
-        assert_eq!(my_x.0, 42);
+    #[test]
+    fn concurrent_reclaim_drops_once() {
+        // Two concurrent reclaim passes have too many interleavings to explore exhaustively.
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(|| {
+            let domain = domain();
+            let drops = Arc::new(AtomicUsize::new(0));
 
-        let n = SHARED_DOMAIN.eager_reclaim(false);
-        assert_eq!(n, 0);
+            let retirers: Vec<_> = (0..2)
+                .map(|_| {
+                    let drops = Arc::clone(&drops);
+                    thread::spawn(move || {
+                        let x = object(domain, &drops);
+                        // Safety: x was never shared, and came from a Box.
+                        unsafe { x.retire() };
+                        domain.eager_reclaim(false);
+                    })
+                })
+                .collect();
+            for retirer in retirers {
+                retirer.join().unwrap();
+            }
 
-        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
-        assert_eq!(my_x.0, 42);
+            domain.eager_reclaim(false);
+            assert_eq!(drops.load(Ordering::SeqCst), 2);
+        });
+    }
 
-        drop(h);
-        assert_eq!(drops_42.load(Ordering::SeqCst), 0);
-        // _not_ drop(h2);
+    #[test]
+    fn reset_races_reclaim() {
+        loom::model(|| {
+            let domain = domain();
+            let drops = Arc::new(AtomicUsize::new(0));
+            let x = Arc::new(AtomicPtr::new(object(domain, &drops)));
 
-        let n = SHARED_DOMAIN.eager_reclaim(false);
-        assert_eq!(n, 1);
+            let mut h = HazPtrHolder::for_domain(domain);
+            // Safety: as in protect_races_retire.
+            unsafe { h.load(&x) }.expect("never null");
 
-        assert_eq!(drops_42.load(Ordering::SeqCst), 1);
-        assert_eq!(drops_9001.load(Ordering::SeqCst), 0);
+            let writer = thread::spawn({
+                let x = Arc::clone(&x);
+                move || {
+                    let old = x.swap(std::ptr::null_mut(), Ordering::SeqCst);
+                    // Safety: old is no longer reachable through x.
+                    unsafe { old.retire() };
+                    domain.eager_reclaim(false);
+                }
+            });
 
-        drop(h2);
-        let n = SHARED_DOMAIN.eager_reclaim(false);
-        assert_eq!(n, 0);
-        assert_eq!(drops_9001.load(Ordering::SeqCst), 0);
+            // The object is either reclaimed by the writer after this, or left for us below.
+            h.reset();
+            writer.join().unwrap();
+            domain.eager_reclaim(false);
+            assert_eq!(drops.load(Ordering::SeqCst), 1);
+        });
     }
 }
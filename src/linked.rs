@@ -0,0 +1,178 @@
+use crate::HazPtrObject;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An object that is retired once nothing links to it anymore (link counting).
+///
+/// In a linked structure, a reader that has protected one node usually goes on to load the node
+/// it links to, and has to check that the first node is still linked in before trusting what it
+/// loaded. With link counting, each node counts the links to it, from other nodes and from the
+/// structure itself. A node is only retired once its count drops to zero, and it only releases
+/// its own links to other nodes when it is reclaimed. So as long as a reader protects a node,
+/// every node reachable from it stays alive too, even nodes that have since been unlinked from
+/// the structure, and there is nothing to check.
+///
+/// ```
+/// use haphazard::{HazPtrDomain, HazPtrObject, Linked};
+/// use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+///
+/// static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+///
+/// struct Node {
+///     value: u32,
+///     next: AtomicPtr<Node>,
+///     links: AtomicUsize,
+/// }
+/// impl Drop for Node {
+///     fn drop(&mut self) {}
+/// }
+/// impl HazPtrObject for Node {
+///     fn domain(&self) -> &HazPtrDomain {
+///         &DOMAIN
+///     }
+/// }
+/// impl Linked for Node {
+///     fn links(&self) -> &AtomicUsize {
+///         &self.links
+///     }
+///     fn for_each_link(&mut self, f: &mut dyn FnMut(*mut Self)) {
+///         f(*self.next.get_mut())
+///     }
+/// }
+///
+/// let node = |value, next| {
+///     Box::into_raw(Box::new(Node {
+///         value,
+///         next: AtomicPtr::new(next),
+///         links: AtomicUsize::new(1),
+///     }))
+/// };
+/// // head -> a -> b, each with the one link to it.
+/// let b = node(2, std::ptr::null_mut());
+/// let head = AtomicPtr::new(node(1, b));
+/// // Unlink a. Since a still links to b, b is only retired once a is reclaimed.
+/// let a = head.swap(std::ptr::null_mut(), Ordering::SeqCst);
+/// // Safety: the nodes came from Boxes, and the structure no longer links to a.
+/// unsafe { a.release_link() };
+/// ```
+pub trait Linked: HazPtrObject {
+    /// The number of links to this object.
+    fn links(&self) -> &AtomicUsize;
+
+    /// Call `f` with every object this one links to, or null for missing links.
+    ///
+    /// This is called once the object is reclaimed, to release its links.
+    fn for_each_link(&mut self, f: &mut dyn FnMut(*mut Self));
+
+    /// Count a new link to this object.
+    ///
+    /// # Safety
+    ///
+    /// The object must be valid, and not retired yet, which holds as long as the caller has a
+    /// link to it, or it is protected and still linked in.
+    unsafe fn acquire_link(self: *mut Self) {
+        // Safety: by the safety contract of acquire_link.
+        unsafe { &*self }.links().fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Release a link to this object, and retire it if that was the last one.
+    ///
+    /// Once the object is reclaimed, the links it holds are released the same way.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own one of the links to the object, and not use it after this. Once the
+    /// last link is released, the object must satisfy the requirements of
+    /// [`HazPtrObject::retire`].
+    unsafe fn release_link(self: *mut Self) {
+        if self.is_null() {
+            return;
+        }
+        // Safety: we own a link, so the object hasn't been retired.
+        if unsafe { &*self }.links().fetch_sub(1, Ordering::SeqCst) != 1 {
+            return;
+        }
+        // Safety: the last link is gone, so by the safety contract of release_link the object
+        // can be retired, and the closure frees it with Self::DELETER.
+        unsafe {
+            self.retire_with(|object: *mut Self| {
+                (&mut *object).for_each_link(&mut |child| child.release_link());
+                Self::DELETER.delete(object);
+            })
+        };
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{HazPtrDomain, HazPtrHolder};
+    use std::sync::atomic::AtomicPtr;
+    use std::sync::Arc;
+
+    static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+    struct Node {
+        value: usize,
+        next: AtomicPtr<Node>,
+        links: AtomicUsize,
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl HazPtrObject for Node {
+        fn domain(&self) -> &HazPtrDomain {
+            &DOMAIN
+        }
+    }
+
+    impl Linked for Node {
+        fn links(&self) -> &AtomicUsize {
+            &self.links
+        }
+
+        fn for_each_link(&mut self, f: &mut dyn FnMut(*mut Self)) {
+            f(*self.next.get_mut())
+        }
+    }
+
+    #[test]
+    fn protected_node_keeps_successors() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let node = |value, next| {
+            Box::into_raw(Box::new(Node {
+                value,
+                next: AtomicPtr::new(next),
+                links: AtomicUsize::new(1),
+                drops: Arc::clone(&drops),
+            }))
+        };
+        // head -> a -> b
+        let b = node(2, std::ptr::null_mut());
+        let head = AtomicPtr::new(node(1, b));
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: head only holds nodes that are retired once they are no longer linked to.
+        let a_ref = unsafe { h.load(&head) }.expect("not null");
+        let a = head.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        // Safety: we take over head's link to a.
+        unsafe { a.release_link() };
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // b is still alive through a, without protecting it.
+        // Safety: a is protected, and links to b.
+        let b_ref = unsafe { &*a_ref.next.load(Ordering::SeqCst) };
+        assert_eq!(b_ref.value, 2);
+
+        h.reset();
+        // Reclaiming a retires b, which the next pass reclaims.
+        DOMAIN.eager_reclaim(false);
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,159 @@
+//! Retiring memory-mapped regions.
+//!
+//! Large read-only tables are often memory-mapped, and hot-swapping them needs the same deferred
+//! reclamation as heap objects: the old mapping may only be unmapped once no reader is looking
+//! at it anymore. A [`Mapping`] owns a region and unmaps it when dropped, so it can be wrapped
+//! in [`HazPtrObjectWrapper`](crate::HazPtrObjectWrapper) and kept in an
+//! [`AtomicBox`](crate::AtomicBox) like any other value. Alternatively, readers can protect the
+//! mapped region itself through an `AtomicPtr<u8>`, and writers retire it with
+//! [`HazPtrDomain::retire_mapping`].
+
+use crate::{deleters, HazPtrDomain};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// A memory-mapped region, unmapped on drop.
+#[derive(Debug)]
+pub struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// Safety: a Mapping is just memory; sharing or moving it between threads is no different from
+// a Box<[u8]>.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Map all of `file` read-only.
+    pub fn map_file(file: &File) -> io::Result<Self> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            // mmap rejects empty mappings.
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map an empty file",
+            ));
+        }
+        // Safety: we ask for a fresh mapping of a valid file descriptor.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Take ownership of the mapped region of `len` bytes at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must describe a readable region mapped with `mmap` that nothing else
+    /// unmaps or writes to.
+    pub unsafe fn from_raw(ptr: *mut u8, len: usize) -> Self {
+        Self { ptr, len }
+    }
+
+    /// Give up ownership of the region, returning its address and length.
+    pub fn into_raw(self) -> (*mut u8, usize) {
+        let this = std::mem::ManuallyDrop::new(self);
+        (this.ptr, this.len)
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // Safety: the region is readable and mapped for as long as we exist.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl std::ops::Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // Safety: we own the mapping, and nobody can access it anymore.
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+impl HazPtrDomain {
+    /// Retire the mapped region of `len` bytes at `ptr`, to be unmapped once no reader protects
+    /// `ptr` anymore.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Mapping::from_raw`], and `ptr` must no longer be reachable by new readers.
+    pub unsafe fn retire_mapping(&self, ptr: *mut u8, len: usize) {
+        let mapping = Box::into_raw(Box::new(Mapping { ptr, len }));
+        // Readers protect the region itself, not the Mapping we keep track of it with.
+        self.retire_at(ptr, mapping, &deleters::drop_box);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::HazPtrHolder;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    fn mapped(contents: &[u8]) -> Mapping {
+        let path = std::env::temp_dir().join(format!(
+            "haphazard-mmap-{}-{}",
+            std::process::id(),
+            contents.len()
+        ));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(contents).unwrap();
+        let mapping = Mapping::map_file(&File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        mapping
+    }
+
+    #[test]
+    fn hot_swap_mappings() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let (ptr, len) = mapped(b"old table").into_raw();
+        let table = AtomicPtr::new(ptr);
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: table only holds mappings, which are only unmapped by retiring them on
+        // DOMAIN.
+        let old = unsafe { h.load(&table) }.unwrap() as *const u8;
+
+        let (new_ptr, new_len) = mapped(b"the new table").into_raw();
+        let old_ptr = table.swap(new_ptr, Ordering::SeqCst);
+        // Safety: old_ptr is no longer reachable through table, and was mapped with len bytes.
+        unsafe { DOMAIN.retire_mapping(old_ptr, len) };
+        DOMAIN.eager_reclaim(false);
+
+        // Still mapped, since we're protecting it.
+        // Safety: the region is mapped and protected.
+        assert_eq!(
+            unsafe { std::slice::from_raw_parts(old, len) },
+            b"old table"
+        );
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+
+        // Safety: nobody else has the new mapping.
+        drop(unsafe { Mapping::from_raw(table.into_inner(), new_len) });
+    }
+}
@@ -0,0 +1,117 @@
+use crate::sync::atomic::AtomicPtr;
+use crate::{AtomicBox, HazPtrDomain, HazPtrHolder, HazPtrObject, SHARED_DOMAIN};
+use std::cell::RefCell;
+
+/// Pin the calling thread to the global domain; see [`HazPtrDomain::pin`].
+pub fn pin() -> PinGuard {
+    SHARED_DOMAIN.pin()
+}
+
+/// Protects any number of objects until it is dropped, without the caller managing holders.
+///
+/// Every load takes a holder of its own, whose hazard slot comes from the calling thread's
+/// cache of released slots, and goes back there when the guard is dropped. In a hot loop that
+/// pins, loads a few objects, and unpins again, no slot is ever acquired from the domain.
+///
+/// ```
+/// use haphazard::{AtomicBox, HazPtrObjectWrapper};
+///
+/// let a = AtomicBox::new(HazPtrObjectWrapper::with_default_domain(1));
+/// let b = AtomicBox::new(HazPtrObjectWrapper::with_default_domain(2));
+/// let guard = haphazard::pin();
+/// let (x, y) = (guard.load_box(&a).unwrap(), guard.load_box(&b).unwrap());
+/// assert_eq!(**x + **y, 3);
+/// ```
+pub struct PinGuard {
+    domain: &'static HazPtrDomain,
+    holders: RefCell<Vec<HazPtrHolder>>,
+}
+
+impl HazPtrDomain {
+    /// Start protecting objects of this domain through a [`PinGuard`].
+    pub fn pin(&'static self) -> PinGuard {
+        PinGuard {
+            domain: self,
+            holders: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl PinGuard {
+    /// The domain this guard protects objects of.
+    pub fn domain(&self) -> &'static HazPtrDomain {
+        self.domain
+    }
+
+    /// Protect the object in `src` until the guard is dropped.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], with this guard's domain as the domain objects in `src`
+    /// are retired on.
+    pub unsafe fn load<'g, T>(&'g self, src: &'_ AtomicPtr<T>) -> Option<&'g T> {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        // Safety: by the safety contract of load.
+        let ptr: *const T = unsafe { holder.load(src) }?;
+        self.holders.borrow_mut().push(holder);
+        // Safety: the holder protects ptr until the guard drops it, and it is only moved, never
+        // reset, until then.
+        Some(unsafe { &*ptr })
+    }
+
+    /// Protect the object in `boxed` until the guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `boxed` is not for this guard's domain.
+    pub fn load_box<'g, T: HazPtrObject + Send>(&'g self, boxed: &AtomicBox<T>) -> Option<&'g T> {
+        let mut holder = HazPtrHolder::for_domain(self.domain);
+        let ptr: *const T = boxed.load(&mut holder)?;
+        self.holders.borrow_mut().push(holder);
+        // Safety: as in load.
+        Some(unsafe { &*ptr })
+    }
+
+    /// Stop protecting everything loaded through this guard so far, and keep the guard for
+    /// further loads.
+    pub fn repin(&mut self) {
+        self.holders.get_mut().clear();
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::HazPtrObjectWrapper;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct CountDrops(Arc<AtomicUsize>);
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn guard_protects_until_repinned() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let value = || HazPtrObjectWrapper::with_domain(&DOMAIN, CountDrops(Arc::clone(&drops)));
+        let a = AtomicBox::with_domain(&DOMAIN, value());
+        let b = AtomicBox::with_domain(&DOMAIN, value());
+
+        let mut guard = DOMAIN.pin();
+        let _x = guard.load_box(&a).unwrap();
+        let _y = guard.load_box(&b).unwrap();
+        a.store(value());
+        b.store(value());
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        guard.repin();
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        drop(guard);
+    }
+}
@@ -0,0 +1,177 @@
+//! Escalating reclamation under memory pressure.
+//!
+//! A [`PressureMonitor`] registers a trigger with the kernel's pressure stall information (PSI)
+//! interface, either system-wide or for a cgroup, and reclaims a domain's retired objects from a
+//! background thread whenever the kernel reports memory pressure. While the pressure persists it
+//! keeps scanning at [`PressureConfig::scan_interval`], so the retiring threads don't have to,
+//! and it goes quiet again once no stall has been reported for [`PressureConfig::relax_after`].
+
+use crate::HazPtrDomain;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often the monitor thread checks whether it should stop.
+const STOP_CHECK: Duration = Duration::from_millis(100);
+
+/// Where to watch for memory pressure, and how to react to it.
+#[derive(Debug, Clone)]
+pub struct PressureConfig {
+    /// The PSI file to register the trigger with: `/proc/pressure/memory` for the whole system,
+    /// or a cgroup's `memory.pressure`.
+    pub path: PathBuf,
+    /// Report pressure when tasks were stalled on memory for at least this long...
+    pub stall: Duration,
+    /// ...within a window of this length.
+    ///
+    /// Unprivileged processes can only use windows that are a multiple of two seconds.
+    pub window: Duration,
+    /// How often to reclaim while under pressure.
+    pub scan_interval: Duration,
+    /// Consider the pressure over once no stall has been reported for this long.
+    pub relax_after: Duration,
+}
+
+impl Default for PressureConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("/proc/pressure/memory"),
+            stall: Duration::from_millis(150),
+            window: Duration::from_secs(2),
+            scan_interval: Duration::from_millis(10),
+            relax_after: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Reclaims a domain's retired objects while the kernel reports memory pressure.
+///
+/// Dropping the monitor stops its thread and removes the trigger.
+pub struct PressureMonitor {
+    pressured: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PressureMonitor {
+    /// Register a trigger as described by `config`, and start reclaiming `domain` when it fires.
+    ///
+    /// Fails if the PSI file can't be opened or doesn't accept the trigger, for example because
+    /// the kernel lacks PSI support or the window isn't allowed for this process.
+    pub fn start(domain: &'static HazPtrDomain, config: PressureConfig) -> io::Result<Self> {
+        let mut trigger = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&config.path)?;
+        write!(
+            trigger,
+            "some {} {}\0",
+            config.stall.as_micros(),
+            config.window.as_micros()
+        )?;
+
+        let pressured = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let pressured = Arc::clone(&pressured);
+            let stop = Arc::clone(&stop);
+            std::thread::Builder::new()
+                .name("haphazard-pressure".into())
+                .spawn(move || watch(domain, trigger, config, &pressured, &stop))?
+        };
+        Ok(Self {
+            pressured,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Whether the monitor currently considers the system to be under memory pressure.
+    pub fn under_pressure(&self) -> bool {
+        self.pressured.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PressureMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(
+    domain: &'static HazPtrDomain,
+    trigger: File,
+    config: PressureConfig,
+    pressured: &AtomicBool,
+    stop: &AtomicBool,
+) {
+    let mut last_stall: Option<Instant> = None;
+    while !stop.load(Ordering::Relaxed) {
+        let timeout = if last_stall.is_some() {
+            config.scan_interval.min(STOP_CHECK)
+        } else {
+            STOP_CHECK
+        };
+        let mut fd = libc::pollfd {
+            fd: trigger.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        // Safety: fd is a single, valid pollfd, and trigger outlives the call.
+        let n = unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) };
+        if n < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            break;
+        }
+        if fd.revents & libc::POLLERR != 0 {
+            // The monitored cgroup went away.
+            break;
+        }
+        if fd.revents & libc::POLLPRI != 0 {
+            last_stall = Some(Instant::now());
+            pressured.store(true, Ordering::Relaxed);
+        }
+
+        if let Some(at) = last_stall {
+            if at.elapsed() >= config.relax_after {
+                last_stall = None;
+                pressured.store(false, Ordering::Relaxed);
+            } else {
+                domain.eager_reclaim(false);
+            }
+        }
+    }
+    pressured.store(false, Ordering::Relaxed);
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_and_stop() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let missing = PressureConfig {
+            path: PathBuf::from("/nonexistent/memory.pressure"),
+            ..PressureConfig::default()
+        };
+        assert!(PressureMonitor::start(&DOMAIN, missing).is_err());
+
+        // PSI may be unavailable or locked down where the tests run.
+        if let Ok(monitor) = PressureMonitor::start(&DOMAIN, PressureConfig::default()) {
+            std::thread::sleep(STOP_CHECK);
+            drop(monitor);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Kani proof harnesses for the core reclamation invariants.
+//!
+//! Run with `cargo kani`. Concurrency is modelled by the operation sequences of
+//! [`crate::fuzzing`]: each sequence is one interleaving of the simulated readers and writer, and
+//! Kani checks every sequence of up to [`MAX_OPS`] operations. The model panics if an object is
+//! reclaimed while protected, if an object is reclaimed twice, or if an unprotected retired
+//! object is never reclaimed.
+
+use crate::fuzzing::{run, Op};
+
+const MAX_OPS: usize = 4;
+
+#[kani::proof]
+#[kani::unwind(6)]
+fn reclamation_invariants() {
+    let ops: [Op; MAX_OPS] = kani::any();
+    let len: usize = kani::any();
+    kani::assume(len <= MAX_OPS);
+    run(&ops[..len]);
+}
+
+#[kani::proof]
+#[kani::unwind(6)]
+fn protected_object_survives_reclaim() {
+    let reader: u8 = kani::any();
+    let then: Op = kani::any();
+    run(&[Op::Protect(reader), Op::Swap, then, Op::Reclaim, Op::Step]);
+}
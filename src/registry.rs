@@ -0,0 +1,195 @@
+//! Ids that objects refer to their domain by.
+//!
+//! An object only needs to know its domain to check that it is retired on the right one, so
+//! rather than a reference, a [`HazPtrObjectWrapper`](crate::HazPtrObjectWrapper) keeps a 64-bit
+//! id, which the registry here maps back to the domain. A domain is registered the first time
+//! its id is asked for, and unregistered when it is dropped.
+//!
+//! Ids are never reused, so an object whose domain was dropped can't be mistaken for one of a
+//! domain created later. The low 32 bits of an id pick the domain's slot in the registry, which
+//! is handed to another domain once the domain is dropped, and the high 32 bits count the
+//! registrations so far, which keeps ids increasing. A lookup only succeeds if the domain in the
+//! slot still has the id looked up.
+//!
+//! The registry is not modelled under loom: ids are assigned under a lock, and looked up in a
+//! table that only grows.
+
+use crate::HazPtrDomain;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+/// Identifies a domain; see [`HazPtrDomain::id`].
+///
+/// Ids are never reused, and a domain registered later has a greater id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DomainId(NonZeroU64);
+
+// Segment k of the table holds the domains with indices 2^k - 1 up to 2^(k+1) - 2, so that the
+// table can grow without moving the slots readers look at.
+const SEGMENTS: usize = 32;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const UNALLOCATED: AtomicPtr<AtomicPtr<HazPtrDomain>> = AtomicPtr::new(std::ptr::null_mut());
+static TABLE: [AtomicPtr<AtomicPtr<HazPtrDomain>>; SEGMENTS] = [UNALLOCATED; SEGMENTS];
+
+struct Ids {
+    // The number of domains registered so far.
+    registered: u32,
+    // The lowest index never handed out.
+    next: u32,
+    // Indices of dropped domains.
+    free: Vec<u32>,
+}
+
+static IDS: Mutex<Ids> = Mutex::new(Ids {
+    registered: 0,
+    next: 0,
+    free: Vec::new(),
+});
+
+/// The segment and offset of the slot for `index`.
+fn locate(index: u32) -> (usize, usize) {
+    let k = (index + 1).ilog2();
+    (k as usize, (index + 1 - (1 << k)) as usize)
+}
+
+/// The slot for `index`, if its segment has been allocated.
+fn slot(index: u32) -> Option<&'static AtomicPtr<HazPtrDomain>> {
+    let (k, offset) = locate(index);
+    let segment = TABLE[k].load(Ordering::Acquire);
+    // Safety: segments are leaked once allocated, and offset is within segment k.
+    (!segment.is_null()).then(|| unsafe { &*segment.add(offset) })
+}
+
+impl DomainId {
+    /// The domain with this id.
+    ///
+    /// # Panics
+    ///
+    /// If the domain has been dropped.
+    pub fn domain(self) -> &'static HazPtrDomain {
+        self.lookup().expect("the domain has been dropped")
+    }
+
+    /// The domain with this id, or `None` if it has been dropped.
+    pub(crate) fn lookup(self) -> Option<&'static HazPtrDomain> {
+        let domain = slot(self.index())?.load(Ordering::Acquire);
+        // Safety: domains only register as 'static, and unregister when they are dropped.
+        let domain = unsafe { domain.as_ref() }?;
+        // The slot may have been handed to another domain since.
+        (domain.id.load(Ordering::Acquire) == self.0.get()).then_some(domain)
+    }
+
+    /// Run `f` on the domain with this id, unless it has been dropped, without letting it be
+    /// dropped in the meantime.
+    ///
+    /// Unlike with [`DomainId::lookup`], the domain stays usable for all of `f`, even without
+    /// a `'static` reference to it, since dropping the domain unregisters it first, which waits
+    /// for `f` to return. `f` must not register or unregister domains.
+    pub(crate) fn while_registered<R>(self, f: impl FnOnce(&HazPtrDomain) -> R) -> Option<R> {
+        // Loom can't switch to another thread while this one blocks the others on a std lock,
+        // and doesn't model the registry anyway.
+        #[cfg(not(loom))]
+        let _ids = IDS.lock().unwrap();
+        self.lookup().map(f)
+    }
+
+    // The index of the domain's slot.
+    fn index(self) -> u32 {
+        self.0.get() as u32
+    }
+}
+
+impl HazPtrDomain {
+    /// The id objects of this domain refer to it by.
+    ///
+    /// The domain is registered the first time this is called. No other domain ever gets the
+    /// same id, even once this one is dropped.
+    pub fn id(&'static self) -> DomainId {
+        match NonZeroU64::new(self.id.load(Ordering::Acquire)) {
+            Some(id) => DomainId(id),
+            None => self.register(),
+        }
+    }
+
+    /// The domain's id, if it has been registered.
+    pub(crate) fn registered_id(&self) -> Option<DomainId> {
+        NonZeroU64::new(self.id.load(Ordering::Acquire)).map(DomainId)
+    }
+
+    #[cold]
+    fn register(&'static self) -> DomainId {
+        let mut ids = IDS.lock().unwrap();
+        // Another thread may have registered the domain while we waited for the lock.
+        if let Some(id) = NonZeroU64::new(self.id.load(Ordering::Acquire)) {
+            return DomainId(id);
+        }
+        ids.registered = ids.registered.checked_add(1).expect("too many domains");
+        let index = match ids.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = ids.next;
+                assert_ne!(index, u32::MAX, "too many domains");
+                ids.next += 1;
+                index
+            }
+        };
+        let (k, _) = locate(index);
+        if TABLE[k].load(Ordering::Acquire).is_null() {
+            let segment: Box<[AtomicPtr<HazPtrDomain>]> = (0..1usize << k)
+                .map(|_| AtomicPtr::new(std::ptr::null_mut()))
+                .collect();
+            TABLE[k].store(Box::leak(segment).as_mut_ptr(), Ordering::Release);
+        }
+        let slot = slot(index).expect("just allocated");
+        let id = NonZeroU64::new((ids.registered as u64) << 32 | index as u64)
+            .expect("registered is not zero");
+        // Lookups check the id after loading the domain from its slot, so set it first.
+        self.id.store(id.get(), Ordering::Release);
+        slot.store(self as *const Self as *mut Self, Ordering::Release);
+        DomainId(id)
+    }
+
+    /// Give up the domain's id, if it has one. Once this returns, no call to
+    /// [`DomainId::while_registered`] is using the domain anymore.
+    pub(crate) fn unregister(&mut self) {
+        let Some(id) = NonZeroU64::new(std::mem::take(self.id.get_mut())) else {
+            return;
+        };
+        let mut ids = IDS.lock().unwrap();
+        let index = DomainId(id).index();
+        slot(index)
+            .expect("registered")
+            .store(std::ptr::null_mut(), Ordering::Release);
+        ids.free.push(index);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{HazPtrObject, HazPtrObjectWrapper};
+
+    #[test]
+    fn ids_resolve_to_domains() {
+        let domain: &'static HazPtrDomain = Box::leak(Box::new(HazPtrDomain::new()));
+        let id = domain.id();
+        assert_eq!(domain.id(), id);
+        assert!(std::ptr::eq(id.domain(), domain));
+
+        let object = HazPtrObjectWrapper::with_domain(domain, 1u32);
+        assert!(std::ptr::eq(object.domain(), domain));
+        assert_eq!(std::mem::size_of::<Option<DomainId>>(), 8);
+        drop(object);
+
+        // Safety: the domain came from a Box, and nothing refers to it anymore.
+        drop(unsafe { Box::from_raw(domain as *const HazPtrDomain as *mut HazPtrDomain) });
+        assert!(id.lookup().is_none());
+
+        // A later domain may take over the slot, but never the id.
+        let later: &'static HazPtrDomain = Box::leak(Box::new(HazPtrDomain::new()));
+        assert!(later.id() > id);
+        assert!(id.lookup().is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Strategies for deciding which retired objects are still guarded during a reclamation scan.
+//!
+//! Every scan collects the addresses currently published in the domain's hazards, builds a
+//! [`HazardSet`] from them with the domain's [`ScanStrategy`], and then checks each retired object
+//! against that set. Which strategy is cheapest depends on the number of hazards relative to the
+//! number of retired objects, so it can be chosen per domain with
+//! [`HazPtrDomain::set_scan_strategy`](crate::HazPtrDomain::set_scan_strategy).
+
+use std::collections::HashSet;
+
+/// Builds a [`HazardSet`] from the hazards published at the start of a scan.
+pub trait ScanStrategy: Sync {
+    /// Build a set containing exactly the (non-null) addresses in `hazards`.
+    fn build(&self, hazards: Vec<*mut u8>) -> Box<dyn HazardSet>;
+}
+
+/// The set of addresses guarded during a single scan.
+pub trait HazardSet {
+    /// Returns `true` if `ptr` is guarded.
+    fn contains(&self, ptr: *mut u8) -> bool;
+}
+
+/// Compare every retired object against every hazard.
+///
+/// Has no setup cost, so it's the best choice when there are only a few hazards.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NestedLoop;
+
+impl ScanStrategy for NestedLoop {
+    fn build(&self, hazards: Vec<*mut u8>) -> Box<dyn HazardSet> {
+        Box::new(Unsorted(hazards))
+    }
+}
+
+struct Unsorted(Vec<*mut u8>);
+
+impl HazardSet for Unsorted {
+    fn contains(&self, ptr: *mut u8) -> bool {
+        self.0.contains(&ptr)
+    }
+}
+
+/// Sort the hazards and binary-search them for each retired object.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SortedArray;
+
+impl ScanStrategy for SortedArray {
+    fn build(&self, mut hazards: Vec<*mut u8>) -> Box<dyn HazardSet> {
+        hazards.sort_unstable();
+        hazards.dedup();
+        Box::new(Sorted(hazards))
+    }
+}
+
+struct Sorted(Vec<*mut u8>);
+
+impl HazardSet for Sorted {
+    fn contains(&self, ptr: *mut u8) -> bool {
+        self.0.binary_search(&ptr).is_ok()
+    }
+}
+
+/// Put the hazards in a hash set.
+///
+/// This is the default, and scales well to many hazards and many retired objects alike.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Hashed;
+
+impl ScanStrategy for Hashed {
+    fn build(&self, hazards: Vec<*mut u8>) -> Box<dyn HazardSet> {
+        Box::new(HashedSet(hazards.into_iter().collect()))
+    }
+}
+
+struct HashedSet(HashSet<*mut u8>);
+
+impl HazardSet for HashedSet {
+    fn contains(&self, ptr: *mut u8) -> bool {
+        self.0.contains(&ptr)
+    }
+}
+
+/// Filter retired objects through a small bloom filter before consulting a sorted array.
+///
+/// Most retired objects are not guarded, and the filter rejects the bulk of those with a couple
+/// of bit tests, which makes this a good fit for many retired objects and many hazards.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bloom;
+
+impl ScanStrategy for Bloom {
+    fn build(&self, hazards: Vec<*mut u8>) -> Box<dyn HazardSet> {
+        // Aim for roughly 16 bits per hazard.
+        let bits = (hazards.len() * 16).next_power_of_two().max(64);
+        let mut filter = vec![0u64; bits / 64];
+        for &ptr in &hazards {
+            for bit in BloomFilter::bits(ptr, bits) {
+                filter[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        let mut sorted = hazards;
+        sorted.sort_unstable();
+        sorted.dedup();
+        Box::new(BloomFilter {
+            filter,
+            bits,
+            sorted: Sorted(sorted),
+        })
+    }
+}
+
+struct BloomFilter {
+    filter: Vec<u64>,
+    bits: usize,
+    sorted: Sorted,
+}
+
+impl BloomFilter {
+    fn bits(ptr: *mut u8, bits: usize) -> [usize; 2] {
+        // Fibonacci hashing, with two different multipliers for the two probes.
+        let addr = ptr.addr() as u64;
+        let h1 = addr.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let h2 = addr.wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        let mask = bits as u64 - 1;
+        [((h1 >> 32) & mask) as usize, ((h2 >> 32) & mask) as usize]
+    }
+}
+
+impl HazardSet for BloomFilter {
+    fn contains(&self, ptr: *mut u8) -> bool {
+        Self::bits(ptr, self.bits)
+            .iter()
+            .all(|&bit| self.filter[bit / 64] & (1 << (bit % 64)) != 0)
+            && self.sorted.contains(ptr)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_agree() {
+        let hazards: Vec<*mut u8> = (1..100)
+            .map(|i| std::ptr::without_provenance_mut(i * 24))
+            .collect();
+        let strategies: [&dyn ScanStrategy; 4] = [&NestedLoop, &SortedArray, &Hashed, &Bloom];
+        for strategy in strategies {
+            let set = strategy.build(hazards.clone());
+            for i in 0..2500 {
+                let ptr = std::ptr::without_provenance_mut(i);
+                assert_eq!(set.contains(ptr), i % 24 == 0 && i > 0 && i < 2400, "{}", i);
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+use crate::sync::AtomicMut;
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper, SHARED_DOMAIN};
+use std::cell::UnsafeCell;
+use std::mem::{size_of, MaybeUninit};
+
+/// How many optimistic reads [`SeqLockBox::load`] attempts before falling back to hazard
+/// pointers.
+const OPTIMISTIC_TRIES: usize = 4;
+
+/// A slot for small `Copy` values that readers can usually read without publishing a hazard.
+///
+/// The current value is kept both inline, guarded by a sequence counter, and in a boxed copy
+/// managed by a hazard-pointer domain. Readers first copy the inline value and use the sequence
+/// counter to detect whether a writer interfered. Only if that keeps failing do they fall back to
+/// protecting the boxed copy, which always succeeds no matter how busy writers are.
+///
+/// Writers are serialized with respect to each other, and each write allocates and retires a
+/// box, so this is only worthwhile when writes are rare compared to reads. Those boxes may be
+/// dropped on whichever thread reclaims them, so `T` must be `Send`.
+pub struct SeqLockBox<T: Copy + Send + 'static> {
+    // Odd while a write is in progress.
+    seq: AtomicUsize,
+    inline: UnsafeCell<Words<T>>,
+    boxed: AtomicPtr<HazPtrObjectWrapper<T>>,
+    domain: &'static HazPtrDomain,
+}
+
+/// A value padded out to whole words, which readers and writers of the inline copy access one
+/// atomic word at a time, so that a reader racing with a writer gets a torn copy rather than a
+/// data race.
+#[repr(C)]
+struct Words<T> {
+    _align: [usize; 0],
+    value: MaybeUninit<T>,
+}
+
+impl<T: Copy> Words<T> {
+    // A struct's size is a multiple of its alignment, which is at least that of usize.
+    const LEN: usize = size_of::<Self>() / size_of::<usize>();
+
+    fn new(value: T) -> Self {
+        // Zeroed, so that the padding after value is initialized.
+        let mut words = MaybeUninit::<Self>::zeroed();
+        // Safety: words is valid for writes, and all-zero is a valid Words.
+        unsafe {
+            (*words.as_mut_ptr()).value.write(value);
+            words.assume_init()
+        }
+    }
+}
+
+impl<T: Copy + Send + 'static> SeqLockBox<T> {
+    /// Create a `SeqLockBox` whose boxed copies belong to the global domain.
+    pub fn new(value: T) -> Self {
+        Self::with_domain(&SHARED_DOMAIN, value)
+    }
+
+    /// Create a `SeqLockBox` whose boxed copies belong to `domain`.
+    pub fn with_domain(domain: &'static HazPtrDomain, value: T) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            inline: UnsafeCell::new(Words::new(value)),
+            boxed: AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                domain, value,
+            )))),
+            domain,
+        }
+    }
+
+    /// Read the value without publishing a hazard, if no write interferes.
+    pub fn try_load_optimistic(&self) -> Option<T> {
+        let seq1 = self.seq.load(Ordering::Acquire);
+        if seq1 % 2 == 1 {
+            return None;
+        }
+        // The copy may be torn by a racing writer, so it stays a MaybeUninit until validated.
+        let mut copy = MaybeUninit::<Words<T>>::uninit();
+        let src = self.inline.get().cast::<usize>();
+        let dst = copy.as_mut_ptr().cast::<usize>();
+        for i in 0..Words::<T>::LEN {
+            // Safety: i is in bounds of both, inline is aligned for usize, and is only ever
+            // accessed atomically.
+            unsafe {
+                let word = std::sync::atomic::AtomicUsize::from_ptr(src.add(i));
+                dst.add(i).write(word.load(Ordering::Relaxed));
+            }
+        }
+        fence(Ordering::Acquire);
+        let seq2 = self.seq.load(Ordering::Relaxed);
+        if seq1 == seq2 {
+            // Safety: no writer interfered, so the copy is of a whole value that was stored.
+            Some(unsafe { copy.assume_init().value.assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Read the value, falling back to protecting the boxed copy with `holder` if optimistic
+    /// reads keep being interrupted by writers.
+    ///
+    /// # Panics
+    ///
+    /// If `holder` is not for this box's domain.
+    pub fn load(&self, holder: &mut HazPtrHolder) -> T {
+        for _ in 0..OPTIMISTIC_TRIES {
+            if let Some(value) = self.try_load_optimistic() {
+                return value;
+            }
+            std::hint::spin_loop();
+        }
+        assert!(
+            std::ptr::eq(holder.domain, self.domain),
+            "holder is for a different domain"
+        );
+        // Safety: boxed always holds a valid Box, only deallocated by retiring it in our
+        // domain.
+        let value = **unsafe { holder.load(&self.boxed) }.expect("never null");
+        holder.reset();
+        value
+    }
+
+    /// Replace the value.
+    pub fn store(&self, value: T) {
+        // Become the only writer.
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                seq = self.seq.load(Ordering::Relaxed);
+                continue;
+            }
+            match self
+                .seq
+                .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(seq_now) => seq = seq_now,
+            }
+        }
+        fence(Ordering::Release);
+
+        let words = Words::new(value);
+        let src = (&words as *const Words<T>).cast::<usize>();
+        let dst = self.inline.get().cast::<usize>();
+        for i in 0..Words::<T>::LEN {
+            // Safety: i is in bounds of both, inline is aligned for usize, and is only ever
+            // accessed atomically. We are the only writer, and readers validate what they read
+            // against seq.
+            unsafe {
+                let word = std::sync::atomic::AtomicUsize::from_ptr(dst.add(i));
+                word.store(src.add(i).read(), Ordering::Relaxed);
+            }
+        }
+        let new = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            self.domain,
+            value,
+        )));
+        let old = self.boxed.swap(new, Ordering::SeqCst);
+
+        self.seq.store(seq + 2, Ordering::Release);
+
+        // Safety: old came from a Box, is no longer reachable, and drop_box matches it.
+        unsafe { old.retire() };
+    }
+}
+
+impl<T: Copy + Send + 'static> Drop for SeqLockBox<T> {
+    fn drop(&mut self) {
+        let old = self.boxed.load_mut();
+        // Safety: as in store, and we're going away.
+        unsafe { old.retire() };
+    }
+}
+
+// Safety: readers on any thread get copies of the value, and writers on any thread store it.
+unsafe impl<T: Copy + Send + Sync + 'static> Send for SeqLockBox<T> {}
+unsafe impl<T: Copy + Send + Sync + 'static> Sync for SeqLockBox<T> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_are_never_torn() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let cell = SeqLockBox::with_domain(&DOMAIN, (0u64, 0u64));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=1000 {
+                    cell.store((i, i));
+                }
+            });
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut h = HazPtrHolder::for_domain(&DOMAIN);
+                    for _ in 0..1000 {
+                        let (a, b) = cell.load(&mut h);
+                        assert_eq!(a, b);
+                    }
+                });
+            }
+        });
+        assert_eq!(cell.try_load_optimistic(), Some((1000, 1000)));
+    }
+
+    #[test]
+    fn values_smaller_than_a_word() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let cell = SeqLockBox::with_domain(&DOMAIN, (false, 'a'));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..1000 {
+                    cell.store(if i % 2 == 0 {
+                        (true, 'b')
+                    } else {
+                        (false, 'a')
+                    });
+                }
+            });
+            let mut h = HazPtrHolder::for_domain(&DOMAIN);
+            for _ in 0..1000 {
+                let (flag, c) = cell.load(&mut h);
+                assert_eq!(c, if flag { 'b' } else { 'a' });
+            }
+        });
+        assert_eq!(cell.try_load_optimistic(), Some((false, 'a')));
+    }
+}
@@ -0,0 +1,241 @@
+//! Capturing and restoring the logical state of a domain, for deterministic replay in tests.
+//!
+//! A [`DomainSnapshot`] records which hazard slots are in use and what they protect, and which
+//! objects are waiting to be reclaimed, by address. Restoring it onto a fresh domain recreates
+//! that state with placeholder objects, so a regression test can start right where a bug
+//! report left off instead of replaying a long warm-up:
+//!
+//! ```
+//! use haphazard::HazPtrDomain;
+//!
+//! static RECORDED: HazPtrDomain = HazPtrDomain::new();
+//! static REPLAY: HazPtrDomain = HazPtrDomain::new();
+//!
+//! let snapshot = RECORDED.snapshot();
+//! let holders = REPLAY.restore(&snapshot);
+//! assert_eq!(REPLAY.snapshot().slots, snapshot.slots);
+//! drop(holders);
+//! ```
+
+use crate::{deleters, HazPtrDomain, HazPtrHolder, Retired};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// The logical state of a domain; see [`HazPtrDomain::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DomainSnapshot {
+    /// The domain's hazard slots, most recently allocated first.
+    pub slots: Vec<SlotSnapshot>,
+    /// The objects waiting to be reclaimed, in no particular order.
+    pub retired: Vec<RetiredSnapshot>,
+}
+
+/// A hazard slot in a [`DomainSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotSnapshot {
+    /// Whether a holder owns the slot.
+    pub active: bool,
+    /// The protected address, or 0 if none.
+    pub addr: usize,
+}
+
+/// A retired object in a [`DomainSnapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetiredSnapshot {
+    /// The address readers protect the object by.
+    pub addr: usize,
+    /// The size of the object, not counting any memory it owns indirectly.
+    pub bytes: usize,
+    /// How long the object had been retired when the snapshot was taken.
+    pub age: Duration,
+}
+
+impl DomainSnapshot {
+    /// The number of slots owned by a holder.
+    pub fn active_slots(&self) -> usize {
+        self.slots.iter().filter(|s| s.active).count()
+    }
+}
+
+// Stands in for a retired object when restoring a snapshot.
+struct Placeholder;
+
+impl Drop for Placeholder {
+    fn drop(&mut self) {}
+}
+
+impl HazPtrDomain {
+    /// Capture the current state of this domain.
+    ///
+    /// The snapshot is only consistent if no other thread uses the domain while it is taken.
+    /// Slots that threads keep cached for reuse after their holders were dropped show up as
+    /// inactive.
+    /// Objects in a single-writer domain's private list are only included when called from the
+    /// writer thread.
+    pub fn snapshot(&self) -> DomainSnapshot {
+        let mut snapshot = DomainSnapshot::default();
+
+        let _walk = self.hazptrs.walk();
+        let mut node = self.hazptrs.head.load(Ordering::SeqCst);
+        while !node.is_null() {
+            // Safety: HazPtrs are not de-allocated while we walk the list.
+            let n = unsafe { &*node };
+            snapshot.slots.push(SlotSnapshot {
+                active: n.is_held(),
+                addr: n.ptr.load(Ordering::SeqCst).addr(),
+            });
+            node = n.next.load(Ordering::SeqCst);
+        }
+
+        let now = Instant::now();
+        let mut visit = |n: &Retired| {
+            snapshot.retired.push(RetiredSnapshot {
+                addr: n.addr.addr(),
+                // Safety: retired objects stay valid until they are reclaimed.
+                bytes: std::mem::size_of_val(unsafe { &*n.ptr }),
+                age: now.saturating_duration_since(n.retired_at),
+            });
+        };
+
+        self.flush_batches();
+        // Take the list so that nobody reclaims from it while we look.
+        let steal = self
+            .retired
+            .head
+            .swap(std::ptr::null_mut(), Ordering::SeqCst);
+        let mut tail = std::ptr::null_mut();
+        let mut node = steal;
+        while !node.is_null() {
+            // Safety: we have exclusive access to the stolen list.
+            let n = unsafe { &*node };
+            visit(n);
+            tail = node;
+            node = n.next.load(Ordering::SeqCst);
+        }
+        if !steal.is_null() {
+            self.splice_retired(steal, tail);
+        }
+
+        let backlog = self.backlog.lock().unwrap();
+        let mut node = backlog.head;
+        while !node.is_null() {
+            // Safety: we hold the backlog lock.
+            let n = unsafe { &*node };
+            visit(n);
+            node = n.next.load(Ordering::SeqCst);
+        }
+        drop(backlog);
+
+        if let Some(wheel) = self.quarantine.lock().unwrap().as_ref() {
+            wheel.for_each(&mut visit);
+        }
+
+        if self.is_writer() {
+            // Safety: only the writer thread accesses the private list.
+            let mut node = unsafe { &*self.private.0.get() }.head;
+            while !node.is_null() {
+                // Safety: the private list is ours alone.
+                let n = unsafe { &*node };
+                visit(n);
+                node = n.next.load(Ordering::SeqCst);
+            }
+        }
+
+        snapshot
+    }
+
+    /// Recreate the state captured in `snapshot` on this domain, which should be fresh.
+    ///
+    /// Every slot in the snapshot is allocated, in the same order. Active slots are handed back
+    /// as holders protecting the recorded addresses, and inactive ones are released again.
+    /// Retired objects are replaced by placeholders that readers protect by the recorded
+    /// addresses, and keep their recorded ages. Their recorded sizes are not restored.
+    ///
+    /// The domain is switched to step mode, so that nothing is reclaimed until the test calls
+    /// [`HazPtrDomain::step`] or [`HazPtrDomain::eager_reclaim`].
+    pub fn restore(&'static self, snapshot: &DomainSnapshot) -> Vec<HazPtrHolder> {
+        self.set_step_mode(true);
+
+        // New slots go at the head of the list, so allocate the last one first.
+        let mut holders: Vec<_> = snapshot
+            .slots
+            .iter()
+            .rev()
+            .map(|slot| {
+                let mut holder = HazPtrHolder::for_domain(self);
+                let hazptr = holder.hazptr();
+                if slot.addr != 0 {
+                    hazptr.protect(std::ptr::without_provenance_mut(slot.addr));
+                }
+                (slot.active, holder)
+            })
+            .collect();
+        holders.reverse();
+        let holders = holders
+            .into_iter()
+            .filter_map(|(active, holder)| active.then_some(holder))
+            .collect();
+
+        let now = Instant::now();
+        for r in &snapshot.retired {
+            let ptr = Box::into_raw(Box::new(Placeholder));
+            let retired = self
+                .alloc_retired(
+                    std::ptr::without_provenance_mut(r.addr),
+                    ptr,
+                    &deleters::drop_box,
+                )
+                .unwrap_or_else(|| {
+                    std::alloc::handle_alloc_error(std::alloc::Layout::new::<Retired>())
+                });
+            // Safety: retired was just allocated, and is not shared yet.
+            unsafe { (*retired).retired_at = now.checked_sub(r.age).unwrap_or(now) };
+            self.push_retired(retired);
+        }
+
+        holders
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{HazPtrObject, HazPtrObjectWrapper};
+    use std::sync::atomic::AtomicPtr;
+
+    #[test]
+    fn replay() {
+        static RECORDED: HazPtrDomain = HazPtrDomain::new();
+        static REPLAY: HazPtrDomain = HazPtrDomain::new();
+
+        let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            &RECORDED, 1,
+        ))));
+        let mut h = HazPtrHolder::for_domain(&RECORDED);
+        // Safety: x holds a valid Box, only freed by retiring it on RECORDED.
+        unsafe { h.load(&x) };
+        // And an idle slot.
+        HazPtrHolder::for_domain(&RECORDED).hazptr();
+        // Safety: x is not used after this, and came from a Box.
+        unsafe { x.into_inner().retire() };
+
+        let snapshot = RECORDED.snapshot();
+        assert_eq!(snapshot.slots.len(), 2);
+        assert_eq!(snapshot.active_slots(), 1);
+        assert_eq!(snapshot.retired.len(), 1);
+
+        let mut holders = REPLAY.restore(&snapshot);
+        let replayed = REPLAY.snapshot();
+        assert_eq!(replayed.slots, snapshot.slots);
+        assert_eq!(replayed.retired[0].addr, snapshot.retired[0].addr);
+        assert!(replayed.retired[0].age >= snapshot.retired[0].age);
+
+        // The restored hazard holds back the placeholder, just like the original.
+        assert!(!REPLAY.step());
+        holders[0].reset();
+        assert!(REPLAY.step());
+
+        h.reset();
+        assert_eq!(RECORDED.eager_reclaim(false), 1);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// The highest values a domain's gauges have reached; see [`HazPtrDomain::high_water`].
+///
+/// [`HazPtrDomain::high_water`]: crate::HazPtrDomain::high_water
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Peaks {
+    /// The most hazard slots held by holders at the same time.
+    pub hazards: usize,
+    /// The most objects waiting to be reclaimed at the same time.
+    pub retired: usize,
+    /// The largest combined size of the objects waiting to be reclaimed, not counting any memory
+    /// they own indirectly.
+    pub retired_bytes: usize,
+    /// The longest a single reclamation pass took, including running deleters.
+    pub reclaim_stall: Duration,
+}
+
+/// All-time and windowed peaks of a domain; see [`HazPtrDomain::high_water`].
+///
+/// [`HazPtrDomain::high_water`]: crate::HazPtrDomain::high_water
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HighWater {
+    /// Peaks since the domain was created.
+    pub all_time: Peaks,
+    /// Peaks since the window was last reset with [`HazPtrDomain::reset_high_water_window`].
+    ///
+    /// [`HazPtrDomain::reset_high_water_window`]: crate::HazPtrDomain::reset_high_water_window
+    pub window: Peaks,
+}
+
+/// How long retired objects waited to be reclaimed; see [`HazPtrDomain::reclaim_ages`].
+///
+/// Ages are bucketed by powers of two: bucket 0 counts ages below one microsecond, and bucket
+/// `i > 0` counts ages from 2<sup>i-1</sup> up to 2<sup>i</sup> microseconds. The last bucket
+/// also counts everything longer.
+///
+/// [`HazPtrDomain::reclaim_ages`]: crate::HazPtrDomain::reclaim_ages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgeHistogram {
+    /// The number of objects reclaimed at each age.
+    pub buckets: [u64; AGE_BUCKETS],
+}
+
+/// The number of buckets in an [`AgeHistogram`].
+pub const AGE_BUCKETS: usize = 40;
+
+impl AgeHistogram {
+    /// The ages counted by bucket `i`, from inclusive to exclusive.
+    pub fn bounds(i: usize) -> (Duration, Duration) {
+        let micros = |e: usize| Duration::from_micros(1 << e);
+        match i {
+            0 => (Duration::ZERO, micros(0)),
+            _ if i + 1 == AGE_BUCKETS => (micros(i - 1), Duration::MAX),
+            _ => (micros(i - 1), micros(i)),
+        }
+    }
+
+    /// The number of objects counted.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// An upper bound on the age below which the fraction `q` (between 0 and 1) of objects was
+    /// reclaimed, or `None` if no objects were counted.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let target = ((total as f64) * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                return Some(Self::bounds(i).1);
+            }
+        }
+        Some(Duration::MAX)
+    }
+}
+
+/// Current gauges of a domain, and their peaks.
+pub(crate) struct Stats {
+    hazards: AtomicUsize,
+    retired: AtomicUsize,
+    retired_bytes: AtomicUsize,
+    all_time: PeakCounters,
+    window: PeakCounters,
+    ages: [AtomicU64; AGE_BUCKETS],
+}
+
+struct PeakCounters {
+    hazards: AtomicUsize,
+    retired: AtomicUsize,
+    retired_bytes: AtomicUsize,
+    reclaim_stall_nanos: AtomicU64,
+}
+
+impl PeakCounters {
+    const_fn! {
+        const fn new() -> Self {
+            Self {
+                hazards: AtomicUsize::new(0),
+                retired: AtomicUsize::new(0),
+                retired_bytes: AtomicUsize::new(0),
+                reclaim_stall_nanos: AtomicU64::new(0),
+            }
+        }
+    }
+
+    fn load(&self) -> Peaks {
+        Peaks {
+            hazards: self.hazards.load(Ordering::Relaxed),
+            retired: self.retired.load(Ordering::Relaxed),
+            retired_bytes: self.retired_bytes.load(Ordering::Relaxed),
+            reclaim_stall: Duration::from_nanos(self.reclaim_stall_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Stats {
+    const_fn! {
+        pub(crate) const fn new() -> Self {
+            Self {
+                hazards: AtomicUsize::new(0),
+                retired: AtomicUsize::new(0),
+                retired_bytes: AtomicUsize::new(0),
+                all_time: PeakCounters::new(),
+                window: PeakCounters::new(),
+                ages: atomic_array!(AtomicU64 = AtomicU64::new(0); AGE_BUCKETS),
+            }
+        }
+    }
+
+    pub(crate) fn acquired_hazard(&self) {
+        let now = self.hazards.fetch_add(1, Ordering::Relaxed) + 1;
+        self.all_time.hazards.fetch_max(now, Ordering::Relaxed);
+        self.window.hazards.fetch_max(now, Ordering::Relaxed);
+    }
+
+    pub(crate) fn released_hazard(&self) {
+        self.hazards.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retired(&self, bytes: usize) {
+        let now = self.retired.fetch_add(1, Ordering::Relaxed) + 1;
+        self.all_time.retired.fetch_max(now, Ordering::Relaxed);
+        self.window.retired.fetch_max(now, Ordering::Relaxed);
+        let now = self.retired_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.all_time
+            .retired_bytes
+            .fetch_max(now, Ordering::Relaxed);
+        self.window.retired_bytes.fetch_max(now, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reclaimed(&self, bytes: usize, age: Duration) {
+        self.retired.fetch_sub(1, Ordering::Relaxed);
+        self.retired_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        let micros = age.as_micros();
+        let bucket = if micros == 0 {
+            0
+        } else {
+            // The number of bits needed for micros, so that 1 goes into bucket 1.
+            (128 - micros.leading_zeros() as usize).min(AGE_BUCKETS - 1)
+        };
+        self.ages[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of hazard slots held by holders.
+    pub(crate) fn hazards(&self) -> usize {
+        self.hazards.load(Ordering::Relaxed)
+    }
+
+    /// The number and combined size of objects waiting to be reclaimed.
+    pub(crate) fn backlog(&self) -> (usize, usize) {
+        (
+            self.retired.load(Ordering::Relaxed),
+            self.retired_bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    pub(crate) fn ages(&self) -> AgeHistogram {
+        let mut buckets = [0; AGE_BUCKETS];
+        for (b, a) in buckets.iter_mut().zip(&self.ages) {
+            *b = a.load(Ordering::Relaxed);
+        }
+        AgeHistogram { buckets }
+    }
+
+    pub(crate) fn reclaim_pass(&self, took: Duration) {
+        let nanos = u64::try_from(took.as_nanos()).unwrap_or(u64::MAX);
+        self.all_time
+            .reclaim_stall_nanos
+            .fetch_max(nanos, Ordering::Relaxed);
+        self.window
+            .reclaim_stall_nanos
+            .fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn high_water(&self) -> HighWater {
+        HighWater {
+            all_time: self.all_time.load(),
+            window: self.window.load(),
+        }
+    }
+
+    /// Start a new window at the current gauges, and return the peaks of the old one.
+    pub(crate) fn reset_window(&self) -> Peaks {
+        Peaks {
+            hazards: self
+                .window
+                .hazards
+                .swap(self.hazards.load(Ordering::Relaxed), Ordering::Relaxed),
+            retired: self
+                .window
+                .retired
+                .swap(self.retired.load(Ordering::Relaxed), Ordering::Relaxed),
+            retired_bytes: self.window.retired_bytes.swap(
+                self.retired_bytes.load(Ordering::Relaxed),
+                Ordering::Relaxed,
+            ),
+            reclaim_stall: Duration::from_nanos(
+                self.window.reclaim_stall_nanos.swap(0, Ordering::Relaxed),
+            ),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! The synchronization primitives the crate is built on.
+//!
+//! Under `cfg(loom)`, these are [loom](https://docs.rs/loom)'s instead of the standard library's,
+//! so that the model checker sees, and permutes, every access:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+//! ```
+//!
+//! Loom's types can't be created in const contexts and have no `get_mut`, so constructors are
+//! declared with [`const_fn!`], arrays of atomics are built with [`atomic_array!`], and exclusive
+//! accesses go through [`AtomicMut`].
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Condvar, Mutex, RwLock};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Condvar, Mutex, RwLock};
+
+#[cfg(loom)]
+pub(crate) use loom::thread_local;
+#[cfg(not(loom))]
+pub(crate) use std::thread_local;
+
+pub(crate) mod atomic {
+    #[cfg(loom)]
+    pub(crate) use loom::sync::atomic::{
+        fence, AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+    };
+    #[cfg(not(loom))]
+    pub(crate) use std::sync::atomic::{
+        fence, AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering,
+    };
+}
+
+use atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+
+/// Declare a `const fn` that is a plain `fn` under loom.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
+
+/// `[$init; $n]`, for an array of `$ty`s that can't be copied.
+macro_rules! atomic_array {
+    ($ty:ty = $init:expr; $n:expr) => {{
+        #[cfg(not(loom))]
+        let array = {
+            #[allow(clippy::declare_interior_mutable_const)]
+            const INIT: $ty = $init;
+            [INIT; $n]
+        };
+        #[cfg(loom)]
+        let array = std::array::from_fn(|_| $init);
+        array
+    }};
+}
+
+/// Plain accesses to an atomic we have exclusive access to.
+pub(crate) trait AtomicMut {
+    type Value;
+
+    fn load_mut(&mut self) -> Self::Value;
+
+    fn store_mut(&mut self, value: Self::Value);
+}
+
+macro_rules! impl_atomic_mut {
+    ($($atomic:ident$(<$t:ident>)? => $value:ty),*) => {$(
+        impl$(<$t>)? AtomicMut for $atomic$(<$t>)? {
+            type Value = $value;
+
+            #[cfg(not(loom))]
+            fn load_mut(&mut self) -> $value {
+                *self.get_mut()
+            }
+
+            #[cfg(not(loom))]
+            fn store_mut(&mut self, value: $value) {
+                *self.get_mut() = value;
+            }
+
+            #[cfg(loom)]
+            fn load_mut(&mut self) -> $value {
+                // Safety: nobody else can access the atomic while we have it borrowed mutably.
+                unsafe { self.unsync_load() }
+            }
+
+            #[cfg(loom)]
+            fn store_mut(&mut self, value: $value) {
+                *self = Self::new(value);
+            }
+        }
+    )*};
+}
+
+impl_atomic_mut!(
+    AtomicBool => bool,
+    AtomicPtr<T> => *mut T,
+    AtomicU32 => u32,
+    AtomicU64 => u64,
+    AtomicUsize => usize
+);
@@ -0,0 +1,108 @@
+//! Pointers with tags in their low bits.
+//!
+//! Lock-free lists and queues commonly mark a node for deletion by setting a low bit of the
+//! pointer to it, which is always zero for an aligned object. Such a pointer can't be protected
+//! as is: the hazard has to hold the address of the object, and the tag must not be lost when
+//! the pointer is loaded, swapped or compared. [`HazPtrHolder::load_tagged`] protects the
+//! object a tagged pointer points to and hands back the tag separately, and the functions here
+//! take tagged pointers apart and put them back together for writers.
+//!
+//! Objects must be retired by their untagged pointer.
+
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::HazPtrHolder;
+
+/// The bits of a `*mut T` that are free for a tag, which are the ones below `T`'s alignment.
+pub const fn mask<T>() -> usize {
+    std::mem::align_of::<T>() - 1
+}
+
+/// The tag of `ptr`.
+pub fn tag<T>(ptr: *mut T) -> usize {
+    ptr.addr() & mask::<T>()
+}
+
+/// `ptr` without its tag.
+pub fn untagged<T>(ptr: *mut T) -> *mut T {
+    ptr.map_addr(|addr| addr & !mask::<T>())
+}
+
+/// `ptr` with its tag replaced by `tag`.
+///
+/// # Panics
+///
+/// If `tag` does not fit in [`mask::<T>()`](mask).
+pub fn with_tag<T>(ptr: *mut T, tag: usize) -> *mut T {
+    assert_eq!(
+        tag & !mask::<T>(),
+        0,
+        "tag does not fit below the alignment"
+    );
+    ptr.map_addr(|addr| (addr & !mask::<T>()) | tag)
+}
+
+impl HazPtrHolder {
+    /// Like [`HazPtrHolder::load`], but for a source whose pointers may carry a tag in the bits
+    /// in [`mask::<T>()`](mask).
+    ///
+    /// Protects the object the untagged pointer points to, and returns a reference to it along
+    /// with the tag. The pointer is only considered stable if neither the object nor the tag
+    /// changed while protecting it, so the tag is as current as the reference.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`HazPtrHolder::load`], for the untagged pointers in `src`.
+    pub unsafe fn load_tagged<'l, T>(
+        &'l mut self,
+        src: &'_ AtomicPtr<T>,
+    ) -> (Option<&'l T>, usize) {
+        let hazptr = self.hazptr();
+        let mut ptr1 = src.load(Ordering::SeqCst);
+        loop {
+            hazptr.protect(untagged(ptr1) as *mut u8);
+            crate::barrier::light();
+            let ptr2 = src.load(Ordering::SeqCst);
+            if ptr1 == ptr2 {
+                // Safety: by the safety contract of load_tagged, and since the object is
+                // protected.
+                break (unsafe { Self::as_ref(untagged(ptr1)) }, tag(ptr1));
+            }
+            ptr1 = ptr2;
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{HazPtrDomain, HazPtrObject, HazPtrObjectWrapper};
+
+    #[test]
+    fn marked_nodes() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+        let node = Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(&DOMAIN, 5u64)));
+        let next = AtomicPtr::new(node);
+        assert!(mask::<HazPtrObjectWrapper<u64>>() >= 1);
+
+        let mut h = HazPtrHolder::for_domain(&DOMAIN);
+        // Safety: next only holds (possibly marked) Boxes, only freed by retiring them.
+        let (value, mark) = unsafe { h.load_tagged(&next) };
+        assert_eq!((**value.unwrap(), mark), (5, 0));
+
+        // Mark the node for deletion, keeping the pointer.
+        let _ = next.compare_exchange(node, with_tag(node, 1), Ordering::SeqCst, Ordering::SeqCst);
+        // Safety: as above.
+        let (value, mark) = unsafe { h.load_tagged(&next) };
+        assert_eq!((**value.unwrap(), mark), (5, 1));
+
+        // Unlink it, and retire it by its untagged pointer.
+        let marked = next.swap(std::ptr::null_mut(), Ordering::SeqCst);
+        assert_eq!(tag(marked), 1);
+        // Safety: the node is no longer reachable, and came from a Box.
+        unsafe { untagged(marked).retire() };
+        assert_eq!(DOMAIN.eager_reclaim(false), 0);
+        h.reset();
+        assert_eq!(DOMAIN.eager_reclaim(false), 1);
+    }
+}
@@ -0,0 +1,115 @@
+//! Instrumentation with [`tracing`](https://docs.rs/tracing), behind the `tracing` feature.
+//!
+//! Holders acquiring slots, protecting and resetting, and retirements are traced as events at
+//! the `TRACE` level. Reclamation passes get a `DEBUG` span, with an event counting what they
+//! reclaimed. Without the feature, the macros here expand to nothing.
+
+/// Emit a `TRACE` event, if the `tracing` feature is enabled.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+/// Emit a `DEBUG` event, if the `tracing` feature is enabled.
+macro_rules! debug_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Enter a `DEBUG` span until the end of the enclosing block, if the `tracing` feature is
+/// enabled.
+macro_rules! debug_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+#[cfg(all(test, not(loom), feature = "tracing"))]
+mod tests {
+    use crate::{HazPtrDomain, HazPtrHolder, HazPtrObject, HazPtrObjectWrapper};
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // Collects the messages of events, and the names of spans.
+    #[derive(Default)]
+    struct Collect {
+        seen: Arc<Mutex<Vec<String>>>,
+        spans: AtomicUsize,
+    }
+
+    struct Message<'a>(&'a mut String);
+
+    impl Visit for Message<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                *self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for Collect {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(span.metadata().name().to_owned());
+            Id::from_u64(self.spans.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = String::new();
+            event.record(&mut Message(&mut message));
+            self.seen.lock().unwrap().push(message);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn traces_protection_and_reclamation() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let collect = Collect::default();
+        let seen = Arc::clone(&collect.seen);
+        tracing::subscriber::with_default(collect, || {
+            let x = AtomicPtr::new(Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+                &DOMAIN, 1,
+            ))));
+            let mut h = HazPtrHolder::for_domain(&DOMAIN);
+            // Safety: x holds a valid Box, only freed by retiring it.
+            let _ = unsafe { h.load(&x) };
+            h.reset();
+            let old = x.swap(std::ptr::null_mut(), Ordering::SeqCst);
+            // Safety: old came from a Box, and is no longer reachable through x.
+            unsafe { old.retire() };
+        });
+        let seen = seen.lock().unwrap();
+        for expected in [
+            "acquired hazard slot",
+            "protected",
+            "reset",
+            "retired",
+            "reclaim",
+            "reclaim pass",
+        ] {
+            assert!(seen.iter().any(|s| s == expected), "{}", expected);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::sync::atomic::{AtomicPtr, Ordering};
+use crate::{HazPtrDomain, HazPtrHolder, HazPtrHolderArray};
+
+/// Walks the nodes of a linked structure hand over hand, keeping each node protected while it is
+/// current.
+///
+/// Moving from one node to the next takes more than protecting the next node: once the current
+/// node is unlinked, a writer may retire the node after it without touching the current node's
+/// `next` pointer, so the next node can only be trusted if the current one was still linked in
+/// after it was protected. A traversal therefore protects three nodes at a time, the previous one
+/// (which holds the link to the current one), the current one, and the next one, and checks the
+/// link to the current node at each hop. The three hazard slots take turns, so a hop never
+/// acquires a slot.
+///
+/// That check alone can't tell whether the previous node is itself still linked in: if it is
+/// unlinked, then the current node, and then the next one is unlinked and retired, the links of
+/// the first two still point where they did. Writers must therefore mark a node before unlinking
+/// it, as in Harris and Michael's lists (and [`collections::List`](crate::collections)), by
+/// setting the low bit of its link with [`tagged::with_tag`](crate::tagged::with_tag), and never
+/// change a marked link again. A marked link never matches the node it points to, so a
+/// traversal only moves on from a node whose predecessor was not yet marked, and hence linked in.
+///
+/// If the current node, or the previous one, turns out to have been unlinked,
+/// [`Traverse::advance`] returns [`Unlinked`], and the traversal has to start over from the head
+/// with [`Traverse::restart`].
+///
+/// ```
+/// use haphazard::{HazPtrDomain, HazPtrObjectWrapper, Traverse};
+/// use std::sync::atomic::AtomicPtr;
+///
+/// static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+///
+/// struct Node {
+///     value: u32,
+///     next: AtomicPtr<HazPtrObjectWrapper<Node>>,
+/// }
+///
+/// let node = |value, next| {
+///     Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+///         &DOMAIN,
+///         Node {
+///             value,
+///             next: AtomicPtr::new(next),
+///         },
+///     )))
+/// };
+/// let head = AtomicPtr::new(node(1, node(2, std::ptr::null_mut())));
+///
+/// // Safety: the nodes are only freed by retiring them on DOMAIN.
+/// let mut walk = unsafe { Traverse::new(&DOMAIN, &head, |node| &node.next) };
+/// let mut sum = 0;
+/// while let Some(node) = walk.current() {
+///     sum += node.value;
+///     if walk.advance().is_err() {
+///         sum = 0;
+///         walk.restart();
+///     }
+/// }
+/// assert_eq!(sum, 3);
+/// # drop(walk);
+/// # let mut next = head.into_inner();
+/// # while !next.is_null() {
+/// #     let node = unsafe { Box::from_raw(next) };
+/// #     next = node.next.load(std::sync::atomic::Ordering::SeqCst);
+/// # }
+/// ```
+pub struct Traverse<'a, T> {
+    holders: [HazPtrHolder; 3],
+    // Which holders protect the previous node and the current one; the third is the spare.
+    prev: usize,
+    cur: usize,
+    head: &'a AtomicPtr<T>,
+    next: for<'n> fn(&'n T) -> &'n AtomicPtr<T>,
+    // The link current was loaded from: the head, or the `next` of the previous node.
+    link: *const AtomicPtr<T>,
+    current: *mut T,
+}
+
+/// Returned by [`Traverse::advance`] when the current node was unlinked, after which its
+/// successors can't be trusted anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unlinked(());
+
+impl std::fmt::Display for Unlinked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the current node was unlinked")
+    }
+}
+
+impl std::error::Error for Unlinked {}
+
+impl<'a, T> Traverse<'a, T> {
+    /// Start a traversal at the node `head` points to, moving from node to node through the link
+    /// that `next` selects.
+    ///
+    /// # Safety
+    ///
+    /// `head`, and every link `next` selects in a node reachable from it, must satisfy the safety
+    /// contract of [`HazPtrHolder::load_tagged`] for `domain`. A node must only be unlinked once
+    /// its link has been marked with a tag of 1, and a marked link must never change again.
+    pub unsafe fn new(
+        domain: &'static HazPtrDomain,
+        head: &'a AtomicPtr<T>,
+        next: for<'n> fn(&'n T) -> &'n AtomicPtr<T>,
+    ) -> Self {
+        let mut traverse = Self {
+            holders: HazPtrHolderArray::<3>::for_domain(domain).into_holders(),
+            prev: 0,
+            cur: 1,
+            head,
+            next,
+            link: head,
+            current: std::ptr::null_mut(),
+        };
+        traverse.restart();
+        traverse
+    }
+
+    /// The current node, or `None` once the traversal has reached the end.
+    pub fn current(&self) -> Option<&T> {
+        // Safety: current is null, or protected by holders[cur] until we move on, which takes
+        // &mut self.
+        unsafe { self.current.as_ref() }
+    }
+
+    /// Move on to the node after the current one, and return it.
+    ///
+    /// Once the traversal has reached the end, this keeps returning `Ok(None)`. If the current
+    /// node has been unlinked, or the previous one marked, nothing changes, and `Err(Unlinked)`
+    /// is returned.
+    pub fn advance(&mut self) -> Result<Option<&T>, Unlinked> {
+        let Some(current) = self.current() else {
+            return Ok(None);
+        };
+        let link = (self.next)(current) as *const AtomicPtr<T>;
+        let spare = 3 - self.prev - self.cur;
+        // Safety: current is protected, so its link can be read, and by the safety contract of
+        // new. A marked link still leads to the rest of the list.
+        let (next, _) = unsafe { self.holders[spare].load_tagged(&*link) };
+        let next = next.map_or(std::ptr::null_mut(), |next| next as *const T as *mut T);
+        // An unmarked link to current means the previous node was still linked in, so current
+        // was too, and next was reachable when it was protected.
+        // Safety: the link is the head, or in the previous node, which is still protected.
+        if unsafe { &*self.link }.load(Ordering::SeqCst) != self.current {
+            self.holders[spare].reset();
+            return Err(Unlinked(()));
+        }
+        self.holders[self.prev].reset();
+        (self.prev, self.cur) = (self.cur, spare);
+        self.link = link;
+        self.current = next;
+        Ok(self.current())
+    }
+
+    /// Start over from the head, and return the first node.
+    pub fn restart(&mut self) -> Option<&T> {
+        self.holders[self.prev].reset();
+        self.link = self.head;
+        // Safety: by the safety contract of new.
+        self.current = unsafe { self.holders[self.cur].load(self.head) }
+            .map_or(std::ptr::null_mut(), |first| first as *const T as *mut T);
+        self.current()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::tagged::with_tag;
+    use crate::{HazPtrObject, HazPtrObjectWrapper};
+
+    static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+
+    struct Node {
+        value: usize,
+        next: AtomicPtr<HazPtrObjectWrapper<Node>>,
+    }
+
+    type NodePtr = *mut HazPtrObjectWrapper<Node>;
+
+    fn node(domain: &'static HazPtrDomain, value: usize, next: NodePtr) -> NodePtr {
+        Box::into_raw(Box::new(HazPtrObjectWrapper::with_domain(
+            domain,
+            Node {
+                value,
+                next: AtomicPtr::new(next),
+            },
+        )))
+    }
+
+    // Mark n, and unlink it from link.
+    fn unlink(link: &AtomicPtr<HazPtrObjectWrapper<Node>>, n: NodePtr) {
+        // Safety: n is still linked in, so not yet retired.
+        let next = unsafe { &*n }.next.load(Ordering::SeqCst);
+        // Safety: as above.
+        unsafe { &*n }
+            .next
+            .store(with_tag(next, 1), Ordering::SeqCst);
+        link.store(next, Ordering::SeqCst);
+    }
+
+    fn free_list(head: AtomicPtr<HazPtrObjectWrapper<Node>>) {
+        let mut next = head.into_inner();
+        while !next.is_null() {
+            // Safety: the remaining nodes came from Boxes, and are not shared anymore.
+            let node = unsafe { Box::from_raw(next) };
+            next = node.next.load(Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn stops_at_unlinked_node() {
+        let node = |value, next| node(&DOMAIN, value, next);
+        // head -> 1 -> 2 -> 3
+        let third = node(3, std::ptr::null_mut());
+        let second = node(2, third);
+        let head = AtomicPtr::new(node(1, second));
+
+        // Safety: the nodes are only freed by retiring them on DOMAIN.
+        let mut walk = unsafe { Traverse::new(&DOMAIN, &head, |node| &node.next) };
+        assert_eq!(walk.current().map(|node| node.value), Some(1));
+        assert_eq!(walk.advance().unwrap().map(|node| node.value), Some(2));
+
+        // Unlink 2 while it is current.
+        // Safety: the first node is protected by the traversal.
+        let first = unsafe { &*head.load(Ordering::SeqCst) };
+        unlink(&first.next, second);
+        // Safety: second came from a Box, and is no longer linked in.
+        unsafe { second.retire() };
+        assert!(walk.advance().is_err());
+        // The current node stays protected.
+        DOMAIN.eager_reclaim(false);
+        assert_eq!(walk.current().map(|node| node.value), Some(2));
+
+        assert_eq!(walk.restart().map(|node| node.value), Some(1));
+        assert_eq!(walk.advance().unwrap().map(|node| node.value), Some(3));
+        assert!(walk.advance().unwrap().is_none());
+        assert!(walk.advance().unwrap().is_none());
+        drop(walk);
+        free_list(head);
+    }
+
+    #[test]
+    fn stops_after_unlinked_previous_node() {
+        static DOMAIN: HazPtrDomain = HazPtrDomain::new();
+        let node = |value, next| node(&DOMAIN, value, next);
+        // head -> 1 -> 2 -> 3 -> 4
+        let fourth = node(4, std::ptr::null_mut());
+        let third = node(3, fourth);
+        let second = node(2, third);
+        let first = node(1, second);
+        let head = AtomicPtr::new(first);
+
+        // Safety: the nodes are only freed by retiring them on DOMAIN.
+        let mut walk = unsafe { Traverse::new(&DOMAIN, &head, |node| &node.next) };
+        assert_eq!(walk.advance().unwrap().map(|node| node.value), Some(2));
+
+        // Unlink the previous node, then the current one, and then the one after it, which
+        // nothing protects, so it is freed right away. The links of 1 and 2 still point at 2
+        // and 3, but are marked.
+        for n in [first, second, third] {
+            unlink(&head, n);
+            // Safety: n came from a Box, and is no longer linked in.
+            unsafe { n.retire() };
+        }
+        DOMAIN.eager_reclaim(false);
+        let retired = DOMAIN.snapshot().retired;
+        assert_eq!(retired.len(), 2);
+        assert!(retired.iter().all(|r| r.addr != third.addr()));
+
+        assert!(walk.advance().is_err());
+        assert_eq!(walk.current().map(|node| node.value), Some(2));
+        assert_eq!(walk.restart().map(|node| node.value), Some(4));
+        drop(walk);
+        free_list(head);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::sync::AtomicMut;
+use crate::Retired;
+use std::time::{Duration, Instant};
+
+/// The number of slots in a [`TimerWheel`].
+const SLOTS: usize = 256;
+
+/// Holds retired objects until they have been retired for at least a fixed delay.
+///
+/// Objects are hashed into slots by the tick at which they become due, so releasing the due
+/// objects only visits the slots for the ticks that passed since the last release, rather than
+/// every object that is waiting. The delay spans at most half the wheel, so a slot mostly holds
+/// objects that are due at the same time; objects that hash to a visited slot but are due a
+/// full rotation later simply stay put.
+pub(crate) struct TimerWheel {
+    delay: Duration,
+    origin: Instant,
+    resolution: Duration,
+    // The first tick whose slot has not been visited yet.
+    cursor: u64,
+    slots: Box<[*mut Retired]>,
+    len: usize,
+}
+
+// Safety: the nodes are only accessed through the Mutex the wheel lives in.
+unsafe impl Send for TimerWheel {}
+
+/// A list of retired objects released from a [`TimerWheel`], newest first like the retired list.
+pub(crate) struct Released {
+    pub(crate) head: *mut Retired,
+    pub(crate) tail: *mut Retired,
+    pub(crate) count: usize,
+}
+
+impl Released {
+    fn link(mut nodes: Vec<*mut Retired>) -> Self {
+        // Safety: the wheel has exclusive access to its nodes.
+        nodes.sort_by_key(|&node| std::cmp::Reverse(unsafe { &*node }.retired_at));
+        let mut head = std::ptr::null_mut();
+        for &node in nodes.iter().rev() {
+            // Safety: as above.
+            unsafe { (*node).next.store_mut(head) };
+            head = node;
+        }
+        Self {
+            head,
+            tail: nodes.last().copied().unwrap_or(std::ptr::null_mut()),
+            count: nodes.len(),
+        }
+    }
+}
+
+impl TimerWheel {
+    pub(crate) fn new(delay: Duration) -> Self {
+        let resolution = (delay / (SLOTS as u32 / 2)).max(Duration::from_micros(1));
+        Self {
+            delay,
+            origin: Instant::now(),
+            resolution,
+            cursor: 0,
+            slots: vec![std::ptr::null_mut(); SLOTS].into_boxed_slice(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// The number of objects waiting in the wheel.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    fn tick(&self, at: Instant) -> u64 {
+        let since = at.saturating_duration_since(self.origin);
+        (since.as_nanos() / self.resolution.as_nanos()) as u64
+    }
+
+    // The first tick at which the whole delay has passed for n.
+    fn due(&self, n: &Retired) -> u64 {
+        self.tick(n.retired_at + self.delay) + 1
+    }
+
+    /// Hold on to `node` until its delay has passed.
+    pub(crate) fn schedule(&mut self, node: *mut Retired) {
+        // Safety: the caller hands us exclusive access to node.
+        let n = unsafe { &mut *node };
+        // Anything due already goes into the next slot we visit.
+        let due = self.due(n).max(self.cursor);
+        let slot = &mut self.slots[(due % SLOTS as u64) as usize];
+        n.next.store_mut(*slot);
+        *slot = node;
+        self.len += 1;
+    }
+
+    /// Remove and return every object that has been waiting for at least the delay by `now`.
+    pub(crate) fn advance(&mut self, now: Instant) -> Released {
+        let mut released = Vec::new();
+        let now = self.tick(now);
+        if now < self.cursor {
+            return Released::link(released);
+        }
+        // After a full rotation, every slot has been visited.
+        let ticks = (now - self.cursor + 1).min(SLOTS as u64);
+        for tick in self.cursor..self.cursor + ticks {
+            let slot = (tick % SLOTS as u64) as usize;
+            let mut node = std::mem::replace(&mut self.slots[slot], std::ptr::null_mut());
+            while !node.is_null() {
+                // Safety: we have exclusive access to the nodes in the wheel.
+                let n = unsafe { &mut *node };
+                let this = node;
+                node = n.next.load_mut();
+                if self.due(n) <= now {
+                    released.push(this);
+                } else {
+                    n.next.store_mut(self.slots[slot]);
+                    self.slots[slot] = this;
+                }
+            }
+        }
+        self.cursor = now + 1;
+        self.len -= released.len();
+        Released::link(released)
+    }
+
+    /// Remove and return every object in the wheel, due or not.
+    pub(crate) fn flush(&mut self) -> Released {
+        let mut released = Vec::with_capacity(self.len);
+        for slot in self.slots.iter_mut() {
+            let mut node = std::mem::replace(slot, std::ptr::null_mut());
+            while !node.is_null() {
+                // Safety: we have exclusive access to the nodes in the wheel.
+                let next = unsafe { &mut *node }.next.load_mut();
+                released.push(node);
+                node = next;
+            }
+        }
+        self.len = 0;
+        Released::link(released)
+    }
+
+    /// Call `f` on every object in the wheel.
+    pub(crate) fn for_each(&self, mut f: impl FnMut(&Retired)) {
+        for &slot in self.slots.iter() {
+            let mut node = slot;
+            while !node.is_null() {
+                // Safety: we have exclusive access to the nodes in the wheel.
+                let n = unsafe { &*node };
+                f(n);
+                node = n.next.load(std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+}